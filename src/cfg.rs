@@ -0,0 +1,388 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::dump::type_str;
+use ir::lower_ast::frontend as s;
+use std::fmt::Write;
+
+/// Control-flow graph of every function as a Graphviz digraph, for `--emit=cfg`.
+/// Each function is a cluster of basic blocks split the way lowering splits
+/// them: at `if`, loop and `switch` heads, labels, and after jumps. Edges out
+/// of a branch are labeled `T`/`F`, and out of a switch with the case value.
+pub fn dot_program(p: &s::Program) -> String {
+    let mut out = String::from("digraph cfg {\n  node [shape=box, fontname=\"monospace\"];\n");
+    for func in &p.functions {
+        let g = Graph::build(&func.body);
+        let _ = writeln!(out, "  subgraph \"cluster_{}\" {{", func.name);
+        let _ = writeln!(out, "    label=\"{}\";", escape(&func.name));
+        // number the printed blocks consecutively
+        let mut ids = vec![0; g.blocks.len()];
+        for (id, i) in (0..g.blocks.len()).filter(|&i| g.live[i]).enumerate() {
+            ids[i] = id;
+        }
+        for (i, b) in g.blocks.iter().enumerate() {
+            if !g.live[i] {
+                continue;
+            }
+            let mut label = match i {
+                ENTRY => "entry".to_string(),
+                EXIT => "exit".to_string(),
+                _ => format!("bb{}", ids[i]),
+            };
+            label += "\\l";
+            for line in &b.lines {
+                label += &escape(line);
+                label += "\\l";
+            }
+            let _ = writeln!(out, "    \"{}.{}\" [label=\"{}\"];", func.name, ids[i], label);
+        }
+        for (i, b) in g.blocks.iter().enumerate() {
+            if !g.live[i] {
+                continue;
+            }
+            for (to, label) in &b.succs {
+                let (from, to) = (ids[i], ids[*to]);
+                let _ = write!(out, "    \"{}.{}\" -> \"{}.{}\"", func.name, from, func.name, to);
+                if label.is_empty() {
+                    out += ";\n";
+                } else {
+                    let _ = writeln!(out, " [label=\"{}\"];", escape(label));
+                }
+            }
+        }
+        out += "  }\n";
+    }
+    out += "}\n";
+    out
+}
+
+const ENTRY: usize = 0;
+const EXIT: usize = 1;
+
+#[derive(Default)]
+struct Block {
+    lines: Vec<String>,
+    succs: Vec<(usize, String)>,
+}
+
+struct Graph {
+    blocks: Vec<Block>,
+    // block that statements are currently appended to
+    cur: usize,
+    // targets of `break`/`continue` in the enclosing loops and switches, innermost last
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+    labels: Vec<(String, usize)>,
+    gotos: Vec<(usize, String)>,
+    // blocks worth printing: reachable from the entry or from dead code
+    live: Vec<bool>,
+}
+
+impl Graph {
+    fn build(body: &[s::Stmt]) -> Graph {
+        let mut g = Graph {
+            blocks: vec![Block::default(), Block::default()],
+            cur: ENTRY,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+            labels: Vec::new(),
+            gotos: Vec::new(),
+            live: Vec::new(),
+        };
+        g.stmts(body);
+        g.edge(g.cur, EXIT, "");
+        for (from, label) in std::mem::take(&mut g.gotos) {
+            if let Some(&(_, to)) = g.labels.iter().find(|(l, _)| *l == label) {
+                g.edge(from, to, "");
+            }
+        }
+        g.mark_live();
+        g
+    }
+
+    fn new_block(&mut self) -> usize {
+        self.blocks.push(Block::default());
+        self.blocks.len() - 1
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: &str) {
+        self.blocks[from].succs.push((to, label.to_string()));
+    }
+
+    // ends the current block with a jump to `to`; what follows is unreachable until a label
+    fn jump(&mut self, to: usize) {
+        self.edge(self.cur, to, "");
+        self.cur = self.new_block();
+    }
+
+    fn line(&mut self, text: String) {
+        self.blocks[self.cur].lines.push(text);
+    }
+
+    fn mark_live(&mut self) {
+        let mut live = vec![false; self.blocks.len()];
+        let mut work: Vec<usize> = (0..self.blocks.len())
+            .filter(|&i| i == ENTRY || !self.blocks[i].lines.is_empty())
+            .collect();
+        while let Some(b) = work.pop() {
+            if !std::mem::replace(&mut live[b], true) {
+                work.extend(self.blocks[b].succs.iter().map(|(to, _)| *to));
+            }
+        }
+        self.live = live;
+    }
+
+    fn stmts(&mut self, body: &[s::Stmt]) {
+        for st in body {
+            self.stmt(st);
+        }
+    }
+
+    fn stmt(&mut self, st: &s::Stmt) {
+        match st {
+            s::Stmt::VarDecl { name, ty, init } => {
+                let mut text = format!("{} {}", type_str(ty), name);
+                if let Some(init) = init {
+                    text += &format!(" = {}", expr_str(init));
+                }
+                self.line(text);
+            }
+            s::Stmt::ConstDecl { name, ty, init } => {
+                self.line(format!("const {} {} = {}", type_str(ty), name, expr_str(init)));
+            }
+            s::Stmt::Assign { name, value } => self.line(format!("{} = {}", name, expr_str(value))),
+            s::Stmt::Store { target, value } => {
+                self.line(format!("{} = {}", expr_str(target), expr_str(value)));
+            }
+            s::Stmt::ExprStmt(e) => self.line(expr_str(e)),
+            s::Stmt::If { cond, then_body, else_body } => {
+                self.line(format!("if {}", expr_str(cond)));
+                let (head, join) = (self.cur, self.new_block());
+                let then_block = self.new_block();
+                self.edge(head, then_block, "T");
+                self.cur = then_block;
+                self.stmts(then_body);
+                self.edge(self.cur, join, "");
+                if else_body.is_empty() {
+                    self.edge(head, join, "F");
+                } else {
+                    let else_block = self.new_block();
+                    self.edge(head, else_block, "F");
+                    self.cur = else_block;
+                    self.stmts(else_body);
+                    self.edge(self.cur, join, "");
+                }
+                self.cur = join;
+            }
+            s::Stmt::While { cond, body } => {
+                let head = self.new_block();
+                self.edge(self.cur, head, "");
+                let (body_block, exit) = (self.new_block(), self.new_block());
+                self.cur = head;
+                self.line(format!("while {}", expr_str(cond)));
+                self.edge(head, body_block, "T");
+                if !matches!(cond, s::Expr::Lit(s::Lit::Bool(true))) {
+                    self.edge(head, exit, "F");
+                }
+                self.breaks.push(exit);
+                self.continues.push(head);
+                self.cur = body_block;
+                self.stmts(body);
+                self.edge(self.cur, head, "");
+                self.breaks.pop();
+                self.continues.pop();
+                self.cur = exit;
+            }
+            s::Stmt::Switch { value, cases } => {
+                self.line(format!("switch {}", expr_str(value)));
+                let (head, exit) = (self.cur, self.new_block());
+                if cases.iter().all(|c| c.label.is_some()) {
+                    self.edge(head, exit, "default");
+                }
+                self.breaks.push(exit);
+                // the first arm's entry isn't reachable by falling through
+                self.cur = self.new_block();
+                for case in cases {
+                    let arm = self.new_block();
+                    self.edge(self.cur, arm, "");
+                    let label = match case.label {
+                        Some(v) => v.to_string(),
+                        None => "default".to_string(),
+                    };
+                    self.edge(head, arm, &label);
+                    self.cur = arm;
+                    self.stmts(&case.body);
+                }
+                self.edge(self.cur, exit, "");
+                self.breaks.pop();
+                self.cur = exit;
+            }
+            s::Stmt::Break => {
+                if let Some(&to) = self.breaks.last() {
+                    self.line("break".to_string());
+                    self.jump(to);
+                }
+            }
+            s::Stmt::Continue => {
+                if let Some(&to) = self.continues.last() {
+                    self.line("continue".to_string());
+                    self.jump(to);
+                }
+            }
+            s::Stmt::Goto(label) => {
+                self.line(format!("goto {}", label));
+                self.gotos.push((self.cur, label.clone()));
+                self.cur = self.new_block();
+            }
+            s::Stmt::Label(label) => {
+                let target = self.new_block();
+                self.edge(self.cur, target, "");
+                self.cur = target;
+                self.line(format!("{}:", label));
+                self.labels.push((label.clone(), target));
+            }
+            s::Stmt::Return(value) => {
+                match value {
+                    Some(e) => self.line(format!("return {}", expr_str(e))),
+                    None => self.line("return".to_string()),
+                }
+                self.jump(EXIT);
+            }
+        }
+    }
+}
+
+// C-like spelling of an expression, fully parenthesized below the top level
+fn expr_str(e: &s::Expr) -> String {
+    match e {
+        s::Expr::Lit(s::Lit::Int { value, .. }) => value.to_string(),
+        s::Expr::Lit(s::Lit::Float { value, .. }) => format!("{:?}", value),
+        s::Expr::Lit(s::Lit::Bool(b)) => b.to_string(),
+        s::Expr::Var(name) => name.clone(),
+        s::Expr::Unary { op, expr } => {
+            let op = match op {
+                s::UnOpRef::Neg => "-",
+                s::UnOpRef::BitNot => "~",
+                s::UnOpRef::AddrOf => "&",
+                s::UnOpRef::Deref => "*",
+            };
+            format!("{}{}", op, operand(expr))
+        }
+        s::Expr::Binary { left, op, right } => {
+            let op = match op {
+                s::BinOpRef::Add => "+",
+                s::BinOpRef::Sub => "-",
+                s::BinOpRef::Mul => "*",
+                s::BinOpRef::Div => "/",
+                s::BinOpRef::Rem => "%",
+                s::BinOpRef::BitAnd => "&",
+                s::BinOpRef::BitOr => "|",
+                s::BinOpRef::BitXor => "^",
+                s::BinOpRef::Shl => "<<",
+                s::BinOpRef::Shr => ">>",
+            };
+            format!("{} {} {}", operand(left), op, operand(right))
+        }
+        s::Expr::Cmp { left, op, right } => {
+            let op = match op {
+                s::CmpOpRef::Eq => "==",
+                s::CmpOpRef::Ne => "!=",
+                s::CmpOpRef::Lt => "<",
+                s::CmpOpRef::Le => "<=",
+                s::CmpOpRef::Gt => ">",
+                s::CmpOpRef::Ge => ">=",
+            };
+            format!("{} {} {}", operand(left), op, operand(right))
+        }
+        s::Expr::Logical { left, op, right } => {
+            let op = match op {
+                s::LogicOpRef::And => "&&",
+                s::LogicOpRef::Or => "||",
+            };
+            format!("{} {} {}", operand(left), op, operand(right))
+        }
+        s::Expr::IncDec { name, op } => match op {
+            s::IncDecOpRef::PreInc => format!("++{}", name),
+            s::IncDecOpRef::PreDec => format!("--{}", name),
+            s::IncDecOpRef::PostInc => format!("{}++", name),
+            s::IncDecOpRef::PostDec => format!("{}--", name),
+        },
+        s::Expr::Call { callee, args } => {
+            let args: Vec<String> = args.iter().map(expr_str).collect();
+            format!("{}({})", callee, args.join(", "))
+        }
+        s::Expr::Member { base, field } => format!("{}.{}", operand(base), field),
+        s::Expr::Ternary { cond, then_expr, else_expr } => {
+            format!("{} ? {} : {}", operand(cond), operand(then_expr), operand(else_expr))
+        }
+        s::Expr::Cast { ty, expr } => format!("({}){}", type_str(ty), operand(expr)),
+        s::Expr::Comma { left, right } => format!("{}, {}", operand(left), operand(right)),
+    }
+}
+
+fn operand(e: &s::Expr) -> String {
+    match e {
+        s::Expr::Lit(_) | s::Expr::Var(_) | s::Expr::Call { .. } | s::Expr::IncDec { .. } => {
+            expr_str(e)
+        }
+        _ => format!("({})", expr_str(e)),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse_translation_unit, ParseOptions};
+    use crate::target::Target;
+
+    fn dot(src: &str) -> String {
+        dot_program(&parse_translation_unit(src, &Target::X86_64, ParseOptions::LENIENT).unwrap().0)
+    }
+
+    // the edge lines of the graph, without indentation
+    fn edges(src: &str) -> Vec<String> {
+        dot(src).lines().filter(|l| l.contains("->")).map(|l| l.trim().to_string()).collect()
+    }
+
+    #[test]
+    fn branches_and_loops() {
+        let src = "int f(int x) { if (x) x = 1; else x = 2; while (x) x--; return x; }";
+        let g = dot(src);
+        assert!(g.starts_with("digraph cfg {\n"), "{g}");
+        assert!(g.contains("\"f.0\" [label=\"entry\\lif x != 0\\l\"];"), "{g}");
+        assert!(g.contains("\"f.6\" [label=\"bb6\\lx = x - 1\\l\"];"), "{g}");
+        assert_eq!(
+            edges(src),
+            [
+                r#""f.0" -> "f.3" [label="T"];"#,
+                r#""f.0" -> "f.4" [label="F"];"#,
+                r#""f.2" -> "f.5";"#,
+                r#""f.3" -> "f.2";"#,
+                r#""f.4" -> "f.2";"#,
+                r#""f.5" -> "f.6" [label="T"];"#,
+                r#""f.5" -> "f.7" [label="F"];"#,
+                r#""f.6" -> "f.5";"#,
+                r#""f.7" -> "f.1";"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn switch_edges_carry_the_case() {
+        let src = "int f(int x) { switch (x) { case 1: x = 2; break; default: return 0; } return x; }";
+        let e = edges(src);
+        assert!(e.contains(&r#""f.0" -> "f.3" [label="1"];"#.to_string()), "{e:?}");
+        assert!(e.contains(&r#""f.0" -> "f.4" [label="default"];"#.to_string()), "{e:?}");
+    }
+
+    #[test]
+    fn labels_start_blocks() {
+        // a function that never returns has no exit block
+        let g = dot("int g() { l: goto l; }");
+        assert!(g.contains("\"g.1\" [label=\"bb1\\ll:\\lgoto l\\l\"];"), "{g}");
+        assert_eq!(edges("int g() { l: goto l; }"), [r#""g.0" -> "g.1";"#, r#""g.1" -> "g.1";"#]);
+    }
+}
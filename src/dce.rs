@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ir::lower_ast::frontend as s;
+
+/// Drops code that can never run or whose result is never used: statements
+/// after a `return`/`break`/`continue`/`goto` up to the next label, the dead arm
+/// of an `if` on a constant condition, `while (false)` loops, and expression
+/// statements without side effects. Run after constant folding, which is what
+/// turns conditions into the literals this pass looks for. Returns how many
+/// statements were dropped, counting those nested in a dropped one.
+pub fn dce_program(p: &mut s::Program) -> usize {
+    let mut removed = 0;
+    for func in &mut p.functions {
+        let body = std::mem::take(&mut func.body);
+        let before = count(&body);
+        func.body = dce_stmts(body);
+        removed += before - count(&func.body);
+    }
+    removed
+}
+
+fn count(body: &[s::Stmt]) -> usize {
+    body.iter()
+        .map(|st| match st {
+            s::Stmt::If { then_body, else_body, .. } => 1 + count(then_body) + count(else_body),
+            s::Stmt::While { body, .. } => 1 + count(body),
+            s::Stmt::Switch { cases, .. } => 1 + cases.iter().map(|c| count(&c.body)).sum::<usize>(),
+            _ => 1,
+        })
+        .sum()
+}
+
+fn dce_stmts(body: Vec<s::Stmt>) -> Vec<s::Stmt> {
+    let mut out = Vec::with_capacity(body.len());
+    let mut reachable = true;
+    for st in body {
+        // a label may be the target of a `goto`, so code resumes there
+        if matches!(st, s::Stmt::Label(_)) {
+            reachable = true;
+        }
+        if !reachable && !contains_label(std::slice::from_ref(&st)) {
+            // the name stays declared for code after a later label
+            match st {
+                s::Stmt::VarDecl { name, ty, .. } => out.push(s::Stmt::VarDecl { name, ty, init: None }),
+                st @ s::Stmt::ConstDecl { .. } => out.push(st),
+                _ => {}
+            }
+            continue;
+        }
+        match st {
+            // the dead arm stays if a `goto` can still jump into it
+            s::Stmt::If { cond: s::Expr::Lit(s::Lit::Bool(c)), then_body, else_body }
+                if !contains_label(if c { &else_body } else { &then_body }) =>
+            {
+                out.extend(dce_stmts(if c { then_body } else { else_body }));
+            }
+            s::Stmt::If { cond, then_body, else_body } => out.push(s::Stmt::If {
+                cond,
+                then_body: dce_stmts(then_body),
+                else_body: dce_stmts(else_body),
+            }),
+            s::Stmt::While { cond: s::Expr::Lit(s::Lit::Bool(false)), ref body }
+                if !contains_label(body) => {}
+            s::Stmt::While { cond, body } => out.push(s::Stmt::While { cond, body: dce_stmts(body) }),
+            s::Stmt::Switch { value, cases } => out.push(s::Stmt::Switch {
+                value,
+                cases: cases
+                    .into_iter()
+                    .map(|c| s::SwitchCase { label: c.label, body: dce_stmts(c.body) })
+                    .collect(),
+            }),
+            s::Stmt::ExprStmt(e) if is_pure(&e) => {}
+            st => out.push(st),
+        }
+        if out.last().is_some_and(is_jump) {
+            reachable = false;
+        }
+    }
+    out
+}
+
+// control never falls through past this statement
+fn is_jump(st: &s::Stmt) -> bool {
+    match st {
+        s::Stmt::Return(_) | s::Stmt::Break | s::Stmt::Continue | s::Stmt::Goto(_) => true,
+        s::Stmt::If { then_body, else_body, .. } => {
+            then_body.last().is_some_and(is_jump) && else_body.last().is_some_and(is_jump)
+        }
+        _ => false,
+    }
+}
+
+fn contains_label(body: &[s::Stmt]) -> bool {
+    body.iter().any(|st| match st {
+        s::Stmt::Label(_) => true,
+        s::Stmt::If { then_body, else_body, .. } => {
+            contains_label(then_body) || contains_label(else_body)
+        }
+        s::Stmt::While { body, .. } => contains_label(body),
+        s::Stmt::Switch { cases, .. } => cases.iter().any(|c| contains_label(&c.body)),
+        _ => false,
+    })
+}
+
+// no calls, no stores: evaluating it can be skipped without changing the program
+pub fn is_pure(e: &s::Expr) -> bool {
+    match e {
+        s::Expr::Lit(_) | s::Expr::Var(_) => true,
+        s::Expr::Call { .. } | s::Expr::IncDec { .. } => false,
+        s::Expr::Unary { expr, .. }
+        | s::Expr::Member { base: expr, .. }
+        | s::Expr::Cast { expr, .. } => is_pure(expr),
+        s::Expr::Binary { left, right, .. }
+        | s::Expr::Cmp { left, right, .. }
+        | s::Expr::Logical { left, right, .. }
+        | s::Expr::Comma { left, right } => is_pure(left) && is_pure(right),
+        s::Expr::Ternary { cond, then_expr, else_expr } => {
+            is_pure(cond) && is_pure(then_expr) && is_pure(else_expr)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::dump_program;
+    use crate::parse::{parse_translation_unit, ParseOptions};
+    use crate::target::Target;
+
+    // the body of `f` after folding and DCE, one node per line
+    fn after_dce(src: &str) -> String {
+        let mut p = parse_translation_unit(src, &Target::X86_64, ParseOptions::LENIENT).unwrap().0;
+        crate::fold::fold_program(&mut p, &Target::X86_64);
+        dce_program(&mut p);
+        let out = dump_program(&p);
+        let body = out.lines().skip_while(|l| !l.starts_with("fn f(")).skip(1);
+        body.map(|l| format!("{}\n", l.trim())).collect()
+    }
+
+    #[test]
+    fn code_after_a_jump() {
+        assert_eq!(after_dce("int f(int x) { return 1; x = 2; return x; }"), "Return\nInt 1: i32\n");
+        assert_eq!(
+            after_dce("void f(int x) { while (x) { break; x = 1; } }"),
+            "While\nCmp Ne\nVar x\nInt 0: i32\nbody\nBreak\n"
+        );
+        // both arms jump, so nothing after the `if` runs
+        assert_eq!(
+            after_dce("int f(int x) { if (x) return 1; else return 2; x = 3; }"),
+            "If\nCmp Ne\nVar x\nInt 0: i32\nthen\nReturn\nInt 1: i32\nelse\nReturn\nInt 2: i32\n"
+        );
+    }
+
+    #[test]
+    fn labels_are_reachable() {
+        let out = after_dce("int f(int x) { goto l; x = 1; l: x = 2; return x; }");
+        assert_eq!(out, "Goto l\nLabel l\nAssign x\nInt 2: i32\nReturn\nVar x\n");
+    }
+
+    #[test]
+    fn dead_declarations_stay_declared() {
+        let out = after_dce("int f() { goto l; int y = 5; l: y = 2; return y; }");
+        assert_eq!(out, "Goto l\nVarDecl y: i32\nLabel l\nAssign y\nInt 2: i32\nReturn\nVar y\n");
+    }
+
+    #[test]
+    fn constant_conditions() {
+        assert_eq!(after_dce("int f() { if (1) return 1; else return 2; }"), "Return\nInt 1: i32\n");
+        assert_eq!(after_dce("int f(int x) { if (0 && x) x = 1; return x; }"), "Return\nVar x\n");
+        assert_eq!(after_dce("int f(int x) { while (0) x++; return x; }"), "Return\nVar x\n");
+        // a `goto` into the dead arm keeps it
+        let out = after_dce("int f(int x) { goto in; if (0) { in: x = 1; } return x; }");
+        assert!(out.contains("If\nBool false\nthen\nLabel in"), "{out}");
+    }
+
+    #[test]
+    fn pure_expression_statements() {
+        assert_eq!(
+            after_dce("int g(); void f(int x) { x + 1; g(); -x, x++; }"),
+            "ExprStmt\nCall g\nAssign x\nBinary Add\nVar x\nInt 1: i32\n"
+        );
+        let out = after_dce("int g(); void f(int x) { x ? g() : 0; x == 1; }");
+        assert!(out.starts_with("ExprStmt\nTernary") && !out.contains("Cmp Eq"), "{out}");
+    }
+
+    #[test]
+    fn counts_nested_statements() {
+        let src = "int f(int x) { return x; if (x) { x = 1; x = 2; } }";
+        let mut p = parse_translation_unit(src, &Target::X86_64, ParseOptions::LENIENT).unwrap().0;
+        assert_eq!(dce_program(&mut p), 3);
+    }
+}
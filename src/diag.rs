@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: MPL-2.0
+
+/// Renders source line `line` of `src` with a `^` under column `col`, gutter
+/// first, for printing below a diagnostic:
+///
+/// ```text
+///  3 | int x = @;
+///    |         ^
+/// ```
+///
+/// Columns count characters, as the lexer does. Tabs before the column are
+/// copied into the caret line so it lines up however wide the terminal draws them.
+/// Lines end at `\n`, `\r\n` or a lone `\r`, as the lexer counts them.
+pub fn caret(src: &str, line: usize, col: usize) -> String {
+    let src = src.replace("\r\n", "\n");
+    let text = src.split(['\n', '\r']).nth(line.saturating_sub(1)).unwrap_or("");
+    let pad: String = text
+        .chars()
+        .take(col.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let gutter = line.to_string();
+    format!(" {} | {}\n {:w$} | {}^\n", gutter, text, "", pad, w = gutter.len())
+}
+
+/// How diagnostics are printed, chosen with `--diagnostics=`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// `file: label: message (line:col)` followed by a caret line
+    Text,
+    /// one JSON object per line, for editors
+    Json,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// One message for the user. `label` prefixes it in text mode ("parse error",
+/// "warning"; empty for none). Whole-program problems have no file, and
+/// some messages no position.
+#[derive(Debug)]
+pub struct Diagnostic<'a> {
+    pub severity: Severity,
+    pub label: &'a str,
+    pub message: String,
+    pub file: Option<&'a str>,
+    /// (line, col)
+    pub pos: Option<(usize, usize)>,
+}
+
+/// Where every diagnostic of a run goes: stderr, in one format.
+#[derive(Clone, Copy, Debug)]
+pub struct Sink {
+    pub format: Format,
+}
+
+impl Sink {
+    /// Prints `d`; in text mode `src`, the file's source, adds the caret line.
+    pub fn report(&self, d: &Diagnostic, src: Option<&str>) {
+        match self.format {
+            Format::Text => {
+                let mut text = String::new();
+                for part in [d.file.unwrap_or(""), d.label] {
+                    if !part.is_empty() {
+                        text += part;
+                        text += ": ";
+                    }
+                }
+                text += &d.message;
+                if let Some((line, col)) = d.pos {
+                    text += &format!(" ({}:{})", line, col);
+                }
+                eprintln!("{}", text);
+                if let (Some((line, col)), Some(src)) = (d.pos, src) {
+                    eprint!("{}", caret(src, line, col));
+                }
+            }
+            Format::Json => {
+                let severity = match d.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                };
+                let file = d.file.map_or("null".to_string(), json_str);
+                let (line, col) = match d.pos {
+                    Some((line, col)) => (line.to_string(), col.to_string()),
+                    None => ("null".to_string(), "null".to_string()),
+                };
+                eprintln!(
+                    "{{\"severity\":\"{}\",\"message\":{},\"file\":{},\"line\":{},\"col\":{}}}",
+                    severity,
+                    json_str(&d.message),
+                    file,
+                    line,
+                    col,
+                );
+            }
+        }
+    }
+}
+
+fn json_str(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caret_under_the_column() {
+        assert_eq!(caret("int x = @;\n", 1, 9), " 1 | int x = @;\n   |         ^\n");
+        assert_eq!(caret("a\nbb\n", 2, 2), " 2 | bb\n   |  ^\n");
+        assert_eq!(caret("a", 7, 1), " 7 | \n   | ^\n");
+    }
+
+    #[test]
+    fn caret_keeps_tabs() {
+        assert_eq!(caret("\tx = @;", 1, 6), " 1 | \tx = @;\n   | \t    ^\n");
+    }
+
+    #[test]
+    fn caret_widens_the_gutter() {
+        let src = "\n".repeat(11) + "x";
+        assert_eq!(caret(&src, 12, 1), " 12 | x\n    | ^\n");
+    }
+
+    #[test]
+    fn json_escapes() {
+        assert_eq!(json_str("plain"), "\"plain\"");
+        assert_eq!(json_str("a \"b\" \\ c\n\t\u{1}"), "\"a \\\"b\\\" \\\\ c\\n\\t\\u0001\"");
+    }
+
+    #[test]
+    fn format_names() {
+        assert_eq!(Format::from_name("json"), Some(Format::Json));
+        assert_eq!(Format::from_name("text"), Some(Format::Text));
+        assert_eq!(Format::from_name("xml"), None);
+    }
+
+    #[test]
+    fn caret_line_endings() {
+        assert_eq!(caret("a\r\nb\rc", 2, 1), " 2 | b\n   | ^\n");
+        assert_eq!(caret("a\r\nb\rc", 3, 1), " 3 | c\n   | ^\n");
+        assert_eq!(caret("a", 7, 1), " 7 | \n   | ^\n");
+    }
+}
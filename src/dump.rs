@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ir::lower_ast::frontend as s;
+use std::fmt::Write;
+
+/// Indented, human-readable rendering of the frontend AST, for `--emit=ast`.
+pub fn dump_program(p: &s::Program) -> String {
+    dump(p, false)
+}
+
+/// Like [`dump_program`], with integer literals in hex (`--print-hex`):
+/// `Int 0xff: i32`, and negative values as `-0x1`.
+pub fn dump_program_hex(p: &s::Program) -> String {
+    dump(p, true)
+}
+
+fn dump(p: &s::Program, hex: bool) -> String {
+    let mut d = Dumper { out: String::new(), hex };
+    for def in &p.structs {
+        d.line(0, format!("struct {} (size {}, align {})", def.name, def.size, def.align));
+        for f in &def.fields {
+            d.line(1, format!("{}: {} @{}", f.name, type_str(&f.ty), f.offset));
+        }
+    }
+    for g in &p.global_decls {
+        d.line(0, format!("extern {}: {}", g.name, type_str(&g.ty)));
+    }
+    for g in &p.globals {
+        d.line(0, format!("{}const {}: {}", linkage_str(g.linkage), g.name, type_str(&g.ty)));
+        d.expr(1, &g.init);
+    }
+    for g in &p.global_vars {
+        d.line(0, format!("{}var {}: {}", linkage_str(g.linkage), g.name, type_str(&g.ty)));
+        if let Some(init) = &g.init {
+            d.expr(1, init);
+        }
+    }
+    for f in &p.prototypes {
+        d.line(0, signature(f.linkage, &f.name, &f.parameters, &f.return_type) + ";");
+    }
+    for f in &p.functions {
+        d.line(0, signature(f.linkage, &f.name, &f.parameters, &f.return_type));
+        d.stmts(1, &f.body);
+    }
+    d.out
+}
+
+fn linkage_str(l: s::Linkage) -> &'static str {
+    match l {
+        s::Linkage::External => "",
+        s::Linkage::Internal => "static ",
+    }
+}
+
+fn signature(l: s::Linkage, name: &str, params: &[s::Parameter], ret: &s::TypeRef) -> String {
+    let params: Vec<String> = params
+        .iter()
+        .map(|p| match p.name.as_str() {
+            "" => type_str(&p.ty),
+            name => format!("{}: {}", name, type_str(&p.ty)),
+        })
+        .collect();
+    format!("{}fn {}({}) -> {}", linkage_str(l), name, params.join(", "), type_str(ret))
+}
+
+/// Short spelling of a type, also used in diagnostics: i32, u8, bool, f64,
+/// i32*, i32[4], struct P, void.
+pub fn type_str(ty: &s::TypeRef) -> String {
+    match ty {
+        s::TypeRef::Void => "void".to_string(),
+        s::TypeRef::Int { bits: 1, .. } => "bool".to_string(),
+        s::TypeRef::Int { bits, signed: true } => format!("i{}", bits),
+        s::TypeRef::Int { bits, signed: false } => format!("u{}", bits),
+        s::TypeRef::Float { bits } => format!("f{}", bits),
+        s::TypeRef::Pointer(inner) => format!("{}*", type_str(inner)),
+        s::TypeRef::Array { elem, len } => format!("{}[{}]", type_str(elem), len),
+        s::TypeRef::Struct(name) => format!("struct {}", name),
+    }
+}
+
+struct Dumper {
+    out: String,
+    /// print integer literals in hex
+    hex: bool,
+}
+
+impl Dumper {
+    fn line(&mut self, depth: usize, text: impl AsRef<str>) {
+        let _ = writeln!(self.out, "{:width$}{}", "", text.as_ref(), width = depth * 2);
+    }
+
+    fn stmts(&mut self, depth: usize, body: &[s::Stmt]) {
+        for st in body {
+            self.stmt(depth, st);
+        }
+    }
+
+    fn stmt(&mut self, depth: usize, st: &s::Stmt) {
+        match st {
+            s::Stmt::VarDecl { name, ty, init } => {
+                self.line(depth, format!("VarDecl {}: {}", name, type_str(ty)));
+                if let Some(init) = init {
+                    self.expr(depth + 1, init);
+                }
+            }
+            s::Stmt::ConstDecl { name, ty, init } => {
+                self.line(depth, format!("ConstDecl {}: {}", name, type_str(ty)));
+                self.expr(depth + 1, init);
+            }
+            s::Stmt::Assign { name, value } => {
+                self.line(depth, format!("Assign {}", name));
+                self.expr(depth + 1, value);
+            }
+            s::Stmt::Store { target, value } => {
+                self.line(depth, "Store");
+                self.expr(depth + 1, target);
+                self.expr(depth + 1, value);
+            }
+            s::Stmt::ExprStmt(e) => {
+                self.line(depth, "ExprStmt");
+                self.expr(depth + 1, e);
+            }
+            s::Stmt::If { cond, then_body, else_body } => {
+                self.line(depth, "If");
+                self.expr(depth + 1, cond);
+                self.line(depth + 1, "then");
+                self.stmts(depth + 2, then_body);
+                if !else_body.is_empty() {
+                    self.line(depth + 1, "else");
+                    self.stmts(depth + 2, else_body);
+                }
+            }
+            s::Stmt::While { cond, body } => {
+                self.line(depth, "While");
+                self.expr(depth + 1, cond);
+                self.line(depth + 1, "body");
+                self.stmts(depth + 2, body);
+            }
+            s::Stmt::Switch { value, cases } => {
+                self.line(depth, "Switch");
+                self.expr(depth + 1, value);
+                for case in cases {
+                    match case.label {
+                        Some(v) => self.line(depth + 1, format!("case {}", v)),
+                        None => self.line(depth + 1, "default"),
+                    }
+                    self.stmts(depth + 2, &case.body);
+                }
+            }
+            s::Stmt::Break => self.line(depth, "Break"),
+            s::Stmt::Continue => self.line(depth, "Continue"),
+            s::Stmt::Goto(label) => self.line(depth, format!("Goto {}", label)),
+            s::Stmt::Label(label) => self.line(depth, format!("Label {}", label)),
+            s::Stmt::Return(value) => {
+                self.line(depth, "Return");
+                if let Some(e) = value {
+                    self.expr(depth + 1, e);
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, depth: usize, e: &s::Expr) {
+        match e {
+            s::Expr::Lit(s::Lit::Int { bits, signed, value }) => {
+                let ty = s::TypeRef::Int { bits: *bits, signed: *signed };
+                let value = match (self.hex, *value < 0) {
+                    (false, _) => value.to_string(),
+                    (true, false) => format!("0x{:x}", value),
+                    (true, true) => format!("-0x{:x}", value.unsigned_abs()),
+                };
+                self.line(depth, format!("Int {}: {}", value, type_str(&ty)));
+            }
+            s::Expr::Lit(s::Lit::Float { bits, value }) => {
+                self.line(depth, format!("Float {:?}: f{}", value, bits));
+            }
+            s::Expr::Lit(s::Lit::Bool(b)) => self.line(depth, format!("Bool {}", b)),
+            s::Expr::Var(name) => self.line(depth, format!("Var {}", name)),
+            s::Expr::Unary { op, expr } => {
+                self.line(depth, format!("Unary {:?}", op));
+                self.expr(depth + 1, expr);
+            }
+            s::Expr::Binary { left, op, right } => {
+                self.line(depth, format!("Binary {:?}", op));
+                self.expr(depth + 1, left);
+                self.expr(depth + 1, right);
+            }
+            s::Expr::Cmp { left, op, right } => {
+                self.line(depth, format!("Cmp {:?}", op));
+                self.expr(depth + 1, left);
+                self.expr(depth + 1, right);
+            }
+            s::Expr::Logical { left, op, right } => {
+                self.line(depth, format!("Logical {:?}", op));
+                self.expr(depth + 1, left);
+                self.expr(depth + 1, right);
+            }
+            s::Expr::IncDec { name, op } => self.line(depth, format!("IncDec {:?} {}", op, name)),
+            s::Expr::Call { callee, args } => {
+                self.line(depth, format!("Call {}", callee));
+                for a in args {
+                    self.expr(depth + 1, a);
+                }
+            }
+            s::Expr::Member { base, field } => {
+                self.line(depth, format!("Member .{}", field));
+                self.expr(depth + 1, base);
+            }
+            s::Expr::Ternary { cond, then_expr, else_expr } => {
+                self.line(depth, "Ternary");
+                self.expr(depth + 1, cond);
+                self.expr(depth + 1, then_expr);
+                self.expr(depth + 1, else_expr);
+            }
+            s::Expr::Cast { ty, expr } => {
+                self.line(depth, format!("Cast {}", type_str(ty)));
+                self.expr(depth + 1, expr);
+            }
+            s::Expr::Comma { left, right } => {
+                self.line(depth, "Comma");
+                self.expr(depth + 1, left);
+                self.expr(depth + 1, right);
+            }
+        }
+    }
+}
@@ -0,0 +1,740 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::target::Target;
+use std::cell::Cell;
+use ir::lower_ast::frontend as s;
+
+/// Folds operators whose operands are all literals into a single literal:
+/// arithmetic, comparisons, logical operators, casts and constant-condition
+/// ternaries. Expressions are folded bottom-up, so one walk already reaches
+/// the fixpoint -- a parent only ever sees operands that are fully folded.
+/// A local `const` whose initializer folds to a literal is replaced by that
+/// value in every use and its declaration dropped, unless its address is taken.
+/// Returns how many expressions were folded.
+pub fn fold_program(p: &mut s::Program, target: &Target) -> usize {
+    let f = Folder { target: *target, folded: Cell::new(0) };
+    for g in &mut p.globals {
+        f.expr(&mut g.init);
+    }
+    for g in &mut p.global_vars {
+        if let Some(init) = &mut g.init {
+            f.expr(init);
+        }
+    }
+    for func in &mut p.functions {
+        f.stmts(&mut func.body);
+        f.propagate_consts(&mut func.body);
+    }
+    f.folded.get()
+}
+
+// locals are unique within a function (the parser renames shadowing ones), so a
+// name means the same object everywhere in the body
+fn each_expr(body: &mut [s::Stmt], f: &mut dyn FnMut(&mut s::Expr)) {
+    for st in body {
+        match st {
+            s::Stmt::VarDecl { init: Some(e), .. }
+            | s::Stmt::ConstDecl { init: e, .. }
+            | s::Stmt::Assign { value: e, .. }
+            | s::Stmt::ExprStmt(e)
+            | s::Stmt::Return(Some(e)) => f(e),
+            s::Stmt::Store { target, value } => {
+                f(target);
+                f(value);
+            }
+            s::Stmt::If { cond, then_body, else_body } => {
+                f(cond);
+                each_expr(then_body, f);
+                each_expr(else_body, f);
+            }
+            s::Stmt::While { cond, body } => {
+                f(cond);
+                each_expr(body, f);
+            }
+            s::Stmt::Switch { value, cases } => {
+                f(value);
+                for case in cases {
+                    each_expr(&mut case.body, f);
+                }
+            }
+            s::Stmt::VarDecl { init: None, .. }
+            | s::Stmt::Break
+            | s::Stmt::Continue
+            | s::Stmt::Goto(_)
+            | s::Stmt::Label(_)
+            | s::Stmt::Return(None) => {}
+        }
+    }
+}
+
+// names whose address is taken in `e`
+fn addr_taken(e: &s::Expr, out: &mut Vec<String>) {
+    match e {
+        s::Expr::Unary { op: s::UnOpRef::AddrOf, expr } => {
+            let mut base = &**expr;
+            while let s::Expr::Member { base: b, .. } = base {
+                base = b;
+            }
+            match base {
+                s::Expr::Var(name) => out.push(name.clone()),
+                _ => addr_taken(expr, out),
+            }
+        }
+        s::Expr::Lit(_) | s::Expr::Var(_) | s::Expr::IncDec { .. } => {}
+        s::Expr::Unary { expr, .. }
+        | s::Expr::Member { base: expr, .. }
+        | s::Expr::Cast { expr, .. } => addr_taken(expr, out),
+        s::Expr::Binary { left, right, .. }
+        | s::Expr::Cmp { left, right, .. }
+        | s::Expr::Logical { left, right, .. }
+        | s::Expr::Comma { left, right } => {
+            addr_taken(left, out);
+            addr_taken(right, out);
+        }
+        s::Expr::Call { args, .. } => {
+            for a in args {
+                addr_taken(a, out);
+            }
+        }
+        s::Expr::Ternary { cond, then_expr, else_expr } => {
+            addr_taken(cond, out);
+            addr_taken(then_expr, out);
+            addr_taken(else_expr, out);
+        }
+    }
+}
+
+// removes the `const` declarations with a literal initializer from `body`, adding
+// their values, converted to the declared type, to `out`
+fn take_consts(body: &mut Vec<s::Stmt>, pinned: &[String], out: &mut Vec<(String, s::Lit)>) {
+    body.retain(|st| {
+        let s::Stmt::ConstDecl { name, ty, init: s::Expr::Lit(lit) } = st else { return true };
+        if pinned.contains(name) {
+            return true;
+        }
+        match cast_lit(ty, lit) {
+            Some(lit) => {
+                out.push((name.clone(), lit));
+                false
+            }
+            None => true,
+        }
+    });
+    for st in body {
+        match st {
+            s::Stmt::If { then_body, else_body, .. } => {
+                take_consts(then_body, pinned, out);
+                take_consts(else_body, pinned, out);
+            }
+            s::Stmt::While { body, .. } => take_consts(body, pinned, out),
+            s::Stmt::Switch { cases, .. } => {
+                for case in cases {
+                    take_consts(&mut case.body, pinned, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Why an expression isn't an integer constant expression.
+#[derive(Debug)]
+pub struct EvalError(pub String);
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Evaluates an integer constant expression: literals, arithmetic, comparisons,
+/// casts, and the names in `consts` (integer const globals, by value). Folding
+/// follows the same rules as `fold_program`, so the result is what the
+/// expression would compute at run time.
+pub fn const_eval(e: &s::Expr, consts: &[(String, s::Lit)], target: &Target) -> Result<i128, EvalError> {
+    match const_lit(e, consts, target)? {
+        s::Lit::Int { value, .. } => Ok(value),
+        s::Lit::Bool(b) => Ok(b as i128),
+        s::Lit::Float { .. } => Err(EvalError("floating-point value".to_string())),
+    }
+}
+
+/// Folds an arithmetic constant expression to a literal. Like [`const_eval`],
+/// but floating-point values are constants too.
+pub fn const_lit(
+    e: &s::Expr,
+    consts: &[(String, s::Lit)],
+    target: &Target,
+) -> Result<s::Lit, EvalError> {
+    let mut e = e.clone();
+    substitute(&mut e, consts);
+    Folder { target: *target, folded: Cell::new(0) }.expr(&mut e);
+    match e {
+        s::Expr::Lit(lit) => Ok(lit),
+        e => Err(EvalError(non_constant(&e))),
+    }
+}
+
+fn substitute(e: &mut s::Expr, consts: &[(String, s::Lit)]) {
+    match e {
+        s::Expr::Var(name) => {
+            if let Some((_, lit)) = consts.iter().find(|(n, _)| n == name) {
+                *e = s::Expr::Lit(lit.clone());
+            }
+        }
+        s::Expr::Lit(_) | s::Expr::IncDec { .. } => {}
+        s::Expr::Unary { expr, .. }
+        | s::Expr::Member { base: expr, .. }
+        | s::Expr::Cast { expr, .. } => substitute(expr, consts),
+        s::Expr::Binary { left, right, .. }
+        | s::Expr::Cmp { left, right, .. }
+        | s::Expr::Logical { left, right, .. }
+        | s::Expr::Comma { left, right } => {
+            substitute(left, consts);
+            substitute(right, consts);
+        }
+        s::Expr::Call { args, .. } => {
+            for a in args {
+                substitute(a, consts);
+            }
+        }
+        s::Expr::Ternary { cond, then_expr, else_expr } => {
+            substitute(cond, consts);
+            substitute(then_expr, consts);
+            substitute(else_expr, consts);
+        }
+    }
+}
+
+// the innermost part of a folded expression that kept it from becoming a literal
+fn non_constant(e: &s::Expr) -> String {
+    let operands: Vec<&s::Expr> = match e {
+        s::Expr::Var(name) | s::Expr::IncDec { name, .. } => {
+            return format!("`{}` is not a constant", name.split('.').next().unwrap_or(name));
+        }
+        s::Expr::Call { callee, .. } => return format!("call to `{}`", callee),
+        s::Expr::Unary { op: s::UnOpRef::AddrOf | s::UnOpRef::Deref, .. }
+        | s::Expr::Member { .. } => {
+            return "address or memory access".to_string();
+        }
+        s::Expr::Unary { expr, .. } | s::Expr::Cast { expr, .. } => vec![expr],
+        s::Expr::Binary { left, right, .. }
+        | s::Expr::Cmp { left, right, .. }
+        | s::Expr::Logical { left, right, .. }
+        | s::Expr::Comma { left, right } => vec![left, right],
+        s::Expr::Ternary { cond, then_expr, else_expr } => vec![cond, then_expr, else_expr],
+        s::Expr::Lit(_) => vec![],
+    };
+    match operands.into_iter().find(|o| !matches!(o, s::Expr::Lit(_))) {
+        Some(o) => non_constant(o),
+        None => match e {
+            s::Expr::Binary { op: s::BinOpRef::Div | s::BinOpRef::Rem, .. } => {
+                "division by zero or overflow".to_string()
+            }
+            s::Expr::Binary { op: s::BinOpRef::Shl | s::BinOpRef::Shr, .. } => {
+                "shift count out of range".to_string()
+            }
+            _ => "not an integer constant expression".to_string(),
+        },
+    }
+}
+
+/// Truncates `value` to `bits` and sign- or zero-extends it back, i.e. the
+/// value a `bits`-wide register would hold.
+pub fn wrap(value: i128, bits: u16, signed: bool) -> i128 {
+    let shift = 128 - bits as u32;
+    if signed {
+        (value << shift) >> shift
+    } else {
+        ((value as u128) << shift >> shift) as i128
+    }
+}
+
+/// Converts a literal to `ty` the way a C cast would, or `None` if that needs
+/// a runtime conversion (pointers, structs) or is undefined (a float out of the
+/// range of the integer type).
+pub fn cast_lit(ty: &s::TypeRef, lit: &s::Lit) -> Option<s::Lit> {
+    Some(match (ty, lit) {
+        // `_Bool` is the 1-bit integer, and anything nonzero converts to 1
+        (&s::TypeRef::Int { bits: 1, signed }, &s::Lit::Int { value, .. }) => {
+            s::Lit::Int { bits: 1, signed, value: (value != 0) as i128 }
+        }
+        (&s::TypeRef::Int { bits: 1, signed }, &s::Lit::Float { value, .. }) => {
+            s::Lit::Int { bits: 1, signed, value: (value != 0.0) as i128 }
+        }
+        (&s::TypeRef::Int { bits, signed }, &s::Lit::Bool(b)) => {
+            s::Lit::Int { bits, signed, value: b as i128 }
+        }
+        (&s::TypeRef::Int { bits, signed }, &s::Lit::Int { value, .. }) => {
+            s::Lit::Int { bits, signed, value: wrap(value, bits, signed) }
+        }
+        // truncates toward zero; out-of-range values and NaN are left to the target
+        (&s::TypeRef::Int { bits, signed }, &s::Lit::Float { value, .. }) => {
+            let value = value.trunc();
+            // both bounds are powers of two, so exact as floats
+            let (lo, end) = match signed {
+                true => (-(1i128 << (bits - 1)), 1i128 << (bits - 1)),
+                false => (0, 1i128 << bits),
+            };
+            if !(value >= lo as f64 && value < end as f64) {
+                return None;
+            }
+            s::Lit::Int { bits, signed, value: value as i128 }
+        }
+        (&s::TypeRef::Float { bits }, &s::Lit::Int { value, .. }) => float_lit(bits, value as f64),
+        (&s::TypeRef::Float { bits }, &s::Lit::Float { value, .. }) => float_lit(bits, value),
+        _ => return None,
+    })
+}
+
+// a `float` literal holds the nearest f32
+fn float_lit(bits: u16, value: f64) -> s::Lit {
+    let value = if bits == 32 { value as f32 as f64 } else { value };
+    s::Lit::Float { bits, value }
+}
+
+fn lit_type(lit: &s::Lit) -> Option<s::TypeRef> {
+    match *lit {
+        s::Lit::Int { bits, signed, .. } => Some(s::TypeRef::Int { bits, signed }),
+        s::Lit::Float { bits, .. } => Some(s::TypeRef::Float { bits }),
+        s::Lit::Bool(_) => None,
+    }
+}
+
+struct Folder {
+    target: Target,
+    folded: Cell<usize>,
+}
+
+impl Folder {
+    fn stmts(&self, body: &mut [s::Stmt]) {
+        for st in body {
+            self.stmt(st);
+        }
+    }
+
+    fn stmt(&self, st: &mut s::Stmt) {
+        match st {
+            s::Stmt::VarDecl { init: Some(e), .. }
+            | s::Stmt::ConstDecl { init: e, .. }
+            | s::Stmt::Assign { value: e, .. }
+            | s::Stmt::ExprStmt(e)
+            | s::Stmt::Return(Some(e)) => self.expr(e),
+            s::Stmt::Store { target, value } => {
+                self.expr(target);
+                self.expr(value);
+            }
+            s::Stmt::If { cond, then_body, else_body } => {
+                self.expr(cond);
+                self.stmts(then_body);
+                self.stmts(else_body);
+            }
+            s::Stmt::While { cond, body } => {
+                self.expr(cond);
+                self.stmts(body);
+            }
+            s::Stmt::Switch { value, cases } => {
+                self.expr(value);
+                for case in cases {
+                    self.stmts(&mut case.body);
+                }
+            }
+            s::Stmt::VarDecl { init: None, .. }
+            | s::Stmt::Break
+            | s::Stmt::Continue
+            | s::Stmt::Goto(_)
+            | s::Stmt::Label(_)
+            | s::Stmt::Return(None) => {}
+        }
+    }
+
+    // a substituted `const` can make another one's initializer a literal, so this
+    // repeats until no new ones turn up
+    fn propagate_consts(&self, body: &mut Vec<s::Stmt>) {
+        let mut pinned = Vec::new();
+        each_expr(body, &mut |e| addr_taken(e, &mut pinned));
+        let mut consts = Vec::new();
+        loop {
+            let known = consts.len();
+            take_consts(body, &pinned, &mut consts);
+            if consts.len() == known {
+                return;
+            }
+            each_expr(body, &mut |e| {
+                substitute(e, &consts);
+                self.expr(e);
+            });
+        }
+    }
+
+    // bottom-up, so operands are already folded when their parent is visited
+    fn expr(&self, e: &mut s::Expr) {
+        match e {
+            s::Expr::Lit(_) | s::Expr::Var(_) | s::Expr::IncDec { .. } => {}
+            s::Expr::Unary { expr, .. }
+            | s::Expr::Member { base: expr, .. }
+            | s::Expr::Cast { expr, .. } => self.expr(expr),
+            s::Expr::Binary { left, right, .. }
+            | s::Expr::Cmp { left, right, .. }
+            | s::Expr::Logical { left, right, .. }
+            | s::Expr::Comma { left, right } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            s::Expr::Call { args, .. } => {
+                for a in args {
+                    self.expr(a);
+                }
+            }
+            s::Expr::Ternary { cond, then_expr, else_expr } => {
+                self.expr(cond);
+                self.expr(then_expr);
+                self.expr(else_expr);
+            }
+        }
+        if let Some(folded) = self.fold(e) {
+            *e = folded;
+            self.folded.set(self.folded.get() + 1);
+        }
+    }
+
+    // a folded comparison is a `true`/`false` literal, but as an operand it's the `int` 1 or 0
+    fn as_int(&self, lit: &s::Lit) -> s::Lit {
+        match *lit {
+            s::Lit::Bool(b) => {
+                s::Lit::Int { bits: self.target.int_bits, signed: true, value: b as i128 }
+            }
+            ref lit => lit.clone(),
+        }
+    }
+
+    fn fold(&self, e: &s::Expr) -> Option<s::Expr> {
+        match e {
+            s::Expr::Unary { op, expr } => {
+                let s::Expr::Lit(lit) = &**expr else { return None };
+                let lit = self.as_int(lit);
+                let lit = cast_lit(&self.target.promote(lit_type(&lit)?), &lit)?;
+                match (op, lit) {
+                    (s::UnOpRef::Neg, s::Lit::Int { bits, signed, value }) => {
+                        Some(int(bits, signed, -value))
+                    }
+                    (s::UnOpRef::BitNot, s::Lit::Int { bits, signed, value }) => {
+                        Some(int(bits, signed, !value))
+                    }
+                    (s::UnOpRef::Neg, s::Lit::Float { bits, value }) => {
+                        Some(s::Expr::Lit(float_lit(bits, -value)))
+                    }
+                    _ => None,
+                }
+            }
+            s::Expr::Binary { left, op, right } => {
+                let (s::Expr::Lit(l), s::Expr::Lit(r)) = (&**left, &**right) else { return None };
+                self.fold_binary(*op, &self.as_int(l), &self.as_int(r))
+            }
+            s::Expr::Cmp { left, op, right } => {
+                let (s::Expr::Lit(l), s::Expr::Lit(r)) = (&**left, &**right) else { return None };
+                let (l, r) = (self.as_int(l), self.as_int(r));
+                let ty = self.target.arith_type(lit_type(&l)?, lit_type(&r)?);
+                let ord = match (cast_lit(&ty, &l)?, cast_lit(&ty, &r)?) {
+                    (s::Lit::Int { value: a, .. }, s::Lit::Int { value: b, .. }) => a.cmp(&b),
+                    (s::Lit::Float { value: a, .. }, s::Lit::Float { value: b, .. }) => {
+                        // NaN compares false with everything but `!=`
+                        match a.partial_cmp(&b) {
+                            Some(ord) => ord,
+                            None => return Some(s::Expr::Lit(s::Lit::Bool(*op == s::CmpOpRef::Ne))),
+                        }
+                    }
+                    _ => return None,
+                };
+                let v = match op {
+                    s::CmpOpRef::Eq => ord.is_eq(),
+                    s::CmpOpRef::Ne => ord.is_ne(),
+                    s::CmpOpRef::Lt => ord.is_lt(),
+                    s::CmpOpRef::Le => ord.is_le(),
+                    s::CmpOpRef::Gt => ord.is_gt(),
+                    s::CmpOpRef::Ge => ord.is_ge(),
+                };
+                Some(s::Expr::Lit(s::Lit::Bool(v)))
+            }
+            // a constant left side decides whether the right one runs at all
+            s::Expr::Logical { left, op, right } => match (&**left, op) {
+                (s::Expr::Lit(s::Lit::Bool(true)), s::LogicOpRef::And)
+                | (s::Expr::Lit(s::Lit::Bool(false)), s::LogicOpRef::Or) => Some((**right).clone()),
+                (s::Expr::Lit(s::Lit::Bool(b)), _) => Some(s::Expr::Lit(s::Lit::Bool(*b))),
+                _ => None,
+            },
+            // only when both arms share a type, so picking one can't change the result type
+            s::Expr::Ternary { cond, then_expr, else_expr } => {
+                let s::Expr::Lit(s::Lit::Bool(c)) = **cond else { return None };
+                let (s::Expr::Lit(t), s::Expr::Lit(f)) = (&**then_expr, &**else_expr) else {
+                    return None;
+                };
+                let same = match (t, f) {
+                    (s::Lit::Bool(_), s::Lit::Bool(_)) => true,
+                    (&s::Lit::Int { bits, signed, .. }, &s::Lit::Int { bits: fb, signed: fs, .. }) => {
+                        (bits, signed) == (fb, fs)
+                    }
+                    (s::Lit::Float { bits: a, .. }, s::Lit::Float { bits: b, .. }) => a == b,
+                    _ => false,
+                };
+                same.then(|| s::Expr::Lit(if c { t.clone() } else { f.clone() }))
+            }
+            s::Expr::Cast { ty, expr } => {
+                let s::Expr::Lit(lit) = &**expr else { return None };
+                cast_lit(ty, &self.as_int(lit)).map(s::Expr::Lit)
+            }
+            s::Expr::Comma { left, right } if matches!(**left, s::Expr::Lit(_)) => {
+                Some((**right).clone())
+            }
+            _ => None,
+        }
+    }
+
+    // operands converted to their common type first, results wrapped to its width;
+    // anything C leaves undefined or that traps (x/0, INT_MIN/-1, oversized shifts) stays put
+    fn fold_binary(&self, op: s::BinOpRef, l: &s::Lit, r: &s::Lit) -> Option<s::Expr> {
+        let (lty, rty) = (lit_type(l)?, lit_type(r)?);
+        if matches!(op, s::BinOpRef::Shl | s::BinOpRef::Shr) {
+            let s::Lit::Int { bits, signed, value: a } = cast_lit(&self.target.promote(lty), l)? else {
+                return None;
+            };
+            let s::Lit::Int { value: n, .. } = *r else { return None };
+            if !(0..bits as i128).contains(&n) {
+                return None;
+            }
+            let value = if op == s::BinOpRef::Shl { a << n } else { a >> n };
+            return Some(int(bits, signed, value));
+        }
+
+        let ty = self.target.arith_type(lty, rty);
+        match (cast_lit(&ty, l)?, cast_lit(&ty, r)?) {
+            (s::Lit::Int { bits, signed, value: a }, s::Lit::Int { value: b, .. }) => {
+                let value = match op {
+                    s::BinOpRef::Add => a + b,
+                    s::BinOpRef::Sub => a - b,
+                    s::BinOpRef::Mul => a.wrapping_mul(b),
+                    s::BinOpRef::Div | s::BinOpRef::Rem if b == 0 => return None,
+                    s::BinOpRef::Div | s::BinOpRef::Rem if wrap(a / b, bits, signed) != a / b => {
+                        return None;
+                    }
+                    s::BinOpRef::Div => a / b,
+                    s::BinOpRef::Rem => a % b,
+                    s::BinOpRef::BitAnd => a & b,
+                    s::BinOpRef::BitOr => a | b,
+                    s::BinOpRef::BitXor => a ^ b,
+                    s::BinOpRef::Shl | s::BinOpRef::Shr => unreachable!(),
+                };
+                Some(int(bits, signed, value))
+            }
+            (s::Lit::Float { bits, value: a }, s::Lit::Float { value: b, .. }) => {
+                let value = match op {
+                    s::BinOpRef::Add => a + b,
+                    s::BinOpRef::Sub => a - b,
+                    s::BinOpRef::Mul => a * b,
+                    s::BinOpRef::Div if b != 0.0 => a / b,
+                    _ => return None,
+                };
+                Some(s::Expr::Lit(float_lit(bits, value)))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn int(bits: u16, signed: bool, value: i128) -> s::Expr {
+    s::Expr::Lit(s::Lit::Int { bits, signed, value: wrap(value, bits, signed) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::dump_program;
+    use crate::parse::{parse_translation_unit, ParseOptions};
+
+    // the folded body of the only function, one node per line
+    fn folded(src: &str) -> String {
+        let mut p = parse_translation_unit(src, &Target::X86_64, ParseOptions::LENIENT).unwrap().0;
+        fold_program(&mut p, &Target::X86_64);
+        let out = dump_program(&p);
+        out.lines().skip(1).map(|l| format!("{}\n", l.trim())).collect()
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(folded("int f() { return 2 + 3; }"), "Return\nInt 5: i32\n");
+        assert_eq!(folded("int f() { return 2 * 3 + 1; }"), "Return\nInt 7: i32\n");
+        assert_eq!(folded("int f() { return (6 | 1) ^ 3 & 2; }"), "Return\nInt 5: i32\n");
+        assert_eq!(folded("long f() { return 1L - 3L; }"), "Return\nInt -2: i64\n");
+    }
+
+    #[test]
+    fn results_wrap_to_the_operand_type() {
+        assert_eq!(folded("unsigned f() { return 0u - 1u; }"), "Return\nInt 4294967295: u32\n");
+        assert_eq!(folded("int f() { return 2147483647 + 1; }"), "Return\nInt -2147483648: i32\n");
+        assert_eq!(wrap(300, 8, false), 44);
+        assert_eq!(wrap(200, 8, true), -56);
+    }
+
+    #[test]
+    fn partial_folding_keeps_the_rest() {
+        assert_eq!(
+            folded("int f(int x) { return x + (2 + 3); }"),
+            "Return\nBinary Add\nVar x\nInt 5: i32\n"
+        );
+    }
+
+    #[test]
+    fn operands_are_converted_first() {
+        assert_eq!(folded("long f() { return 1 + 2L; }"), "Return\nInt 3: i64\n");
+        assert_eq!(folded("unsigned f() { return -1 + 0u; }"), "Return\nInt 4294967295: u32\n");
+        assert_eq!(folded("double f() { return 1 + 0.5; }"), "Return\nFloat 1.5: f64\n");
+        assert_eq!(folded("int f() { return (char)100 + (char)100; }"), "Return\nInt 200: i32\n");
+    }
+
+    #[test]
+    fn division_and_shifts() {
+        assert_eq!(folded("int f() { return 7 / 2 - 7 % 2; }"), "Return\nInt 2: i32\n");
+        assert_eq!(folded("int f() { return -7 / 2; }"), "Return\nInt -3: i32\n");
+        assert_eq!(folded("int f() { return 1 << 4 >> 2; }"), "Return\nInt 4: i32\n");
+        assert_eq!(folded("int f() { return -16 >> 2; }"), "Return\nInt -4: i32\n");
+        assert_eq!(folded("unsigned f() { return 1u << 31; }"), "Return\nInt 2147483648: u32\n");
+    }
+
+    #[test]
+    fn undefined_operations_are_left_alone() {
+        let kept = |src: &str| folded(src).lines().nth(1).unwrap().to_string();
+        // the parser rejects a constant zero divisor or an out-of-range shift amount,
+        // but folding mustn't trap on one either
+        for (op, r) in [(s::BinOpRef::Div, 0), (s::BinOpRef::Rem, 0), (s::BinOpRef::Shl, 32)] {
+            let (one, r) = (int(32, true, 1), int(32, true, r));
+            let mut e = s::Expr::Binary { left: Box::new(one), op, right: Box::new(r) };
+            Folder { target: Target::X86_64, folded: Cell::new(0) }.expr(&mut e);
+            assert!(matches!(e, s::Expr::Binary { .. }), "{op:?}: {e:?}");
+        }
+        assert_eq!(kept("int f() { return (-2147483647 - 1) / -1; }"), "Binary Div");
+        assert_eq!(kept("double f() { return 1.0 / 0.0; }"), "Binary Div");
+    }
+
+    #[test]
+    fn comparisons_and_logic() {
+        assert_eq!(folded("int f() { return -1 < 0u; }"), "Return\nBool false\n");
+        assert_eq!(folded("int f() { return 0.5 >= 0.25; }"), "Return\nBool true\n");
+        assert_eq!(folded("int f() { return 1 < 2 && 3; }"), "Return\nBool true\n");
+        assert_eq!(folded("int f() { return 0 || 0; }"), "Return\nBool false\n");
+        // a constant left side short-circuits even when the right one isn't constant
+        assert_eq!(folded("int f(int x) { return 0 && x; }"), "Return\nBool false\n");
+        assert_eq!(
+            folded("int f(int x) { return 1 && x; }"),
+            "Return\nCmp Ne\nVar x\nInt 0: i32\n"
+        );
+    }
+
+    #[test]
+    fn nan_is_unordered() {
+        let cmp = |op| {
+            let nan = s::Expr::Lit(s::Lit::Float { bits: 64, value: f64::NAN });
+            let one = s::Expr::Lit(s::Lit::Float { bits: 64, value: 1.0 });
+            let mut e = s::Expr::Cmp { left: Box::new(nan), op, right: Box::new(one) };
+            Folder { target: Target::X86_64, folded: Cell::new(0) }.expr(&mut e);
+            match e {
+                s::Expr::Lit(s::Lit::Bool(b)) => b,
+                other => panic!("{other:?}"),
+            }
+        };
+        assert!(!cmp(s::CmpOpRef::Eq));
+        assert!(!cmp(s::CmpOpRef::Lt));
+        assert!(!cmp(s::CmpOpRef::Ge));
+        assert!(cmp(s::CmpOpRef::Ne));
+    }
+
+    #[test]
+    fn ternaries_and_casts() {
+        assert_eq!(folded("int f() { return 1 ? 4 : 5; }"), "Return\nInt 4: i32\n");
+        assert_eq!(folded("int f() { return 0 ? 4 : 5; }"), "Return\nInt 5: i32\n");
+        // arms of different types need the conversion lowering does
+        assert!(folded("long f() { return 1 ? 4 : 5L; }").contains("Ternary"));
+        let kept = folded("int f(int x) { return (unsigned char)(x, 300); }");
+        assert_eq!(kept.lines().nth(1), Some("Cast u8"));
+        assert_eq!(folded("int f() { return (unsigned char)(200 + 100); }"), "Return\nInt 44: u8\n");
+        assert_eq!(folded("double f() { return (float)(1 + 2); }"), "Return\nFloat 3.0: f32\n");
+    }
+
+    #[test]
+    fn promoted_unary_operators() {
+        assert_eq!(folded("int f() { return ~(char)0; }"), "Return\nInt -1: i32\n");
+        assert_eq!(folded("int f() { return -(unsigned char)1; }"), "Return\nInt -1: i32\n");
+        assert_eq!(folded("unsigned f() { return -1u; }"), "Return\nInt 4294967295: u32\n");
+        assert_eq!(folded("double f() { return -(0.5 + 1.0); }"), "Return\nFloat -1.5: f64\n");
+    }
+
+    #[test]
+    fn cast_lit_wraps_integers() {
+        let int = |value| s::Lit::Int { bits: 32, signed: true, value };
+        let cast = |ty: s::TypeRef, value: i128| match cast_lit(&ty, &int(value)) {
+            Some(s::Lit::Int { value, .. }) => value,
+            other => panic!("{other:?}"),
+        };
+        let ty = |bits, signed| s::TypeRef::Int { bits, signed };
+        assert_eq!(cast(ty(8, true), 300), 44);
+        assert_eq!(cast(ty(8, false), -1), 255);
+        assert_eq!(cast(ty(64, false), -1), u64::MAX as i128);
+        assert!(matches!(
+            cast_lit(&s::TypeRef::Float { bits: 32 }, &int(3)),
+            Some(s::Lit::Float { bits: 32, value }) if value == 3.0
+        ));
+        assert!(cast_lit(&s::TypeRef::Pointer(Box::new(ty(8, true))), &int(0)).is_none());
+    }
+
+    #[test]
+    fn out_of_range_float_casts_are_not_folded() {
+        assert_eq!(folded("int f() { return (int)-2.9; }"), "Return\nInt -2: i32\n");
+        let uchar = folded("unsigned char f() { return (unsigned char)255.5; }");
+        assert_eq!(uchar, "Return\nInt 255: u8\n");
+        assert_eq!(
+            folded("long f() { return (long)-9223372036854775808.0; }"),
+            "Return\nInt -9223372036854775808: i64\n"
+        );
+        for cast in ["(int)3e9", "(unsigned)-1.0", "(long)9223372036854775808.0", "(int)(0.0 / 0.0)"] {
+            let out = folded(&format!("int f() {{ return {cast}; }}"));
+            assert!(out.contains("Cast"), "{cast}: {out}");
+        }
+    }
+
+    #[test]
+    fn unsigned_comparisons_stay_unsigned() {
+        // as signed 32-bit values this would be -1 < 1
+        assert_eq!(folded("int f() { return 0xFFFFFFFFu < 1u; }"), "Return\nBool false\n");
+        assert_eq!(folded("int f() { return 1u > -1; }"), "Return\nBool false\n");
+    }
+
+    #[test]
+    fn folded_comparisons_are_ints_as_operands() {
+        assert_eq!(folded("int f() { return (1 < 2 < 3) + (3 > 2 > 1); }"), "Return\nInt 1: i32\n");
+        assert_eq!(folded("int f() { return -(2 == 2); }"), "Return\nInt -1: i32\n");
+        assert_eq!(folded("int f() { return 5 == 5 == 1; }"), "Return\nBool true\n");
+    }
+
+    #[test]
+    fn conversions_to_bool() {
+        assert_eq!(folded("_Bool f() { return (_Bool)0.5; }"), "Return\nInt 1: bool\n");
+        assert_eq!(folded("_Bool f() { return (_Bool)256; }"), "Return\nInt 1: bool\n");
+        assert_eq!(folded("int f() { return (_Bool)0 + (1 < 2); }"), "Return\nInt 1: i32\n");
+    }
+
+    #[test]
+    fn local_consts_are_propagated() {
+        assert_eq!(folded("int f(void) { const int k = 4; return k * k; }"), "Return\nInt 16: i32\n");
+        let chained = folded("int f(void) { const int a = 2; const int b = a + 1; return b; }");
+        assert_eq!(chained, "Return\nInt 3: i32\n");
+        let pinned = folded("int f(void) { const int k = 4; const int *p = &k; return k + *p; }");
+        assert!(pinned.starts_with("ConstDecl k"), "{pinned}");
+    }
+
+    #[test]
+    fn counts_folded_expressions() {
+        let src = "int f(void) { return 1 + 2 + 3; } int g(int x) { return x; }";
+        let mut p = parse_translation_unit(src, &Target::X86_64, ParseOptions::LENIENT).unwrap().0;
+        assert_eq!(fold_program(&mut p, &Target::X86_64), 2);
+        assert_eq!(fold_program(&mut p, &Target::X86_64), 0);
+    }
+}
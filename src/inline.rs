@@ -0,0 +1,310 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::dce::is_pure;
+use ir::lower_ast::frontend as s;
+
+// callee bodies larger than this many expression nodes are left as calls
+const MAX_NODES: usize = 16;
+
+/// Replaces calls to small leaf functions with their bodies. A callee is
+/// inlined when its whole body is `return e;`, `e` is pure (no calls, so it
+/// isn't recursive either, and no increments) and at most `MAX_NODES` nodes,
+/// and every parameter and the return type is a scalar. A call is only
+/// replaced when its arguments are pure, since an inlined parameter may be
+/// read any number of times, including none. With both sides pure, reading an
+/// argument later than the call would have can't observe a different value.
+/// Arguments and the result are cast to the declared types, as the call would
+/// convert them. Run constant folding afterwards to fold the inlined code.
+/// Returns how many calls were inlined.
+pub fn inline_program(p: &mut s::Program) -> usize {
+    let leaves: Vec<s::Function> = p.functions.iter().filter(|f| is_leaf(f)).cloned().collect();
+    let mut inlined = 0;
+    for func in &mut p.functions {
+        // a callee's globals must not be captured by a caller's local of the same name
+        let mut locals: Vec<String> = func.parameters.iter().map(|p| p.name.clone()).collect();
+        declared(&func.body, &mut locals);
+        let mut inl = Inliner { leaves: &leaves, locals, inlined: 0 };
+        inl.stmts(&mut func.body);
+        inlined += inl.inlined;
+    }
+    inlined
+}
+
+fn is_leaf(f: &s::Function) -> bool {
+    let [s::Stmt::Return(Some(e))] = f.body.as_slice() else { return false };
+    is_scalar(&f.return_type)
+        && f.parameters.iter().all(|p| is_scalar(&p.ty))
+        && is_pure(e)
+        && size(e).is_some_and(|n| n <= MAX_NODES)
+}
+
+// bool is left out: converting to it is a comparison, not a cast
+fn is_scalar(ty: &s::TypeRef) -> bool {
+    match ty {
+        s::TypeRef::Int { bits, .. } => *bits > 1,
+        s::TypeRef::Float { .. } | s::TypeRef::Pointer(_) => true,
+        _ => false,
+    }
+}
+
+// node count, or `None` if the expression calls anything
+fn size(e: &s::Expr) -> Option<usize> {
+    let n = match e {
+        s::Expr::Lit(_) | s::Expr::Var(_) | s::Expr::IncDec { .. } => 0,
+        s::Expr::Call { .. } => return None,
+        s::Expr::Unary { expr, .. }
+        | s::Expr::Member { base: expr, .. }
+        | s::Expr::Cast { expr, .. } => size(expr)?,
+        s::Expr::Binary { left, right, .. }
+        | s::Expr::Cmp { left, right, .. }
+        | s::Expr::Logical { left, right, .. }
+        | s::Expr::Comma { left, right } => size(left)? + size(right)?,
+        s::Expr::Ternary { cond, then_expr, else_expr } => {
+            size(cond)? + size(then_expr)? + size(else_expr)?
+        }
+    };
+    Some(n + 1)
+}
+
+fn declared(body: &[s::Stmt], out: &mut Vec<String>) {
+    for st in body {
+        match st {
+            s::Stmt::VarDecl { name, .. } | s::Stmt::ConstDecl { name, .. } => out.push(name.clone()),
+            s::Stmt::If { then_body, else_body, .. } => {
+                declared(then_body, out);
+                declared(else_body, out);
+            }
+            s::Stmt::While { body, .. } => declared(body, out),
+            s::Stmt::Switch { cases, .. } => {
+                for case in cases {
+                    declared(&case.body, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+struct Inliner<'a> {
+    leaves: &'a [s::Function],
+    // parameters and locals of the caller
+    locals: Vec<String>,
+    inlined: usize,
+}
+
+impl Inliner<'_> {
+    fn stmts(&mut self, body: &mut [s::Stmt]) {
+        for st in body {
+            self.stmt(st);
+        }
+    }
+
+    fn stmt(&mut self, st: &mut s::Stmt) {
+        match st {
+            s::Stmt::VarDecl { init: Some(e), .. }
+            | s::Stmt::ConstDecl { init: e, .. }
+            | s::Stmt::Assign { value: e, .. }
+            | s::Stmt::ExprStmt(e)
+            | s::Stmt::Return(Some(e)) => self.expr(e),
+            s::Stmt::Store { target, value } => {
+                self.expr(target);
+                self.expr(value);
+            }
+            s::Stmt::If { cond, then_body, else_body } => {
+                self.expr(cond);
+                self.stmts(then_body);
+                self.stmts(else_body);
+            }
+            s::Stmt::While { cond, body } => {
+                self.expr(cond);
+                self.stmts(body);
+            }
+            s::Stmt::Switch { value, cases } => {
+                self.expr(value);
+                for case in cases {
+                    self.stmts(&mut case.body);
+                }
+            }
+            s::Stmt::VarDecl { init: None, .. }
+            | s::Stmt::Break
+            | s::Stmt::Continue
+            | s::Stmt::Goto(_)
+            | s::Stmt::Label(_)
+            | s::Stmt::Return(None) => {}
+        }
+    }
+
+    fn expr(&mut self, e: &mut s::Expr) {
+        match e {
+            s::Expr::Lit(_) | s::Expr::Var(_) | s::Expr::IncDec { .. } => {}
+            s::Expr::Unary { expr, .. }
+            | s::Expr::Member { base: expr, .. }
+            | s::Expr::Cast { expr, .. } => self.expr(expr),
+            s::Expr::Binary { left, right, .. }
+            | s::Expr::Cmp { left, right, .. }
+            | s::Expr::Logical { left, right, .. }
+            | s::Expr::Comma { left, right } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            s::Expr::Call { args, .. } => {
+                for a in args {
+                    self.expr(a);
+                }
+            }
+            s::Expr::Ternary { cond, then_expr, else_expr } => {
+                self.expr(cond);
+                self.expr(then_expr);
+                self.expr(else_expr);
+            }
+        }
+        let s::Expr::Call { callee, args } = e else { return };
+        if let Some(body) = self.inlined(callee, args) {
+            *e = body;
+            self.inlined += 1;
+        }
+    }
+
+    fn inlined(&self, callee: &str, args: &[s::Expr]) -> Option<s::Expr> {
+        let f = self.leaves.iter().find(|f| f.name == callee)?;
+        if f.parameters.len() != args.len() || !args.iter().all(is_pure) {
+            return None;
+        }
+        let [s::Stmt::Return(Some(body))] = f.body.as_slice() else { return None };
+        let args: Vec<(&str, s::Expr)> = f
+            .parameters
+            .iter()
+            .zip(args)
+            .map(|(p, a)| (p.name.as_str(), cast(p.ty.clone(), a.clone())))
+            .collect();
+        let body = self.substitute(body, &args)?;
+        Some(cast(f.return_type.clone(), body))
+    }
+
+    // `e` with each parameter replaced by its argument; `None` if a parameter has
+    // its address taken, or a global of the callee is shadowed here
+    fn substitute(&self, e: &s::Expr, args: &[(&str, s::Expr)]) -> Option<s::Expr> {
+        let sub = |e: &s::Expr| self.substitute(e, args).map(Box::new);
+        let is_param = |name: &str| args.iter().any(|(p, _)| *p == name);
+        Some(match e {
+            s::Expr::Lit(_) => e.clone(),
+            s::Expr::Var(name) => match args.iter().find(|(p, _)| p == name) {
+                Some((_, arg)) => arg.clone(),
+                None if self.locals.contains(name) => return None,
+                None => e.clone(),
+            },
+            s::Expr::Unary { op: s::UnOpRef::AddrOf, expr } => {
+                let mut base = &**expr;
+                while let s::Expr::Member { base: b, .. } = base {
+                    base = b;
+                }
+                if matches!(base, s::Expr::Var(name) if is_param(name)) {
+                    return None;
+                }
+                s::Expr::Unary { op: s::UnOpRef::AddrOf, expr: sub(expr)? }
+            }
+            s::Expr::Unary { op, expr } => s::Expr::Unary { op: *op, expr: sub(expr)? },
+            s::Expr::Member { base, field } => {
+                s::Expr::Member { base: sub(base)?, field: field.clone() }
+            }
+            s::Expr::Cast { ty, expr } => s::Expr::Cast { ty: ty.clone(), expr: sub(expr)? },
+            s::Expr::Binary { left, op, right } => {
+                s::Expr::Binary { left: sub(left)?, op: *op, right: sub(right)? }
+            }
+            s::Expr::Cmp { left, op, right } => {
+                s::Expr::Cmp { left: sub(left)?, op: *op, right: sub(right)? }
+            }
+            s::Expr::Logical { left, op, right } => {
+                s::Expr::Logical { left: sub(left)?, op: *op, right: sub(right)? }
+            }
+            s::Expr::Comma { left, right } => s::Expr::Comma { left: sub(left)?, right: sub(right)? },
+            s::Expr::Ternary { cond, then_expr, else_expr } => s::Expr::Ternary {
+                cond: sub(cond)?,
+                then_expr: sub(then_expr)?,
+                else_expr: sub(else_expr)?,
+            },
+            // a leaf's body is pure
+            s::Expr::Call { .. } | s::Expr::IncDec { .. } => return None,
+        })
+    }
+}
+
+fn cast(ty: s::TypeRef, e: s::Expr) -> s::Expr {
+    s::Expr::Cast { ty, expr: Box::new(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::dump_program;
+    use crate::parse::{parse_translation_unit, ParseOptions};
+    use crate::target::Target;
+
+    fn program(src: &str) -> s::Program {
+        parse_translation_unit(src, &Target::X86_64, ParseOptions::LENIENT).unwrap().0
+    }
+
+    // the body of `main` after inlining and folding, one node per line
+    fn main_body(src: &str) -> String {
+        let mut p = program(src);
+        inline_program(&mut p);
+        crate::fold::fold_program(&mut p, &Target::X86_64);
+        let out = dump_program(&p);
+        let body = out.lines().skip_while(|l| !l.starts_with("fn main(")).skip(1);
+        body.map(|l| format!("{}\n", l.trim())).collect()
+    }
+
+    #[test]
+    fn small_leaves_are_inlined_and_folded() {
+        let src = "int add(int a, int b) { return a + b; } int main(void) { return add(2, 3); }";
+        assert_eq!(main_body(src), "Return\nInt 5: i32\n");
+    }
+
+    #[test]
+    fn arguments_are_converted() {
+        let src = "long twice(long a) { return a * 2; } long main(void) { return twice(4) + 1; }";
+        assert_eq!(main_body(src), "Return\nInt 9: i64\n");
+    }
+
+    #[test]
+    fn recursive_and_large_functions_stay_calls() {
+        let recursive = "int f(int n) { return n ? f(n - 1) : 0; } int main(void) { return f(3); }";
+        assert!(main_body(recursive).contains("Call f"));
+        let large = "int big(int a) { return a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a; } \
+                     int main(void) { return big(2); }";
+        assert!(main_body(large).contains("Call big"));
+        let statements = "int g(int a) { int b = a; return b; } int main(void) { return g(1); }";
+        assert!(main_body(statements).contains("Call g"));
+    }
+
+    #[test]
+    fn impure_arguments_stay_calls() {
+        let src = "int id(int a) { return a; } int main(void) { int x = 0; return id(x++); }";
+        assert!(main_body(src).contains("Call id"));
+    }
+
+    #[test]
+    fn callee_globals_are_not_captured() {
+        let src = "int n = 1; int get(void) { return n; } \
+                   int main(void) { int n = 2; return get(); }";
+        assert_eq!(main_body(src), "VarDecl n.1: i32\nInt 2: i32\nReturn\nCast i32\nVar n\n");
+    }
+
+    #[test]
+    fn counts_inlined_calls() {
+        let mut p = program(
+            "int sq(int a) { return a * a; } int main(void) { int x = 3; return sq(x) + sq(2); }",
+        );
+        assert_eq!(inline_program(&mut p), 2);
+        assert_eq!(inline_program(&mut p), 0);
+    }
+
+    #[test]
+    fn impure_callees_stay_calls() {
+        // inlined, `g` would be read after the increment rather than before the call
+        let src = "int g; int bump(int a) { return g++ + a; } int main(void) { return bump(g); }";
+        assert!(main_body(src).contains("Call bump"));
+        let src = "int g; int next(void) { return ++g; } int main(void) { return g + next(); }";
+        assert!(main_body(src).contains("Call next"));
+    }
+}
@@ -3,14 +3,33 @@
 #[derive(Clone, Debug, PartialEq)]
 pub enum Tok {
     // keywords
+    Char,
+    Short,
     Int,
+    Long,
+    Float,
+    Double,
+    Signed,
     Unsigned,
+    Bool,
     Void,
+    Struct,
+    Enum,
+    Typedef,
     Const,
+    Extern,
+    Static,
+    Sizeof,
     Return,
     If,
     Else,
     While,
+    For,
+    Do,
+    Switch,
+    Case,
+    Default,
+    Goto,
     Break,
     Continue,
     True,
@@ -18,15 +37,25 @@ pub enum Tok {
 
     // identifiers / literals
     Ident(String),
-    IntLit(i128),
+    IntLit(i128, IntSuffix),
+    FloatLit(f64),
+    CharLit(i128),
+    StrLit(Vec<u8>),
 
     // punct
     LParen,
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
     Semi,
     Comma,
+    Question,
+    Colon,
+    Dot,
+    Arrow,
+    Hash,
 
     // ops
     Assign,   // =
@@ -40,10 +69,156 @@ pub enum Tok {
     Plus,     // +
     Minus,    // -
     Star,     // *
+    Slash,    // /
+    Percent,  // %
+    Bang,     // !
+    AndAnd,   // &&
+    OrOr,     // ||
+    Amp,      // &
+    Pipe,     // |
+    Caret,    // ^
+    Tilde,    // ~
+    Shl,      // <<
+    Shr,      // >>
+
+    PlusPlus,      // ++
+    MinusMinus,    // --
+    PlusAssign,    // +=
+    MinusAssign,   // -=
+    StarAssign,    // *=
+    SlashAssign,   // /=
+    PercentAssign, // %=
+    AmpAssign,     // &=
+    PipeAssign,    // |=
+    CaretAssign,   // ^=
+    ShlAssign,     // <<=
+    ShrAssign,     // >>=
 
     Eof,
 }
 
+impl Tok {
+    /// The token as a user would write it, quoted, for diagnostics: `'foo'`,
+    /// `')'`, `'42u'`. String and character literals and the end of input
+    /// are named instead. `Debug` stays the internal spelling.
+    pub fn describe(&self) -> String {
+        let text = match self {
+            Tok::Char => "char",
+            Tok::Short => "short",
+            Tok::Int => "int",
+            Tok::Long => "long",
+            Tok::Float => "float",
+            Tok::Double => "double",
+            Tok::Signed => "signed",
+            Tok::Unsigned => "unsigned",
+            Tok::Bool => "_Bool",
+            Tok::Void => "void",
+            Tok::Struct => "struct",
+            Tok::Enum => "enum",
+            Tok::Typedef => "typedef",
+            Tok::Const => "const",
+            Tok::Extern => "extern",
+            Tok::Static => "static",
+            Tok::Sizeof => "sizeof",
+            Tok::Return => "return",
+            Tok::If => "if",
+            Tok::Else => "else",
+            Tok::While => "while",
+            Tok::For => "for",
+            Tok::Do => "do",
+            Tok::Switch => "switch",
+            Tok::Case => "case",
+            Tok::Default => "default",
+            Tok::Goto => "goto",
+            Tok::Break => "break",
+            Tok::Continue => "continue",
+            Tok::True => "true",
+            Tok::False => "false",
+            Tok::Ident(name) => return format!("'{}'", name),
+            Tok::IntLit(v, suffix) => {
+                let u = if suffix.unsigned { "u" } else { "" };
+                return format!("'{}{}{}'", v, u, "l".repeat(suffix.longs as usize));
+            }
+            Tok::FloatLit(v) => return format!("'{:?}'", v),
+            Tok::CharLit(_) => return "character literal".to_string(),
+            Tok::StrLit(_) => return "string literal".to_string(),
+            Tok::LParen => "(",
+            Tok::RParen => ")",
+            Tok::LBrace => "{",
+            Tok::RBrace => "}",
+            Tok::LBracket => "[",
+            Tok::RBracket => "]",
+            Tok::Semi => ";",
+            Tok::Comma => ",",
+            Tok::Question => "?",
+            Tok::Colon => ":",
+            Tok::Dot => ".",
+            Tok::Arrow => "->",
+            Tok::Hash => "#",
+            Tok::Assign => "=",
+            Tok::EqEq => "==",
+            Tok::NotEq => "!=",
+            Tok::Lt => "<",
+            Tok::Le => "<=",
+            Tok::Gt => ">",
+            Tok::Ge => ">=",
+            Tok::Plus => "+",
+            Tok::Minus => "-",
+            Tok::Star => "*",
+            Tok::Slash => "/",
+            Tok::Percent => "%",
+            Tok::Bang => "!",
+            Tok::AndAnd => "&&",
+            Tok::OrOr => "||",
+            Tok::Amp => "&",
+            Tok::Pipe => "|",
+            Tok::Caret => "^",
+            Tok::Tilde => "~",
+            Tok::Shl => "<<",
+            Tok::Shr => ">>",
+            Tok::PlusPlus => "++",
+            Tok::MinusMinus => "--",
+            Tok::PlusAssign => "+=",
+            Tok::MinusAssign => "-=",
+            Tok::StarAssign => "*=",
+            Tok::SlashAssign => "/=",
+            Tok::PercentAssign => "%=",
+            Tok::AmpAssign => "&=",
+            Tok::PipeAssign => "|=",
+            Tok::CaretAssign => "^=",
+            Tok::ShlAssign => "<<=",
+            Tok::ShrAssign => ">>=",
+            Tok::Eof => return "end of input".to_string(),
+        };
+        format!("'{}'", text)
+    }
+}
+
+/// `u`/`U` and `l`/`L`/`ll`/`LL` suffix on an integer literal, in either order.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IntSuffix {
+    pub unsigned: bool,
+    pub longs: u8,
+}
+
+/// Source range of a token: byte offsets `start..end` plus the line/col of `start`.
+/// `logical_line` counts lines after `\`-newline continuations are joined, so a
+/// continued directive stays on one; diagnostics use the physical `line`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+    pub logical_line: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
 #[derive(Clone, Debug)]
 pub struct LexError {
     pub msg: String,
@@ -57,13 +232,17 @@ impl std::fmt::Display for LexError {
     }
 }
 
-pub fn lex_all(src: &str) -> Result<Vec<Tok>, LexError> {
+pub fn lex_all(src: &str) -> Result<Vec<Spanned<Tok>>, LexError> {
     let mut lx = Lexer::new(src);
     let mut out = Vec::new();
     loop {
+        lx.skip_ws_and_comments()?;
+        let (start, line, col) = (lx.i, lx.line, lx.col);
+        let logical_line = lx.line - lx.spliced;
         let t = lx.next_tok()?;
         let end = matches!(t, Tok::Eof);
-        out.push(t);
+        let span = Span { start, end: lx.i, line, col, logical_line };
+        out.push(Spanned { value: t, span });
         if end { break; }
     }
     Ok(out)
@@ -74,15 +253,21 @@ struct Lexer<'a> {
     i: usize,
     line: usize,
     col: usize,
+    // `\`-newline continuations seen so far
+    spliced: usize,
 }
 
 impl<'a> Lexer<'a> {
     fn new(src: &'a str) -> Self {
-        Self { s: src.as_bytes(), i: 0, line: 1, col: 1 }
+        Self { s: src.as_bytes(), i: 0, line: 1, col: 1, spliced: 0 }
     }
 
     fn err<T>(&self, msg: impl Into<String>) -> Result<T, LexError> {
-        Err(LexError { msg: msg.into(), line: self.line, col: self.col })
+        self.err_at(self.line, self.col, msg)
+    }
+
+    fn err_at<T>(&self, line: usize, col: usize, msg: impl Into<String>) -> Result<T, LexError> {
+        Err(LexError { msg: msg.into(), line, col })
     }
 
     fn peek(&self) -> Option<u8> {
@@ -92,11 +277,16 @@ impl<'a> Lexer<'a> {
     fn bump(&mut self) -> Option<u8> {
         let c = self.s.get(self.i).copied()?;
         self.i += 1;
-        if c == b'\n' {
-            self.line += 1;
-            self.col = 1;
-        } else {
-            self.col += 1;
+        match c {
+            // `\r\n` ends one line, at its `\n`; a lone `\r` is an old Mac line end
+            b'\r' if self.peek() == Some(b'\n') => {}
+            b'\n' | b'\r' => {
+                self.line += 1;
+                self.col = 1;
+            }
+            // UTF-8 continuation bytes belong to the previous column
+            c if c & 0xC0 == 0x80 => {}
+            _ => self.col += 1,
         }
         Some(c)
     }
@@ -105,28 +295,63 @@ impl<'a> Lexer<'a> {
         self.s.get(self.i..self.i + pat.len()) == Some(pat)
     }
 
+    // a backslash ending a line joins it to the next, as if neither were there
+    fn skip_splice(&mut self) -> bool {
+        let len = if self.starts_with(b"\\\n") {
+            2
+        } else if self.starts_with(b"\\\r\n") {
+            3
+        } else if self.starts_with(b"\\\r") {
+            2
+        } else {
+            return false;
+        };
+        for _ in 0..len {
+            self.bump();
+        }
+        self.spliced += 1;
+        true
+    }
+
     fn skip_ws_and_comments(&mut self) -> Result<(), LexError> {
         loop {
             while matches!(self.peek(), Some(b' ' | b'\t' | b'\r' | b'\n')) {
                 self.bump();
             }
+            if self.skip_splice() {
+                continue;
+            }
 
+            // a continued line continues the comment too
             if self.starts_with(b"//") {
-                while let Some(c) = self.bump() {
-                    if c == b'\n' { break; }
+                loop {
+                    if self.skip_splice() {
+                        continue;
+                    }
+                    match self.bump() {
+                        Some(b'\n' | b'\r') | None => break,
+                        Some(_) => {}
+                    }
                 }
                 continue;
             }
 
+            // block comments nest: `/* a /* b */ c */` is one comment
             if self.starts_with(b"/*") {
+                let (line, col) = (self.line, self.col);
                 self.bump(); self.bump();
-                while self.i < self.s.len() && !self.starts_with(b"*/") {
-                    self.bump();
+                let mut depth = 1;
+                while depth > 0 {
+                    if self.starts_with(b"/*") {
+                        self.bump(); self.bump();
+                        depth += 1;
+                    } else if self.starts_with(b"*/") {
+                        self.bump(); self.bump();
+                        depth -= 1;
+                    } else if self.bump().is_none() {
+                        return self.err_at(line, col, "unterminated block comment");
+                    }
                 }
-                if !self.starts_with(b"*/") {
-                    return self.err("unterminated block comment");
-                }
-                self.bump(); self.bump();
                 continue;
             }
 
@@ -136,15 +361,32 @@ impl<'a> Lexer<'a> {
     }
 
     fn next_tok(&mut self) -> Result<Tok, LexError> {
-        self.skip_ws_and_comments()?;
-
         let Some(c) = self.peek() else { return Ok(Tok::Eof); };
 
+        // three-char ops
+        if self.starts_with(b"<<=") { self.bump(); self.bump(); self.bump(); return Ok(Tok::ShlAssign); }
+        if self.starts_with(b">>=") { self.bump(); self.bump(); self.bump(); return Ok(Tok::ShrAssign); }
+
         // two-char ops
         if self.starts_with(b"==") { self.bump(); self.bump(); return Ok(Tok::EqEq); }
         if self.starts_with(b"!=") { self.bump(); self.bump(); return Ok(Tok::NotEq); }
+        if self.starts_with(b"<<") { self.bump(); self.bump(); return Ok(Tok::Shl); }
+        if self.starts_with(b">>") { self.bump(); self.bump(); return Ok(Tok::Shr); }
         if self.starts_with(b"<=") { self.bump(); self.bump(); return Ok(Tok::Le); }
         if self.starts_with(b">=") { self.bump(); self.bump(); return Ok(Tok::Ge); }
+        if self.starts_with(b"&&") { self.bump(); self.bump(); return Ok(Tok::AndAnd); }
+        if self.starts_with(b"||") { self.bump(); self.bump(); return Ok(Tok::OrOr); }
+        if self.starts_with(b"->") { self.bump(); self.bump(); return Ok(Tok::Arrow); }
+        if self.starts_with(b"++") { self.bump(); self.bump(); return Ok(Tok::PlusPlus); }
+        if self.starts_with(b"--") { self.bump(); self.bump(); return Ok(Tok::MinusMinus); }
+        if self.starts_with(b"+=") { self.bump(); self.bump(); return Ok(Tok::PlusAssign); }
+        if self.starts_with(b"-=") { self.bump(); self.bump(); return Ok(Tok::MinusAssign); }
+        if self.starts_with(b"*=") { self.bump(); self.bump(); return Ok(Tok::StarAssign); }
+        if self.starts_with(b"/=") { self.bump(); self.bump(); return Ok(Tok::SlashAssign); }
+        if self.starts_with(b"%=") { self.bump(); self.bump(); return Ok(Tok::PercentAssign); }
+        if self.starts_with(b"&=") { self.bump(); self.bump(); return Ok(Tok::AmpAssign); }
+        if self.starts_with(b"|=") { self.bump(); self.bump(); return Ok(Tok::PipeAssign); }
+        if self.starts_with(b"^=") { self.bump(); self.bump(); return Ok(Tok::CaretAssign); }
 
         // single-char
         match c {
@@ -152,8 +394,17 @@ impl<'a> Lexer<'a> {
             b')' => { self.bump(); return Ok(Tok::RParen); }
             b'{' => { self.bump(); return Ok(Tok::LBrace); }
             b'}' => { self.bump(); return Ok(Tok::RBrace); }
+            b'[' => { self.bump(); return Ok(Tok::LBracket); }
+            b']' => { self.bump(); return Ok(Tok::RBracket); }
             b';' => { self.bump(); return Ok(Tok::Semi); }
             b',' => { self.bump(); return Ok(Tok::Comma); }
+            b'?' => { self.bump(); return Ok(Tok::Question); }
+            b'#' => { self.bump(); return Ok(Tok::Hash); }
+            b':' => { self.bump(); return Ok(Tok::Colon); }
+            b'.' if !self.s.get(self.i + 1).is_some_and(|d| d.is_ascii_digit()) => {
+                self.bump();
+                return Ok(Tok::Dot);
+            }
 
             b'=' => { self.bump(); return Ok(Tok::Assign); }
             b'<' => { self.bump(); return Ok(Tok::Lt); }
@@ -162,17 +413,29 @@ impl<'a> Lexer<'a> {
             b'+' => { self.bump(); return Ok(Tok::Plus); }
             b'-' => { self.bump(); return Ok(Tok::Minus); }
             b'*' => { self.bump(); return Ok(Tok::Star); }
+            b'/' => { self.bump(); return Ok(Tok::Slash); }
+            b'%' => { self.bump(); return Ok(Tok::Percent); }
+            b'!' => { self.bump(); return Ok(Tok::Bang); }
+            b'&' => { self.bump(); return Ok(Tok::Amp); }
+            b'|' => { self.bump(); return Ok(Tok::Pipe); }
+            b'^' => { self.bump(); return Ok(Tok::Caret); }
+            b'~' => { self.bump(); return Ok(Tok::Tilde); }
             _ => {}
         }
 
+        if c == b'\'' {
+            return self.lex_char_lit();
+        }
+        if c == b'"' {
+            return self.lex_str_lit();
+        }
+
         // number
+        if c == b'.' || (c.is_ascii_digit() && self.is_float_ahead()) {
+            return self.lex_float();
+        }
         if c.is_ascii_digit() {
-            let mut v: i128 = 0;
-            while let Some(d) = self.peek().filter(|x| x.is_ascii_digit()) {
-                self.bump();
-                v = v * 10 + (d - b'0') as i128;
-            }
-            return Ok(Tok::IntLit(v));
+            return self.lex_number();
         }
 
         // ident / keyword
@@ -182,17 +445,38 @@ impl<'a> Lexer<'a> {
                 let _ = x;
                 self.bump();
             }
-            let text = std::str::from_utf8(&self.s[start..self.i]).unwrap();
+            let Ok(text) = std::str::from_utf8(&self.s[start..self.i]) else {
+                return self.err("invalid UTF-8 in identifier");
+            };
 
             return Ok(match text {
+                "char" => Tok::Char,
+                "short" => Tok::Short,
                 "int" => Tok::Int,
+                "long" => Tok::Long,
+                "float" => Tok::Float,
+                "double" => Tok::Double,
+                "signed" => Tok::Signed,
                 "unsigned" => Tok::Unsigned,
+                "_Bool" | "bool" => Tok::Bool,
                 "void" => Tok::Void,
+                "struct" => Tok::Struct,
+                "enum" => Tok::Enum,
+                "typedef" => Tok::Typedef,
                 "const" => Tok::Const,
+                "extern" => Tok::Extern,
+                "static" => Tok::Static,
+                "sizeof" => Tok::Sizeof,
                 "return" => Tok::Return,
                 "if" => Tok::If,
                 "else" => Tok::Else,
                 "while" => Tok::While,
+                "for" => Tok::For,
+                "do" => Tok::Do,
+                "switch" => Tok::Switch,
+                "case" => Tok::Case,
+                "default" => Tok::Default,
+                "goto" => Tok::Goto,
                 "break" => Tok::Break,
                 "continue" => Tok::Continue,
                 "true" => Tok::True,
@@ -201,6 +485,705 @@ impl<'a> Lexer<'a> {
             });
         }
 
-        self.err(format!("unexpected char: {:?}", c as char))
+        self.err(format!("unexpected char: {:?}", self.char_at(self.i)))
+    }
+
+    // the whole character starting at byte `i`; tokens only end on ASCII bytes, so this is
+    // always a character boundary, but a stray byte is shown as U+FFFD rather than trusted
+    fn char_at(&self, i: usize) -> char {
+        let end = self.s.len().min(i + 4);
+        let text = self.s.get(i..end).unwrap_or_default();
+        let valid = match std::str::from_utf8(text) {
+            Ok(t) => t,
+            Err(e) => std::str::from_utf8(&text[..e.valid_up_to()]).unwrap_or_default(),
+        };
+        valid.chars().next().unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+
+    // 0x1F | 0b1010 | 0755 | 42 | 1_000_000
+    fn lex_number(&mut self) -> Result<Tok, LexError> {
+        let (line, col) = (self.line, self.col);
+        let (radix, name) = if self.starts_with(b"0x") || self.starts_with(b"0X") {
+            self.bump(); self.bump();
+            if self.is_hex_float_ahead() {
+                return self.lex_hex_float(line, col);
+            }
+            (16, "hexadecimal")
+        } else if self.starts_with(b"0b") || self.starts_with(b"0B") {
+            self.bump(); self.bump();
+            (2, "binary")
+        } else if self.starts_with(b"0") {
+            (8, "octal")
+        } else {
+            (10, "decimal")
+        };
+
+        let is_digit = if radix == 16 { u8::is_ascii_hexdigit } else { u8::is_ascii_digit };
+        let mut v: i128 = 0;
+        let mut ndigits = 0;
+        loop {
+            if self.digit_separator(is_digit)? {
+                continue;
+            }
+            let Some(c) = self.peek().filter(|x| x.is_ascii_alphanumeric()) else { break };
+            if matches!(c, b'u' | b'U' | b'l' | b'L') {
+                break;
+            }
+            let Some(d) = (c as char).to_digit(radix) else {
+                return self.err(format!("invalid digit {:?} in {} literal", c as char, name));
+            };
+            self.bump();
+            v = match v.checked_mul(radix as i128).and_then(|v| v.checked_add(d as i128)) {
+                Some(v) => v,
+                None => return self.err_at(line, col, "integer literal too large"),
+            };
+            ndigits += 1;
+        }
+
+        if ndigits == 0 {
+            return self.err(format!("missing digits in {} literal", name));
+        }
+        let suffix = self.lex_int_suffix()?;
+
+        // nothing wider than `unsigned long long` exists to hold it
+        if v > u64::MAX as i128 {
+            return self.err_at(line, col, "integer literal too large");
+        }
+        Ok(Tok::IntLit(v, suffix))
+    }
+
+    // decimal digits followed by '.' or an exponent
+    fn is_float_ahead(&self) -> bool {
+        let digits = self.s[self.i..].iter().take_while(|c| c.is_ascii_digit() || **c == b'_').count();
+        matches!(self.s.get(self.i + digits), Some(b'.' | b'e' | b'E'))
+    }
+
+    // 3.14 | .5 | 1. | 1e-3 | 2.5E+10
+    fn lex_float(&mut self) -> Result<Tok, LexError> {
+        let (line, col) = (self.line, self.col);
+        let start = self.i;
+        self.skip_digits()?;
+        if self.peek() == Some(b'.') {
+            self.bump();
+            self.skip_digits()?;
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.bump();
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.bump();
+            }
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return self.err("missing digits in exponent");
+            }
+            self.skip_digits()?;
+        }
+
+        let Ok(text) = std::str::from_utf8(&self.s[start..self.i]) else {
+            return self.err_at(line, col, "invalid UTF-8 in floating literal");
+        };
+        if self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == b'_') {
+            return self.err(format!("invalid suffix on floating literal {:?}", text));
+        }
+        match text.replace('_', "").parse::<f64>() {
+            Ok(v) if v.is_finite() => Ok(Tok::FloatLit(v)),
+            _ => self.err_at(line, col, "floating literal out of range"),
+        }
+    }
+
+    // hex digits followed by '.' or a binary exponent, just past the `0x`
+    fn is_hex_float_ahead(&self) -> bool {
+        let digits =
+            self.s[self.i..].iter().take_while(|c| c.is_ascii_hexdigit() || **c == b'_').count();
+        matches!(self.s.get(self.i + digits), Some(b'.' | b'p' | b'P'))
+    }
+
+    // 0x1.8p3 | 0x.1p-2 | 0x1p4, just past the `0x`; the exponent is required
+    fn lex_hex_float(&mut self, line: usize, col: usize) -> Result<Tok, LexError> {
+        // the mantissa keeps 120 bits; later nonzero digits only matter for rounding
+        let (mut m, mut exp, mut sticky, mut ndigits) = (0u128, 0i64, false, 0);
+        let mut frac = false;
+        loop {
+            match self.peek() {
+                Some(b'.') if !frac => frac = true,
+                Some(b'_') => {
+                    self.digit_separator(u8::is_ascii_hexdigit)?;
+                    continue;
+                }
+                Some(c) if c.is_ascii_hexdigit() => {
+                    let d = (c as char).to_digit(16).unwrap_or(0) as u128;
+                    if m >> 120 == 0 {
+                        m = m << 4 | d;
+                        exp -= if frac { 4 } else { 0 };
+                    } else {
+                        sticky |= d != 0;
+                        exp += if frac { 0 } else { 4 };
+                    }
+                    ndigits += 1;
+                }
+                _ => break,
+            }
+            self.bump();
+        }
+        if ndigits == 0 {
+            return self.err("missing digits in hexadecimal floating literal");
+        }
+        if !matches!(self.peek(), Some(b'p' | b'P')) {
+            return self.err("hexadecimal floating literal requires a `p` exponent");
+        }
+        self.bump();
+        let neg = match self.peek() {
+            Some(b'-') => { self.bump(); true }
+            Some(b'+') => { self.bump(); false }
+            _ => false,
+        };
+        if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            return self.err("missing digits in exponent");
+        }
+        let mut e: i64 = 0;
+        loop {
+            if self.digit_separator(u8::is_ascii_digit)? {
+                continue;
+            }
+            let Some(c) = self.peek().filter(|c| c.is_ascii_digit()) else { break };
+            self.bump();
+            // far past the range of f64 either way
+            e = (e * 10 + (c - b'0') as i64).min(100_000);
+        }
+        if self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == b'_') {
+            return self.err("invalid suffix on hexadecimal floating literal");
+        }
+        exp += if neg { -e } else { e };
+
+        // u128 -> f64 rounds to nearest; scaling by powers of two is exact short of
+        // the subnormal range
+        let mut v = (m | sticky as u128) as f64;
+        while exp != 0 && v != 0.0 && v.is_finite() {
+            let step = exp.clamp(-1000, 1000);
+            v *= 2f64.powi(step as i32);
+            exp -= step;
+        }
+        if v.is_finite() {
+            Ok(Tok::FloatLit(v))
+        } else {
+            self.err_at(line, col, "floating literal out of range")
+        }
+    }
+
+    // skips a `_` that separates two digits of a number; one anywhere else is an error
+    fn digit_separator(&mut self, is_digit: fn(&u8) -> bool) -> Result<bool, LexError> {
+        if self.peek() != Some(b'_') {
+            return Ok(false);
+        }
+        let before = self.i.checked_sub(1).and_then(|i| self.s.get(i)).is_some_and(is_digit);
+        if !before || !self.s.get(self.i + 1).is_some_and(is_digit) {
+            return self.err("digit separator `_` must be between two digits");
+        }
+        self.bump();
+        Ok(true)
+    }
+
+    // a possibly empty run of decimal digits and separators
+    fn skip_digits(&mut self) -> Result<(), LexError> {
+        loop {
+            if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.bump();
+            } else if !self.digit_separator(u8::is_ascii_digit)? {
+                return Ok(());
+            }
+        }
     }
-}
\ No newline at end of file
+
+    fn lex_int_suffix(&mut self) -> Result<IntSuffix, LexError> {
+        let (line, col) = (self.line, self.col);
+        let start = self.i;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == b'_') {
+            self.bump();
+        }
+
+        let mut sfx = IntSuffix::default();
+        let mut rest = &self.s[start..self.i];
+        loop {
+            if !sfx.unsigned && matches!(rest.first(), Some(b'u' | b'U')) {
+                sfx.unsigned = true;
+                rest = &rest[1..];
+            } else if sfx.longs == 0 && (rest.starts_with(b"ll") || rest.starts_with(b"LL")) {
+                sfx.longs = 2;
+                rest = &rest[2..];
+            } else if sfx.longs == 0 && matches!(rest.first(), Some(b'l' | b'L')) {
+                sfx.longs = 1;
+                rest = &rest[1..];
+            } else {
+                break;
+            }
+        }
+
+        if !rest.is_empty() {
+            let text = String::from_utf8_lossy(&self.s[start..self.i]);
+            return self.err_at(line, col, format!("invalid integer suffix {:?}", text));
+        }
+        Ok(sfx)
+    }
+
+    // 'a' | '\n' | '\xff'  (value as a signed char)
+    fn lex_char_lit(&mut self) -> Result<Tok, LexError> {
+        let (line, col) = (self.line, self.col);
+        self.bump();
+
+        let b = match self.peek() {
+            None | Some(b'\n' | b'\r') => {
+                return self.err_at(line, col, "unterminated character literal");
+            }
+            Some(b'\'') => return self.err_at(line, col, "empty character literal"),
+            Some(b'\\') => self.lex_escape()?,
+            Some(c) => {
+                self.bump();
+                c
+            }
+        };
+
+        match self.peek() {
+            Some(b'\'') => {
+                self.bump();
+                Ok(Tok::CharLit(b as i8 as i128))
+            }
+            None | Some(b'\n' | b'\r') => self.err_at(line, col, "unterminated character literal"),
+            Some(_) => self.err_at(line, col, "multi-character character literal"),
+        }
+    }
+
+    // "..."  (decoded bytes, no trailing NUL)
+    fn lex_str_lit(&mut self) -> Result<Tok, LexError> {
+        let (line, col) = (self.line, self.col);
+        self.bump();
+
+        let mut out = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.bump();
+                    return Ok(Tok::StrLit(out));
+                }
+                Some(b'\n' | b'\r') => return self.err("newline in string literal"),
+                None => return self.err_at(line, col, "unterminated string literal"),
+                Some(b'\\') if self.skip_splice() => {}
+                Some(b'\\') => out.push(self.lex_escape()?),
+                Some(c) => {
+                    self.bump();
+                    out.push(c);
+                }
+            }
+        }
+    }
+
+    // \n \t \r \a \b \f \v \\ \' \" \? \xHH \NNN (octal, 1-3 digits);
+    // shared by character and string literals
+    fn lex_escape(&mut self) -> Result<u8, LexError> {
+        let (line, col) = (self.line, self.col);
+        self.bump();
+        let at = self.i;
+        let Some(c) = self.bump() else {
+            return self.err_at(line, col, "unterminated escape sequence");
+        };
+        Ok(match c {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'\\' => b'\\',
+            b'\'' => b'\'',
+            b'"' => b'"',
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'v' => 0x0b,
+            b'?' => b'?',
+            b'0'..=b'7' => {
+                let mut v = u32::from(c - b'0');
+                for _ in 0..2 {
+                    match self.peek() {
+                        Some(d @ b'0'..=b'7') => {
+                            self.bump();
+                            v = v * 8 + u32::from(d - b'0');
+                        }
+                        _ => break,
+                    }
+                }
+                if v > 0xff {
+                    return self.err_at(line, col, "octal escape sequence out of range");
+                }
+                v as u8
+            }
+            b'x' => {
+                // every hex digit that follows belongs to the escape
+                let mut v: u32 = 0;
+                let mut ndigits = 0;
+                while let Some(d) = self.peek().and_then(|x| (x as char).to_digit(16)) {
+                    self.bump();
+                    v = v.saturating_mul(16).saturating_add(d);
+                    ndigits += 1;
+                }
+                if ndigits == 0 {
+                    return self.err_at(line, col, "\\x used with no following hex digits");
+                }
+                if v > 0xff {
+                    return self.err_at(line, col, "hex escape sequence out of range");
+                }
+                v as u8
+            }
+            _ => {
+                let ch = self.char_at(at);
+                return self.err_at(line, col, format!("unknown escape sequence: \\{}", ch));
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toks(src: &str) -> Vec<Tok> {
+        let mut out: Vec<Tok> = lex_all(src).unwrap().into_iter().map(|t| t.value).collect();
+        assert_eq!(out.pop(), Some(Tok::Eof));
+        out
+    }
+
+    fn int(v: i128) -> Tok {
+        Tok::IntLit(v, IntSuffix::default())
+    }
+
+    fn err(src: &str) -> LexError {
+        lex_all(src).unwrap_err()
+    }
+
+    #[test]
+    fn integer_bases() {
+        assert_eq!(
+            toks("0x1F 0XfF 0b1010 0B1 0755 0 42"),
+            [31, 255, 10, 1, 493, 0, 42].map(int)
+        );
+        assert!(err("0x1G").msg.contains("hexadecimal"));
+        assert!(err("08").msg.contains("octal"));
+        assert!(err("0b2").msg.contains("binary"));
+        assert!(err("0x").msg.contains("missing digits"));
+    }
+
+    #[test]
+    fn integer_suffixes() {
+        let sfx = |unsigned, longs| IntSuffix { unsigned, longs };
+        assert_eq!(
+            toks("1u 1L 1ull 1LLU 1lu"),
+            [
+                Tok::IntLit(1, sfx(true, 0)),
+                Tok::IntLit(1, sfx(false, 1)),
+                Tok::IntLit(1, sfx(true, 2)),
+                Tok::IntLit(1, sfx(true, 2)),
+                Tok::IntLit(1, sfx(true, 1)),
+            ]
+        );
+        assert!(lex_all("1uu").is_err());
+        assert!(lex_all("1lL").is_err());
+        assert!(lex_all("3LLL").is_err());
+    }
+
+    #[test]
+    fn integer_overflow_is_an_error() {
+        let e = err("int x = 1234567890123456789012345678901234567890;");
+        assert_eq!((e.msg.as_str(), e.line, e.col), ("integer literal too large", 1, 9));
+        assert!(lex_all("18446744073709551615").is_ok());
+        assert!(lex_all("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn char_literals() {
+        assert_eq!(
+            toks(r"'A' '\n' '\t' '\r' '\\' '\'' '\0' '\x41' '\xff'"),
+            [65, 10, 9, 13, 92, 39, 0, 65, -1].map(Tok::CharLit)
+        );
+        assert!(err("'a").msg.contains("unterminated character literal"));
+        assert!(err("''").msg.contains("empty character literal"));
+        assert!(err("'ab'").msg.contains("multi-character"));
+        assert!(err(r"'\x'").msg.contains("no following hex digits"));
+        assert!(err(r"'\q'").msg.contains("unknown escape sequence"));
+    }
+
+    #[test]
+    fn string_literals() {
+        assert_eq!(
+            toks(r#""hello\n" "" "a\"b\x41""#),
+            [
+                Tok::StrLit(b"hello\n".to_vec()),
+                Tok::StrLit(Vec::new()),
+                Tok::StrLit(b"a\"bA".to_vec()),
+            ]
+        );
+        let e = err("x = \"abc");
+        assert_eq!((e.msg.as_str(), e.line, e.col), ("unterminated string literal", 1, 5));
+        let e = err("\"ab\ncd\"");
+        assert_eq!((e.msg.as_str(), e.line, e.col), ("newline in string literal", 1, 4));
+    }
+
+    #[test]
+    fn spans() {
+        let t = lex_all("int x;\n  return 0x1F;").unwrap();
+        let at: Vec<(usize, usize, usize, usize)> =
+            t.iter().map(|t| (t.span.start, t.span.end, t.span.line, t.span.col)).collect();
+        assert_eq!(at[..5], [(0, 3, 1, 1), (4, 5, 1, 5), (5, 6, 1, 6), (9, 15, 2, 3), (16, 20, 2, 10)]);
+    }
+
+    #[test]
+    fn slash_is_not_a_comment() {
+        assert_eq!(toks("a / b % c"), [
+            Tok::Ident("a".into()),
+            Tok::Slash,
+            Tok::Ident("b".into()),
+            Tok::Percent,
+            Tok::Ident("c".into()),
+        ]);
+        assert_eq!(
+            toks("a // b\n/ c /* d */"),
+            [Tok::Ident("a".into()), Tok::Slash, Tok::Ident("c".into())]
+        );
+    }
+
+    #[test]
+    fn bang() {
+        assert_eq!(toks("!a"), [Tok::Bang, Tok::Ident("a".into())]);
+    }
+
+    #[test]
+    fn logical_operators() {
+        assert_eq!(toks("&& ||"), [Tok::AndAnd, Tok::OrOr]);
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        assert_eq!(toks("& | ^ ~ &&"), [Tok::Amp, Tok::Pipe, Tok::Caret, Tok::Tilde, Tok::AndAnd]);
+    }
+
+    #[test]
+    fn shift_operators() {
+        assert_eq!(toks("<< >> < >"), [Tok::Shl, Tok::Shr, Tok::Lt, Tok::Gt]);
+    }
+
+    #[test]
+    fn question_and_colon() {
+        assert_eq!(toks("?:"), [Tok::Question, Tok::Colon]);
+    }
+
+    #[test]
+    fn compound_assignment_operators() {
+        assert_eq!(toks("+= -= *= /= %= &= |= ^= <<= >>="), [
+            Tok::PlusAssign,
+            Tok::MinusAssign,
+            Tok::StarAssign,
+            Tok::SlashAssign,
+            Tok::PercentAssign,
+            Tok::AmpAssign,
+            Tok::PipeAssign,
+            Tok::CaretAssign,
+            Tok::ShlAssign,
+            Tok::ShrAssign,
+        ]);
+        assert_eq!(toks("a<<=b<<c"), [
+            Tok::Ident("a".into()),
+            Tok::ShlAssign,
+            Tok::Ident("b".into()),
+            Tok::Shl,
+            Tok::Ident("c".into()),
+        ]);
+    }
+
+    #[test]
+    fn increment_and_decrement() {
+        assert_eq!(
+            toks("a+++b"),
+            [Tok::Ident("a".into()), Tok::PlusPlus, Tok::Plus, Tok::Ident("b".into())]
+        );
+        assert_eq!(toks("--x"), [Tok::MinusMinus, Tok::Ident("x".into())]);
+    }
+
+    #[test]
+    fn switch_keywords() {
+        assert_eq!(toks("switch case default defaults"), [
+            Tok::Switch,
+            Tok::Case,
+            Tok::Default,
+            Tok::Ident("defaults".into()),
+        ]);
+    }
+
+    #[test]
+    fn floating_literals() {
+        assert_eq!(toks("2.75 .5 1. 1e-3 2.5E+10"), [
+            Tok::FloatLit(2.75),
+            Tok::FloatLit(0.5),
+            Tok::FloatLit(1.0),
+            Tok::FloatLit(1e-3),
+            Tok::FloatLit(2.5e10),
+        ]);
+        // a member access is still a dot
+        assert_eq!(toks("s.x"), [Tok::Ident("s".into()), Tok::Dot, Tok::Ident("x".into())]);
+        let at = |src: &str| {
+            let e = err(src);
+            (e.msg, e.line, e.col)
+        };
+        assert_eq!(at("1e+;"), ("missing digits in exponent".into(), 1, 4));
+        assert_eq!(at("1.5q"), ("invalid suffix on floating literal \"1.5\"".into(), 1, 4));
+        assert_eq!(at("1e999"), ("floating literal out of range".into(), 1, 1));
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        assert_eq!(toks("a /* x /* y */ z */ b"), [Tok::Ident("a".into()), Tok::Ident("b".into())]);
+        assert_eq!(toks("a /**/ /* /**/ */ b"), [Tok::Ident("a".into()), Tok::Ident("b".into())]);
+        assert_eq!(err("/* a /* b */ c").msg, "unterminated block comment");
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let toks = lex_all("/* héllo → */ x = \"ü\"; y").unwrap();
+        let spans: Vec<_> = toks.iter().map(|t| (t.span.line, t.span.col)).collect();
+        assert_eq!(spans, [(1, 15), (1, 17), (1, 19), (1, 22), (1, 24), (1, 25)]);
+        let e = err("x = é;");
+        assert_eq!((e.msg.as_str(), e.line, e.col), ("unexpected char: 'é'", 1, 5));
+    }
+
+    #[test]
+    fn unterminated_comment_points_at_the_opener() {
+        let e = err("int x;\n  /* never\nclosed\n");
+        assert_eq!((e.msg.as_str(), e.line, e.col), ("unterminated block comment", 2, 3));
+        // with nesting, it's the outermost opener that is left open
+        let e = err("a /* b /* c */ d");
+        assert_eq!((e.msg.as_str(), e.line, e.col), ("unterminated block comment", 1, 3));
+    }
+
+    #[test]
+    fn octal_and_simple_escapes() {
+        assert_eq!(
+            toks(r"'\a' '\b' '\f' '\v' '\?' '\7' '\101' '\377'"),
+            [7, 8, 12, 11, 63, 7, 65, -1].map(Tok::CharLit)
+        );
+        // at most three octal digits are taken, and 8 isn't one
+        assert_eq!(toks(r#""\1234\08""#), [Tok::StrLit(vec![0o123, b'4', 0, b'8'])]);
+        let e = err(r"x = '\400';");
+        assert_eq!((e.msg.as_str(), e.line, e.col), ("octal escape sequence out of range", 1, 6));
+    }
+
+    #[test]
+    fn hex_escapes_take_every_digit() {
+        assert_eq!(toks(r#""\x41g\x0041""#), [Tok::StrLit(vec![0x41, b'g', 0x41])]);
+        assert_eq!(toks(r"'\x7F' '\x000000ff'"), [127, -1].map(Tok::CharLit));
+        let e = err(r#"s = "\x41BC";"#);
+        assert_eq!((e.msg.as_str(), e.line, e.col), ("hex escape sequence out of range", 1, 6));
+        assert_eq!(err(r"'\x100'").msg, "hex escape sequence out of range");
+        assert_eq!(err(r"'\xfffffffffffffffff'").msg, "hex escape sequence out of range");
+    }
+
+    #[test]
+    fn bool_keywords() {
+        assert_eq!(toks("_Bool bool Bool"), [Tok::Bool, Tok::Bool, Tok::Ident("Bool".to_string())]);
+    }
+
+    #[test]
+    fn non_ascii_in_messages() {
+        assert_eq!(err("int x = 1 € 2;").msg, "unexpected char: '€'");
+        assert_eq!(err(r"'\é'").msg, r"unknown escape sequence: \é");
+        assert_eq!(err("\"\\😀\"").msg, "unknown escape sequence: \\😀");
+    }
+
+    #[test]
+    fn arbitrary_input_does_not_panic() {
+        let pieces = [
+            "int", "x", "_a1", "0x", "0x1p", "1e", "1.5e+", "09", "'", "\"", "\\", "\\x", "\\777",
+            "/*", "*/", "//", "\n", "\r", "\t", " ", "é", "€", "😀", "#", "@", "$", "`", ".", "..",
+            "...", "<<=", ">>", "->", "'\\", "\"\\", "0b", "1u", "1lu", "1llu", "2.5f",
+        ];
+        // a fixed linear congruential sequence, so a failure reproduces
+        let mut seed: u64 = 0x2545f4914f6cdd1d;
+        for _ in 0..3000 {
+            let mut src = String::new();
+            for _ in 0..(seed >> 60) + 1 {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                src += pieces[(seed >> 33) as usize % pieces.len()];
+            }
+            let _ = lex_all(&src);
+        }
+    }
+
+    #[test]
+    fn line_continuations() {
+        assert_eq!(toks("\"ab\\\ncd\""), [Tok::StrLit(b"abcd".to_vec())]);
+        assert_eq!(toks("\"ab\\\r\ncd\""), [Tok::StrLit(b"abcd".to_vec())]);
+        assert_eq!(toks("// comment \\\nstill comment\nx"), [Tok::Ident("x".to_string())]);
+        assert_eq!(toks("a \\\n b"), [Tok::Ident("a".to_string()), Tok::Ident("b".to_string())]);
+        // positions stay physical, the logical line doesn't advance
+        let t = lex_all("a \\\n b\nc").unwrap();
+        let at = |i: usize| (t[i].span.line, t[i].span.col, t[i].span.logical_line);
+        assert_eq!((at(0), at(1), at(2)), ((1, 1, 1), (2, 2, 1), (3, 1, 2)));
+        // a backslash not followed by a newline is still an error
+        assert!(err("a \\ b").msg.contains("unexpected char"));
+    }
+
+    #[test]
+    fn hex_floating_literals() {
+        assert_eq!(
+            toks("0x1p4 0x1.8p1 0X.8P+1 0xAp-2 0x1.fffffffffffffp1023 0x1p-1074"),
+            [16.0, 3.0, 1.0, 2.5, f64::MAX, 5e-324].map(Tok::FloatLit)
+        );
+        // still integers without a '.' or an exponent
+        assert_eq!(toks("0x1f"), [int(31)]);
+        assert!(err("0x1.8").msg.contains("requires a `p` exponent"));
+        assert!(err("0x.p1").msg.contains("missing digits in hexadecimal floating literal"));
+        assert!(err("0x1p").msg.contains("missing digits in exponent"));
+        assert!(err("0x1p1z").msg.contains("invalid suffix"));
+        let e = err("x = 0x1p1024;");
+        assert_eq!((e.msg.as_str(), e.line, e.col), ("floating literal out of range", 1, 5));
+    }
+
+    #[test]
+    fn tokens_describe_themselves_as_source() {
+        let described: Vec<String> = lex_all("int foo ) -> <<= 42u 7ul 2.5 'a' \"s\"")
+            .unwrap()
+            .iter()
+            .map(|t| t.value.describe())
+            .collect();
+        assert_eq!(
+            described,
+            [
+                "'int'", "'foo'", "')'", "'->'", "'<<='", "'42u'", "'7ul'", "'2.5'",
+                "character literal", "string literal", "end of input",
+            ]
+        );
+    }
+
+    #[test]
+    fn line_endings() {
+        let e = err("int x;\r\nint y;\r\n$");
+        assert_eq!((e.line, e.col), (3, 1));
+        let e = err("int x;\rint y; $");
+        assert_eq!((e.line, e.col), (2, 8));
+        let e = err("a\r\n  $");
+        assert_eq!((e.line, e.col), (2, 3));
+    }
+
+    #[test]
+    fn digit_separators() {
+        assert_eq!(
+            toks("1_000_000 0xFF_FF 0b1010_1010 07_7 1_0u"),
+            [
+                int(1_000_000),
+                int(0xffff),
+                int(0b1010_1010),
+                int(0o77),
+                Tok::IntLit(10, IntSuffix { unsigned: true, longs: 0 }),
+            ]
+        );
+        assert_eq!(toks("1_000.000_5e1_0 0x1_0.8p1_0"), [1.0000005e13, 16896.0].map(Tok::FloatLit));
+        // `_5` is an identifier, as in C
+        assert_eq!(toks("_5"), [Tok::Ident("_5".into())]);
+        for bad in ["5_", "0x_1", "0b_1", "1__0", "1_.5", "1._5", "1.5_", "0x1_.8p1", "1_u", "0_"] {
+            let e = err(bad);
+            assert!(e.msg.contains("digit separator"), "{bad}: {}", e.msg);
+        }
+    }
+}
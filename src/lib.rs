@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The whale-c compiler as a library: C source in, Whale IR out. The
+//! `whale-c` binary is a thin command-line wrapper over these modules.
+
+pub mod cfg;
+mod dce;
+pub mod diag;
+pub mod dump;
+mod fold;
+mod inline;
+pub mod lex;
+pub mod merge;
+pub mod opt;
+pub mod parse;
+mod peephole;
+pub mod pp;
+pub mod target;
+pub mod uninit;
+
+use ir::lower_ast::frontend as s;
+use target::Target;
+
+/// Why a compilation stopped. Lowering and verifier errors are kept as their
+/// debug text.
+#[derive(Debug)]
+pub enum CompileError {
+    Parse(Vec<parse::ParseError>),
+    Merge(String),
+    Lower(String),
+    Verify(String),
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::Parse(errors) => {
+                let msgs: Vec<String> = errors.iter().map(|e| format!("parse error: {e}")).collect();
+                write!(f, "{}", msgs.join("\n"))
+            }
+            CompileError::Merge(e) => write!(f, "{e}"),
+            CompileError::Lower(e) => write!(f, "lower error: {e}"),
+            CompileError::Verify(e) => write!(f, "verify error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Parses one translation unit with the default options, runs the `-O0`
+/// pipeline and returns the printed IR. Warnings are dropped; call
+/// [`parse::parse_translation_unit`] directly to see them.
+pub fn compile_to_ir(src: &str, target: &Target) -> Result<String, CompileError> {
+    let (mut program, _warnings) =
+        parse::parse_translation_unit(src, target, parse::ParseOptions::LENIENT)
+            .map_err(CompileError::Parse)?;
+    opt::optimize(&mut program, opt::OptLevel::O0, target);
+    lower(&program, target)
+}
+
+/// Lowers an already optimized program, runs the zero pass and the verifier,
+/// and returns the printed module.
+pub fn lower(program: &s::Program, target: &Target) -> Result<String, CompileError> {
+    let mut module = ir::lower_ast::lower_o0(program, target.triple, target.data_layout())
+        .map_err(|e| CompileError::Lower(format!("{e:?}")))?;
+
+    ir::zero::pass::run_zero_pass(&mut module);
+
+    ir::verifier::verify_module(&module).map_err(|e| CompileError::Verify(format!("{e:?}")))?;
+
+    Ok(ir::printer::print_module(&module))
+}
@@ -1,47 +1,278 @@
 // SPDX-License-Identifier: MPL-2.0
 
-mod lex;
-mod parse;
-
 use std::fs;
+use std::io::Read;
+use whale_c::target::Target;
+use whale_c::{cfg, diag, dump, lex, merge, opt, parse, pp, uninit, CompileError};
+
+const USAGE: &str = concat!(
+    "usage: whale-c [--emit=tokens|ast|cfg|ir] [--target=<triple>] [-O0|-O1|-O2] [--werror]",
+    " [--stats] [--print-hex] [--std=lenient|strict|c89] [--diagnostics=text|json]",
+    " [-o <out>] <file.c | ->...",
+);
+
+/// Exit statuses, listed by `--help`; any other failure exits with 1.
+const EXIT_USAGE: i32 = 2;
+const EXIT_PARSE: i32 = 10;
+const EXIT_LOWER: i32 = 11;
+const EXIT_VERIFY: i32 = 12;
+
+const EXIT_HELP: &str = concat!(
+    "  0   success\n",
+    "  1   other errors (conflicting definitions across inputs, --werror)\n",
+    "  2   usage or I/O error\n",
+    "  10  lex or parse error\n",
+    "  11  lowering error\n",
+    "  12  IR verifier error",
+);
+
+/// On/off switches of the driver itself.
+#[derive(Clone, Copy)]
+struct Flags {
+    /// `--werror`
+    werror: bool,
+    /// `--stats`: print what the frontend passes did to stderr
+    stats: bool,
+    /// `--print-hex`: integer literals in hex in the `--emit=ast` dump
+    print_hex: bool,
+}
+
+/// What to print instead of (or as) the compiled module.
+#[derive(Clone, Copy, PartialEq)]
+enum Emit {
+    Tokens,
+    Ast,
+    Cfg,
+    Ir,
+}
+
+impl Emit {
+    /// Every kind with its `--emit=` spelling; `ir` is the default.
+    const ALL: [(&'static str, Emit); 4] =
+        [("tokens", Emit::Tokens), ("ast", Emit::Ast), ("cfg", Emit::Cfg), ("ir", Emit::Ir)];
+
+    fn from_name(name: &str) -> Option<Emit> {
+        Emit::ALL.iter().find(|(n, _)| *n == name).map(|&(_, e)| e)
+    }
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("{msg}\n{USAGE}");
+    std::process::exit(EXIT_USAGE);
+}
 
 fn main() {
-    let path = std::env::args().nth(1).unwrap_or_else(|| {
-        eprintln!("usage: whale-c <file.c>");
-        std::process::exit(2);
-    });
+    let mut emit = Emit::Ir;
+    let mut level = opt::OptLevel::O0;
+    let mut werror = false;
+    let mut stats = false;
+    let mut print_hex = false;
+    let mut target = Target::X86_64;
+    let mut std = parse::ParseOptions::LENIENT;
+    let mut diags = diag::Sink { format: diag::Format::Text };
+    let mut paths = Vec::new();
+    let mut out_path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            println!("{USAGE}\n\noptimization levels:\n{}", opt::PIPELINE_HELP);
+            println!("\nexit status:\n{EXIT_HELP}");
+            return;
+        } else if let Some(l) = opt::OptLevel::from_flag(&arg) {
+            level = l;
+        } else if let Some(triple) = arg.strip_prefix("--target=") {
+            target = Target::from_triple(triple).unwrap_or_else(|| {
+                let known: Vec<&str> = Target::ALL.iter().map(|t| t.triple).collect();
+                usage_error(&format!("unknown target `{triple}`; supported: {}", known.join(", ")))
+            });
+        } else if let Some(name) = arg.strip_prefix("--std=") {
+            std = parse::ParseOptions::from_std(name).unwrap_or_else(|| {
+                usage_error(&format!("unknown --std `{name}`; expected one of: lenient, strict, c89"))
+            });
+        } else if let Some(name) = arg.strip_prefix("--diagnostics=") {
+            diags.format = diag::Format::from_name(name).unwrap_or_else(|| {
+                usage_error(&format!("unknown --diagnostics `{name}`; expected one of: text, json"))
+            });
+        } else if arg == "--werror" {
+            werror = true;
+        } else if arg == "--stats" {
+            stats = true;
+        } else if arg == "--print-hex" {
+            print_hex = true;
+        } else if arg == "-o" {
+            match args.next() {
+                Some(out) => out_path = Some(out),
+                None => usage_error("missing path after `-o`"),
+            }
+        } else if let Some(kind) = arg.strip_prefix("--emit=") {
+            emit = Emit::from_name(kind).unwrap_or_else(|| {
+                let known: Vec<&str> = Emit::ALL.iter().map(|(name, _)| *name).collect();
+                let known = known.join(", ");
+                usage_error(&format!("unknown --emit kind `{kind}`; expected one of: {known}"))
+            });
+        } else if arg.starts_with('-') && arg != "-" {
+            usage_error(&format!("unknown option `{arg}`"));
+        } else {
+            paths.push(arg);
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{USAGE}");
+        std::process::exit(EXIT_USAGE);
+    }
+    // the IR text comes from the ir crate's printer, which has no such option
+    if print_hex && emit != Emit::Ast {
+        usage_error("--print-hex only applies to --emit=ast");
+    }
 
-    let src = fs::read_to_string(&path).unwrap_or_else(|e| {
-        eprintln!("failed to read {path}: {e}");
-        std::process::exit(2);
-    });
+    let inputs: Vec<(String, String)> = paths.into_iter().map(read_input).collect();
+    let flags = Flags { werror, stats, print_hex };
+    let (text, status) = compile(emit, &target, std, level, flags, &diags, &inputs);
 
-    let program = match parse::parse_translation_unit(&src) {
-        Ok(p) => p,
-        Err(e) => {
-            eprintln!("parse error: {e}");
-            std::process::exit(1);
+    // `-o -` is an explicit stdout
+    match out_path.as_deref() {
+        None | Some("-") => print!("{text}"),
+        Some(out) => {
+            if let Err(e) = fs::write(out, text) {
+                eprintln!("failed to write {out}: {e}");
+                std::process::exit(EXIT_USAGE);
+            }
         }
-    };
+    }
+    if status != 0 {
+        std::process::exit(status);
+    }
+}
 
-    let mut module = match ir::lower_ast::lower_o0(
-        &program,
-        "x86_64-whale-linux",
-        ir::DataLayout::default_64bit_le(),
-    ) {
-        Ok(m) => m,
+// (display name, source); `-` reads the source from stdin
+fn read_input(path: String) -> (String, String) {
+    if path == "-" {
+        let mut src = String::new();
+        if let Err(e) = std::io::stdin().read_to_string(&mut src) {
+            eprintln!("failed to read <stdin>: {e}");
+            std::process::exit(EXIT_USAGE);
+        }
+        return ("<stdin>".to_string(), src);
+    }
+    match fs::read_to_string(&path) {
+        Ok(src) => (path, src),
         Err(e) => {
-            eprintln!("lower error: {e:?}");
-            std::process::exit(1);
+            eprintln!("failed to read {path}: {e}");
+            std::process::exit(EXIT_USAGE);
         }
-    };
+    }
+}
+
+fn dump_ast(p: &ir::lower_ast::frontend::Program, flags: Flags) -> String {
+    if flags.print_hex {
+        dump::dump_program_hex(p)
+    } else {
+        dump::dump_program(p)
+    }
+}
 
-    ir::zero::pass::run_zero_pass(&mut module);
+/// Runs the pipeline up to `emit` and returns what it prints and the exit status.
+/// Errors exit right away, except that parse errors under `--emit=ast` still
+/// print the functions that parsed cleanly. Several inputs are parsed
+/// separately and merged into one module.
+fn compile(
+    emit: Emit,
+    target: &Target,
+    std: parse::ParseOptions,
+    level: opt::OptLevel,
+    flags: Flags,
+    diags: &diag::Sink,
+    inputs: &[(String, String)],
+) -> (String, i32) {
+    let error = |label, message, file, pos| diag::Diagnostic {
+        severity: diag::Severity::Error,
+        label,
+        message,
+        file,
+        pos,
+    };
+    if emit == Emit::Tokens {
+        let mut out = String::new();
+        for (name, src) in inputs {
+            let toks = lex::lex_all(src).and_then(pp::preprocess).unwrap_or_else(|e| {
+                let d = error("lex error", e.msg, Some(name), Some((e.line, e.col)));
+                diags.report(&d, Some(src));
+                std::process::exit(EXIT_PARSE);
+            });
+            for t in toks {
+                out += &format!("{}:{}\t{:?}\n", t.span.line, t.span.col, t.value);
+            }
+        }
+        return (out, 0);
+    }
 
-    if let Err(e) = ir::verifier::verify_module(&module) {
-        eprintln!("verify error: {e:?}");
+    let mut units = Vec::with_capacity(inputs.len());
+    let mut warned = false;
+    let mut failed = false;
+    for (name, src) in inputs {
+        let parsed = parse::parse_recovering(src, target, std);
+        if !parsed.errors.is_empty() {
+            failed = true;
+            for e in parsed.errors {
+                let d = error("parse error", e.kind.to_string(), Some(name), Some((e.line, e.col)));
+                diags.report(&d, Some(src));
+            }
+            units.push((name.clone(), parsed.program));
+            continue;
+        }
+        let uninit = uninit::check_program(&parsed.program);
+        warned |= !parsed.warnings.is_empty() || !uninit.is_empty();
+        let positioned = parsed.warnings.into_iter().map(|w| (w.msg, Some((w.line, w.col))));
+        for (message, pos) in positioned.chain(uninit.into_iter().map(|w| (w, None))) {
+            let d = diag::Diagnostic {
+                severity: diag::Severity::Warning,
+                label: "warning",
+                message,
+                file: Some(name),
+                pos,
+            };
+            diags.report(&d, Some(src));
+        }
+        units.push((name.clone(), parsed.program));
+    }
+    if failed {
+        // what did parse is still worth looking at
+        if emit != Emit::Ast {
+            std::process::exit(EXIT_PARSE);
+        }
+        let dumps: Vec<String> = units.iter().map(|(_, p)| dump_ast(p, flags)).collect();
+        return (dumps.concat(), EXIT_PARSE);
+    }
+    if warned && flags.werror {
+        let d = error("error", "warnings treated as errors (--werror)".to_string(), None, None);
+        diags.report(&d, None);
+        std::process::exit(1);
+    }
+    let mut program = merge::merge_programs(units).unwrap_or_else(|e| {
+        diags.report(&error("", e, None, None), None);
         std::process::exit(1);
+    });
+
+    // frontend passes run before the AST dump so `-O1 --emit=ast|cfg` shows their effect
+    let stats = opt::optimize(&mut program, level, target);
+    if flags.stats {
+        eprintln!("{stats}");
     }
 
-    print!("{}", ir::printer::print_module(&module));
+    match emit {
+        Emit::Ast => return (dump_ast(&program, flags), 0),
+        Emit::Cfg => return (cfg::dot_program(&program), 0),
+        _ => {}
+    }
+
+    let module = whale_c::lower(&program, target).unwrap_or_else(|e| {
+        let (code, d) = match e {
+            CompileError::Lower(e) => (EXIT_LOWER, error("lower error", e, None, None)),
+            CompileError::Verify(e) => (EXIT_VERIFY, error("verify error", e, None, None)),
+            e => (1, error("", e.to_string(), None, None)),
+        };
+        diags.report(&d, None);
+        std::process::exit(code);
+    });
+    (module, 0)
 }
\ No newline at end of file
@@ -0,0 +1,331 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ir::lower_ast::frontend as s;
+
+/// Combines separately parsed translation units into one program, so calls
+/// and `extern` objects resolve across files. `units` pairs each program with
+/// the file name used in diagnostics. A `static` symbol is private to its file:
+/// if another file uses the same name it is renamed, and only clashes between
+/// external symbols are errors.
+pub fn merge_programs(units: Vec<(String, s::Program)>) -> Result<s::Program, String> {
+    let mut out = s::Program::default();
+    // symbol -> file that defined it
+    let mut defined: Vec<(String, String)> = Vec::new();
+
+    let scopes: Vec<Vec<String>> = units.iter().map(|(_, p)| file_scope_names(p)).collect();
+    // every name in use, so a renamed symbol gets a fresh one
+    let mut taken: Vec<String> = scopes.concat();
+    for (_, p) in &units {
+        for func in &p.functions {
+            taken.extend(local_names(func));
+        }
+    }
+
+    for (k, (file, mut p)) in units.into_iter().enumerate() {
+        let elsewhere = |name: &str| {
+            scopes.iter().enumerate().any(|(j, names)| j != k && names.iter().any(|n| n == name))
+        };
+        let mut renames: Vec<(String, String)> = Vec::new();
+        for name in internal_names(&p) {
+            if elsewhere(&name) && !renames.iter().any(|(from, _)| *from == name) {
+                let mut n = 1;
+                while taken.contains(&format!("{}.{}", name, n)) {
+                    n += 1;
+                }
+                let to = format!("{}.{}", name, n);
+                taken.push(to.clone());
+                renames.push((name, to));
+            }
+        }
+        if !renames.is_empty() {
+            rename_program(&mut p, &renames);
+        }
+
+        for def in p.structs {
+            match out.structs.iter().find(|d| d.name == def.name) {
+                Some(prev) if same_layout(prev, &def) => {}
+                Some(_) => {
+                    return Err(format!("{}: conflicting definitions of `struct {}`", file, def.name));
+                }
+                None => out.structs.push(def),
+            }
+        }
+
+        let names = p.globals.iter().map(|g| &g.name)
+            .chain(p.global_vars.iter().map(|g| &g.name))
+            .chain(p.functions.iter().map(|f| &f.name));
+        // static symbols that could clash were renamed above, so any clash left is
+        // between two external ones
+        for name in names {
+            if let Some((_, first)) = defined.iter().find(|(n, _)| n == name) {
+                let msg = format!("duplicate definition of `{}` (first defined in {})", name, first);
+                return Err(format!("{}: {}", file, msg));
+            }
+            defined.push((name.clone(), file.clone()));
+        }
+
+        out.globals.extend(p.globals);
+        out.global_vars.extend(p.global_vars);
+        out.global_decls.extend(p.global_decls);
+        out.prototypes.extend(p.prototypes);
+        out.functions.extend(p.functions);
+    }
+
+    // a declaration is redundant once some file defines the symbol
+    let is_defined = |name: &str| defined.iter().any(|(n, _)| n == name);
+    out.global_decls.retain(|d| !is_defined(&d.name));
+    out.prototypes.retain(|f| !is_defined(&f.name));
+    Ok(out)
+}
+
+fn file_scope_names(p: &s::Program) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    names.extend(p.globals.iter().map(|g| g.name.clone()));
+    names.extend(p.global_vars.iter().map(|g| g.name.clone()));
+    names.extend(p.global_decls.iter().map(|g| g.name.clone()));
+    names.extend(p.prototypes.iter().map(|f| f.name.clone()));
+    names.extend(p.functions.iter().map(|f| f.name.clone()));
+    names
+}
+
+fn internal_names(p: &s::Program) -> Vec<String> {
+    let internal = |linkage: &s::Linkage| matches!(linkage, s::Linkage::Internal);
+    let mut names: Vec<String> = Vec::new();
+    names.extend(p.globals.iter().filter(|g| internal(&g.linkage)).map(|g| g.name.clone()));
+    names.extend(p.global_vars.iter().filter(|g| internal(&g.linkage)).map(|g| g.name.clone()));
+    names.extend(p.prototypes.iter().filter(|f| internal(&f.linkage)).map(|f| f.name.clone()));
+    names.extend(p.functions.iter().filter(|f| internal(&f.linkage)).map(|f| f.name.clone()));
+    names
+}
+
+// parameters and locals, as lowered
+fn local_names(func: &s::Function) -> Vec<String> {
+    let mut out: Vec<String> = func.parameters.iter().map(|p| p.name.clone()).collect();
+    declared(&func.body, &mut out);
+    out
+}
+
+fn declared(body: &[s::Stmt], out: &mut Vec<String>) {
+    for st in body {
+        match st {
+            s::Stmt::VarDecl { name, .. } | s::Stmt::ConstDecl { name, .. } => out.push(name.clone()),
+            s::Stmt::If { then_body, else_body, .. } => {
+                declared(then_body, out);
+                declared(else_body, out);
+            }
+            s::Stmt::While { body, .. } => declared(body, out),
+            s::Stmt::Switch { cases, .. } => {
+                for case in cases {
+                    declared(&case.body, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Renames file-scope symbols and every use of them. An object name is left alone
+// in a function with a local of that name, which can't see the file-scope one:
+// the parser gives a local that would shadow a visible global a fresh name.
+// Calls always name functions.
+fn rename_program(p: &mut s::Program, renames: &[(String, String)]) {
+    p.globals.iter_mut().for_each(|g| rename_name(&mut g.name, renames));
+    p.global_vars.iter_mut().for_each(|g| rename_name(&mut g.name, renames));
+    p.global_decls.iter_mut().for_each(|g| rename_name(&mut g.name, renames));
+    p.prototypes.iter_mut().for_each(|f| rename_name(&mut f.name, renames));
+    let r = Renamer { renames, locals: Vec::new() };
+    for g in &mut p.globals {
+        r.expr(&mut g.init);
+    }
+    for g in &mut p.global_vars {
+        if let Some(init) = &mut g.init {
+            r.expr(init);
+        }
+    }
+    for func in &mut p.functions {
+        rename_name(&mut func.name, renames);
+        let r = Renamer { renames, locals: local_names(func) };
+        r.stmts(&mut func.body);
+    }
+}
+
+struct Renamer<'a> {
+    renames: &'a [(String, String)],
+    // locals of the function being renamed
+    locals: Vec<String>,
+}
+
+impl Renamer<'_> {
+    fn stmts(&self, body: &mut [s::Stmt]) {
+        for st in body {
+            match st {
+                s::Stmt::VarDecl { init: Some(e), .. }
+                | s::Stmt::ConstDecl { init: e, .. }
+                | s::Stmt::ExprStmt(e)
+                | s::Stmt::Return(Some(e)) => self.expr(e),
+                s::Stmt::Assign { name, value } => {
+                    self.object(name);
+                    self.expr(value);
+                }
+                s::Stmt::Store { target, value } => {
+                    self.expr(target);
+                    self.expr(value);
+                }
+                s::Stmt::If { cond, then_body, else_body } => {
+                    self.expr(cond);
+                    self.stmts(then_body);
+                    self.stmts(else_body);
+                }
+                s::Stmt::While { cond, body } => {
+                    self.expr(cond);
+                    self.stmts(body);
+                }
+                s::Stmt::Switch { value, cases } => {
+                    self.expr(value);
+                    for case in cases {
+                        self.stmts(&mut case.body);
+                    }
+                }
+                s::Stmt::VarDecl { init: None, .. }
+                | s::Stmt::Break
+                | s::Stmt::Continue
+                | s::Stmt::Goto(_)
+                | s::Stmt::Label(_)
+                | s::Stmt::Return(None) => {}
+            }
+        }
+    }
+
+    fn expr(&self, e: &mut s::Expr) {
+        match e {
+            s::Expr::Lit(_) => {}
+            s::Expr::Var(name) | s::Expr::IncDec { name, .. } => self.object(name),
+            s::Expr::Call { callee, args } => {
+                rename_name(callee, self.renames);
+                for a in args {
+                    self.expr(a);
+                }
+            }
+            s::Expr::Unary { expr, .. }
+            | s::Expr::Member { base: expr, .. }
+            | s::Expr::Cast { expr, .. } => self.expr(expr),
+            s::Expr::Binary { left, right, .. }
+            | s::Expr::Cmp { left, right, .. }
+            | s::Expr::Logical { left, right, .. }
+            | s::Expr::Comma { left, right } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            s::Expr::Ternary { cond, then_expr, else_expr } => {
+                self.expr(cond);
+                self.expr(then_expr);
+                self.expr(else_expr);
+            }
+        }
+    }
+
+    fn object(&self, name: &mut String) {
+        if !self.locals.contains(name) {
+            rename_name(name, self.renames);
+        }
+    }
+}
+
+fn rename_name(name: &mut String, renames: &[(String, String)]) {
+    if let Some((_, to)) = renames.iter().find(|(from, _)| from == name) {
+        *name = to.clone();
+    }
+}
+
+fn same_layout(a: &s::StructDef, b: &s::StructDef) -> bool {
+    a.size == b.size
+        && a.align == b.align
+        && a.fields.len() == b.fields.len()
+        && a.fields.iter().zip(&b.fields).all(|(x, y)| x.name == y.name && x.offset == y.offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::dump_program;
+    use crate::parse::{parse_translation_unit, ParseOptions};
+    use crate::target::Target;
+
+    fn merged(files: &[(&str, &str)]) -> Result<String, String> {
+        let units = files
+            .iter()
+            .map(|(name, src)| {
+                let p = parse_translation_unit(src, &Target::X86_64, ParseOptions::LENIENT).unwrap().0;
+                (name.to_string(), p)
+            })
+            .collect();
+        merge_programs(units).map(|p| dump_program(&p))
+    }
+
+    #[test]
+    fn calls_resolve_across_files() {
+        let out = merged(&[
+            ("a.c", "int g(int); extern int n; int f() { return g(n); }"),
+            ("b.c", "int n = 4; int g(int x) { return x; }"),
+        ])
+        .unwrap();
+        assert!(!out.contains("fn g(i32) -> i32;"), "{out}");
+        assert!(!out.contains("extern n"), "{out}");
+        assert!(out.contains("fn g(x: i32) -> i32"), "{out}");
+
+        // declarations of symbols no file defines stay external
+        let out = merged(&[("a.c", "int h(); extern int m; int f() { return h() + m; }")]).unwrap();
+        assert!(out.contains("fn h() -> i32;") && out.contains("extern m: i32"), "{out}");
+    }
+
+    #[test]
+    fn duplicate_definitions() {
+        let err = merged(&[("a.c", "int x = 1;"), ("b.c", "int x = 2;")]).unwrap_err();
+        assert_eq!(err, "b.c: duplicate definition of `x` (first defined in a.c)");
+        let err = merged(&[("a.c", "void f() {}"), ("b.c", "void f() {}")]).unwrap_err();
+        assert_eq!(err, "b.c: duplicate definition of `f` (first defined in a.c)");
+    }
+
+    #[test]
+    fn static_symbols_are_private() {
+        let unit = |n: i32, f: &str| {
+            format!("static int n = {n}; static int get(void) {{ return n; }} \
+                     int {f}(void) {{ return get(); }}")
+        };
+        let (a, b) = (unit(1, "a"), unit(2, "b"));
+        let out = merged(&[("a.c", &a), ("b.c", &b)]).unwrap();
+        assert!(out.contains("static var n.1: i32") && out.contains("static var n.2: i32"), "{out}");
+        assert!(out.contains("static fn get.1()") && out.contains("static fn get.2()"), "{out}");
+        let b = &out[out.find("static fn get.2()").unwrap()..];
+        assert!(b.contains("Var n.2") && b.contains("Call get.2") && !b.contains(".1"), "{out}");
+    }
+
+    #[test]
+    fn static_symbol_next_to_an_external_one() {
+        let out = merged(&[
+            ("a.c", "static int f(void) { return 1; } int a(void) { return f(); }"),
+            ("b.c", "int f(void) { return 2; }"),
+        ])
+        .unwrap();
+        assert!(out.contains("static fn f.1()") && out.contains("Call f.1"), "{out}");
+        assert!(out.contains("\nfn f()"), "{out}");
+    }
+
+    #[test]
+    fn renaming_skips_shadowing_locals() {
+        let out = merged(&[
+            ("a.c", "static int n = 1; int a(void) { int m = n; { int n = 3; m = n; } return m + n; }"),
+            ("b.c", "int n = 2;"),
+        ])
+        .unwrap();
+        assert!(out.contains("static var n.2: i32"), "{out}");
+        assert!(out.contains("Var n.1") && out.contains("Var n.2"), "{out}");
+    }
+
+    #[test]
+    fn conflicting_structs() {
+        let err = merged(&[("a.c", "struct S { int a; };"), ("b.c", "struct S { char a; };")]);
+        assert_eq!(err.unwrap_err(), "b.c: conflicting definitions of `struct S`");
+        assert!(merged(&[("a.c", "struct S { int a; };"), ("b.c", "struct S { int a; };")]).is_ok());
+    }
+}
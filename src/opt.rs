@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::dce;
+use crate::fold;
+use crate::inline;
+use crate::peephole;
+use crate::target::Target;
+use ir::lower_ast::frontend as s;
+
+/// `-O` level. Every level runs the passes of the levels below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    O0,
+    O1,
+    O2,
+}
+
+impl OptLevel {
+    pub fn from_flag(flag: &str) -> Option<OptLevel> {
+        match flag {
+            "-O0" => Some(OptLevel::O0),
+            "-O1" | "-O" => Some(OptLevel::O1),
+            "-O2" => Some(OptLevel::O2),
+            _ => None,
+        }
+    }
+}
+
+/// The passes each level adds, for `--help`.
+pub const PIPELINE_HELP: &str = concat!(
+    "  -O0  zero pass only (default)\n",
+    "  -O1  -O0 + constant folding, algebraic simplification, dead-code elimination\n",
+    "  -O2  -O1 + inlining of small leaf functions",
+);
+
+/// What the frontend passes did, summed over every run of each, for `--stats`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    /// expressions folded to a literal
+    pub folded: usize,
+    /// algebraic identities rewritten
+    pub simplified: usize,
+    /// calls replaced by the callee's body
+    pub inlined: usize,
+    /// dead statements dropped, nested ones included
+    pub removed: usize,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "constant_fold: folded {}, peephole: rewrote {}, inline: inlined {}, dce: removed {}",
+            self.folded, self.simplified, self.inlined, self.removed,
+        )
+    }
+}
+
+/// Runs the frontend passes for `level` before lowering.
+/// The IR-level zero pass runs at every level and is not listed here.
+pub fn optimize(p: &mut s::Program, level: OptLevel, target: &Target) -> Stats {
+    let mut stats = Stats::default();
+    if level >= OptLevel::O1 {
+        // each simplification can leave new constants to fold, e.g. `(x - x) + 1`
+        stats.folded += fold::fold_program(p, target);
+        // inlined arguments are often constants, so folding runs again after it
+        if level >= OptLevel::O2 {
+            stats.inlined = inline::inline_program(p);
+            if stats.inlined > 0 {
+                stats.folded += fold::fold_program(p, target);
+            }
+        }
+        loop {
+            let n = peephole::simplify_program(p);
+            if n == 0 {
+                break;
+            }
+            stats.simplified += n;
+            stats.folded += fold::fold_program(p, target);
+        }
+        stats.removed = dce::dce_program(p);
+    }
+    stats
+}
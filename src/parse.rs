@@ -1,333 +1,4350 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use crate::lex::{lex_all, Tok};
+use crate::dce;
+use crate::dump;
+use crate::fold;
+use crate::lex::{lex_all, IntSuffix, Span, Spanned, Tok};
+use crate::pp::preprocess;
+use crate::target::Target;
 use ir::lower_ast::frontend as s;
 
 #[derive(Debug)]
-pub struct ParseError(pub String);
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub line: usize,
+    pub col: usize,
+}
 
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{} ({}:{})", self.kind, self.line, self.col)
+    }
+}
+
+/// What went wrong, for callers that need more than the message.
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    /// `want` was required and `got` came instead
+    Expected { want: Tok, got: Tok },
+    ExpectedIdent { got: Tok },
+    ExpectedType { got: Tok },
+    ExpectedExpr { got: Tok },
+    /// the input ended where `want` was required
+    UnexpectedEof { want: String },
+    /// from the lexer or preprocessor
+    Lex(String),
+    /// everything else: redeclarations, type errors, unsupported constructs
+    Other(String),
+}
+
+impl std::fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::Expected { want, got } => {
+                write!(f, "expected {}, got {}", want.describe(), got.describe())
+            }
+            ParseErrorKind::ExpectedIdent { got } => {
+                write!(f, "expected identifier, got {}", got.describe())
+            }
+            ParseErrorKind::ExpectedType { got } => write!(f, "expected type, got {}", got.describe()),
+            ParseErrorKind::ExpectedExpr { got } => {
+                write!(f, "expected expression, got {}", got.describe())
+            }
+            ParseErrorKind::UnexpectedEof { want } => write!(f, "expected {}, got end of input", want),
+            ParseErrorKind::Lex(msg) | ParseErrorKind::Other(msg) => f.write_str(msg),
+        }
+    }
+}
+
+/// A non-fatal diagnostic; the program still compiles.
+#[derive(Debug)]
+pub struct Warning {
+    pub msg: String,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}:{})", self.msg, self.line, self.col)
+    }
+}
+
+/// Dialect switches, chosen with `--std=`. Lenient is the default.
+#[derive(Clone, Copy, Debug)]
+pub struct ParseOptions {
+    /// `f(a, b,)` and `int f(int a,)`
+    pub trailing_commas: bool,
+    /// `int f()`, declaring a function without saying what parameters it takes
+    pub unprototyped: bool,
+    /// a declaration after a statement in the same block, as C99 allows
+    pub mixed_decls: bool,
+}
+
+impl ParseOptions {
+    pub const LENIENT: ParseOptions =
+        ParseOptions { trailing_commas: true, unprototyped: true, mixed_decls: true };
+    pub const STRICT: ParseOptions =
+        ParseOptions { trailing_commas: false, unprototyped: false, mixed_decls: true };
+    /// declarations only at the top of a block; `int f()` is still C89
+    pub const C89: ParseOptions =
+        ParseOptions { trailing_commas: false, unprototyped: true, mixed_decls: false };
+
+    pub fn from_std(name: &str) -> Option<ParseOptions> {
+        match name {
+            "lenient" => Some(ParseOptions::LENIENT),
+            "strict" => Some(ParseOptions::STRICT),
+            "c89" | "c90" => Some(ParseOptions::C89),
+            _ => None,
+        }
+    }
+}
+
+pub fn parse_translation_unit(
+    src: &str,
+    target: &Target,
+    opts: ParseOptions,
+) -> Result<(s::Program, Vec<Warning>), Vec<ParseError>> {
+    let out = parse_recovering(src, target, opts);
+    if !out.errors.is_empty() {
+        return Err(out.errors);
     }
+    Ok((out.program, out.warnings))
+}
+
+/// Everything one parse produced. When there are errors, `program` keeps only
+/// the functions that parsed without any, for inspecting; it isn't fit to lower.
+#[derive(Debug)]
+pub struct ParseOutput {
+    pub program: s::Program,
+    pub warnings: Vec<Warning>,
+    pub errors: Vec<ParseError>,
 }
 
-pub fn parse_translation_unit(src: &str) -> Result<s::Program, ParseError> {
-    let toks = lex_all(src).map_err(|e| ParseError(e.to_string()))?;
-    let mut p = Parser { toks, i: 0 };
+/// Like [`parse_translation_unit`], but a parse error doesn't discard the rest:
+/// parsing resumes after the broken declaration or function, and the clean
+/// functions are returned alongside every error.
+pub fn parse_recovering(src: &str, target: &Target, opts: ParseOptions) -> ParseOutput {
+    let toks = match lex_all(src).and_then(preprocess) {
+        Ok(toks) => toks,
+        Err(e) => {
+            let errors = vec![ParseError { kind: ParseErrorKind::Lex(e.msg), line: e.line, col: e.col }];
+            return ParseOutput { program: s::Program::default(), warnings: Vec::new(), errors };
+        }
+    };
+    let mut p = Parser {
+        toks,
+        i: 0,
+        target: *target,
+        opts,
+        structs: Vec::new(),
+        typedefs: Vec::new(),
+        enum_types: Vec::new(),
+        enumerators: Vec::new(),
+        consts: Vec::new(),
+        global_vars: Vec::new(),
+        global_decls: Vec::new(),
+        fns: Vec::new(),
+        vars: Vec::new(),
+        scope_start: 0,
+        fn_name: String::new(),
+        fn_ret: s::TypeRef::Void,
+        fn_locals: Vec::new(),
+        const_params: Vec::new(),
+        labels: Vec::new(),
+        gotos: Vec::new(),
+        warnings: Vec::new(),
+        errors: Vec::new(),
+        hoisted: Vec::new(),
+    };
 
     let mut globals = Vec::new();
+    let mut prototypes = Vec::new();
     let mut functions = Vec::new();
 
     while !p.is_eof() {
-        if p.peek_is(&Tok::Const) {
-            globals.push(p.parse_global_const()?);
-        } else {
-            functions.push(p.parse_function()?);
+        let depth = p.vars.len();
+        let (errors, defined) = (p.errors.len(), functions.len());
+        if let Err(e) = p.parse_top_level(&mut globals, &mut prototypes, &mut functions) {
+            p.record(e);
+            p.vars.truncate(depth);
+            p.hoisted.clear();
+            p.synchronize_top_level();
+        }
+        // a function that only recovered at statement level is still broken
+        if p.errors.len() > errors {
+            functions.truncate(defined);
         }
     }
 
-    Ok(s::Program { globals, functions })
+    let program = s::Program {
+        structs: p.structs,
+        globals,
+        global_vars: p.global_vars,
+        global_decls: p.global_decls,
+        prototypes,
+        functions,
+    };
+    ParseOutput { program, warnings: p.warnings, errors: p.errors }
+}
+
+// An object in scope. Lowering knows locals only by name, so one that would
+// collide with another name visible in the function is given a fresh one.
+struct Binding {
+    name: String,
+    lowered: String,
+    ty: s::TypeRef,
+    // for a declaration with a leading `const`, how many dereferences reach the
+    // const object: 0 for the variable itself, 1 for the pointee of `const int *p`
+    const_level: Option<usize>,
+}
+
+impl Binding {
+    fn new(name: &str, ty: &s::TypeRef, declared_const: bool) -> Self {
+        let const_level = declared_const.then(|| indirections(ty));
+        Binding { name: name.to_string(), lowered: name.to_string(), ty: ty.clone(), const_level }
+    }
 }
 
 struct Parser {
-    toks: Vec<Tok>,
+    toks: Vec<Spanned<Tok>>,
     i: usize,
+    target: Target,
+    opts: ParseOptions,
+    structs: Vec<s::StructDef>,
+    typedefs: Vec<(String, s::TypeRef)>,
+    enum_types: Vec<String>,
+    enumerators: Vec<(String, i128)>,
+    // values of integer const globals, for constant expressions
+    consts: Vec<(String, s::Lit)>,
+    global_vars: Vec<s::GlobalVar>,
+    global_decls: Vec<s::GlobalDecl>,
+    // functions declared or defined so far, which calls may name
+    fns: Vec<String>,
+
+    // objects in scope, innermost last; `scope_start` is where the innermost block begins
+    vars: Vec<Binding>,
+    scope_start: usize,
+
+    // function being parsed, and every name its locals were given so far
+    fn_name: String,
+    fn_ret: s::TypeRef,
+    fn_locals: Vec<String>,
+    // parameters of the last signature parsed that were declared `const`
+    const_params: Vec<String>,
+
+    // per-function label bookkeeping, checked once the body is parsed
+    labels: Vec<String>,
+    gotos: Vec<(String, Span)>,
+
+    warnings: Vec<Warning>,
+    // errors recovered from so far; parsing goes on to report the rest
+    errors: Vec<ParseError>,
+    // assignments used as values, waiting to run before the statement that contains them
+    hoisted: Vec<s::Stmt>,
 }
 
 impl Parser {
     fn is_eof(&self) -> bool {
-        matches!(self.toks.get(self.i), Some(Tok::Eof) | None)
+        matches!(self.toks.get(self.i).map(|t| &t.value), Some(Tok::Eof) | None)
     }
 
     fn peek(&self) -> &Tok {
-        self.toks.get(self.i).unwrap_or(&Tok::Eof)
+        self.toks.get(self.i).map_or(&Tok::Eof, |t| &t.value)
     }
 
     fn peek2(&self) -> &Tok {
-        self.toks.get(self.i + 1).unwrap_or(&Tok::Eof)
+        self.toks.get(self.i + 1).map_or(&Tok::Eof, |t| &t.value)
+    }
+
+    fn peek3(&self) -> &Tok {
+        self.toks.get(self.i + 2).map_or(&Tok::Eof, |t| &t.value)
     }
 
     fn bump(&mut self) -> Tok {
-        let t = self.toks.get(self.i).cloned().unwrap_or(Tok::Eof);
+        let t = self.toks.get(self.i).map_or(Tok::Eof, |t| t.value.clone());
         self.i += 1;
         t
     }
 
+    // span of the next token; past the end this is the Eof token's span
+    fn span(&self) -> Span {
+        self.toks.get(self.i).or(self.toks.last()).map_or(Span::default(), |t| t.span)
+    }
+
+    // span of the token just consumed by `bump`
+    fn prev_span(&self) -> Span {
+        let i = self.i.saturating_sub(1);
+        self.toks.get(i).or(self.toks.last()).map_or(Span::default(), |t| t.span)
+    }
+
+    fn err_at<T>(&self, span: Span, msg: impl Into<String>) -> Result<T, ParseError> {
+        self.fail_at(span, ParseErrorKind::Other(msg.into()))
+    }
+
+    fn fail_at<T>(&self, span: Span, kind: ParseErrorKind) -> Result<T, ParseError> {
+        Err(ParseError { kind, line: span.line, col: span.col })
+    }
+
+    // the current token isn't what `want` describes; running out of input is its own kind
+    fn unexpected<T>(&self, want: &str, kind: ParseErrorKind) -> Result<T, ParseError> {
+        match self.peek() {
+            Tok::Eof => self.fail_at(self.span(), ParseErrorKind::UnexpectedEof { want: want.into() }),
+            _ => self.fail_at(self.span(), kind),
+        }
+    }
+
+    fn warn_at(&mut self, span: Span, msg: impl Into<String>) {
+        self.warnings.push(Warning { msg: msg.into(), line: span.line, col: span.col });
+    }
+
+    // an error unwinding through several recovery points is only reported once
+    fn record(&mut self, e: ParseError) {
+        if self.errors.last().is_none_or(|l| (l.line, l.col) != (e.line, e.col)) {
+            self.errors.push(e);
+        }
+    }
+
+    // Skips the rest of a bad statement: through the next `;` or balanced `{...}`,
+    // stopping before a `}` that closes the enclosing block.
+    fn synchronize(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.peek() {
+                Tok::Eof => return,
+                Tok::RBrace if depth == 0 => return,
+                Tok::Semi if depth == 0 => {
+                    self.bump();
+                    return;
+                }
+                Tok::LBrace => depth += 1,
+                Tok::RBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.bump();
+                        return;
+                    }
+                }
+                _ => {}
+            }
+            self.bump();
+        }
+    }
+
+    // Like `synchronize`, but a stray `}` at file scope is skipped on its own, so
+    // every call consumes at least one token before end of input.
+    fn synchronize_top_level(&mut self) {
+        if self.peek_is(&Tok::RBrace) {
+            self.bump();
+        } else {
+            self.synchronize();
+        }
+    }
+
+    // a statement, or nothing after recording its error and skipping past it
+    fn parse_stmt_recovering(&mut self) -> Vec<s::Stmt> {
+        self.parse_stmt().unwrap_or_else(|e| {
+            self.hoisted.clear();
+            self.record(e);
+            self.synchronize();
+            Vec::new()
+        })
+    }
+
     fn peek_is(&self, t: &Tok) -> bool {
         self.peek() == t
     }
 
+    // a mismatched token is left in place, so recovery can still see a `;` or `}`
     fn expect(&mut self, want: Tok) -> Result<(), ParseError> {
-        let got = self.bump();
-        if got == want {
+        if self.peek_is(&want) {
+            self.bump();
             Ok(())
         } else {
-            Err(ParseError(format!("expected {:?}, got {:?}", want, got)))
+            let got = self.peek().clone();
+            self.unexpected(&want.describe(), ParseErrorKind::Expected { want, got })
         }
     }
 
     fn expect_ident(&mut self) -> Result<String, ParseError> {
-        match self.bump() {
-            Tok::Ident(s) => Ok(s),
-            other => Err(ParseError(format!("expected identifier, got {:?}", other))),
+        match self.peek().clone() {
+            Tok::Ident(s) => {
+                self.bump();
+                Ok(s)
+            }
+            got => self.unexpected("identifier", ParseErrorKind::ExpectedIdent { got }),
         }
     }
 
-    fn parse_type(&mut self) -> Result<s::TypeRef, ParseError> {
-        // 매우 간단: [unsigned] int | void
-        let mut signed = true;
-        if self.peek_is(&Tok::Unsigned) {
-            self.bump();
-            signed = false;
-        }
+    fn is_type_start(&self) -> bool {
+        matches!(
+            self.peek(),
+            Tok::Void
+                | Tok::Char
+                | Tok::Short
+                | Tok::Int
+                | Tok::Long
+                | Tok::Float
+                | Tok::Double
+                | Tok::Signed
+                | Tok::Unsigned
+                | Tok::Bool
+        )
+    }
 
-        match self.bump() {
-            Tok::Int => Ok(s::TypeRef::Int { bits: 32, signed }),
-            Tok::Void => Ok(s::TypeRef::Void),
-            other => Err(ParseError(format!("expected type, got {:?}", other))),
-        }
+    // a type name inside parens, as in casts and `sizeof(type)`
+    fn is_type_name(&self) -> bool {
+        self.is_type_start() || self.is_decl_start() || self.peek_is(&Tok::Const)
     }
 
-    fn lit_i32(v: i128) -> s::Expr {
-        s::Expr::Lit(s::Lit::Int { bits: 32, signed: true, value: v })
+    fn typedef_of(&self, name: &str) -> Option<&s::TypeRef> {
+        self.typedefs.iter().find(|(n, _)| n == name).map(|(_, ty)| ty)
     }
 
-    fn ensure_bool(e: s::Expr) -> s::Expr {
-    match e {
-        s::Expr::Cmp { .. } => e,
-        s::Expr::Lit(s::Lit::Bool(_)) => e,
-        _ => s::Expr::Cmp {
-            left: Box::new(e),
-            op: s::CmpOpRef::Ne,
-            right: Box::new(Self::lit_i32(0)),
-        },
+    // does a local declaration start here?
+    fn is_decl_start(&self) -> bool {
+        match self.peek() {
+            Tok::Char
+            | Tok::Short
+            | Tok::Int
+            | Tok::Long
+            | Tok::Float
+            | Tok::Double
+            | Tok::Signed
+            | Tok::Unsigned
+            | Tok::Bool
+            | Tok::Void
+            | Tok::Struct
+            | Tok::Enum => true,
+            Tok::Ident(name) => self.typedef_of(name).is_some(),
+            _ => false,
+        }
     }
-}
 
-    fn parse_global_const(&mut self) -> Result<s::GlobalConst, ParseError> {
-        self.expect(Tok::Const)?;
+    // typedef type name dims ';'
+    fn parse_typedef(&mut self) -> Result<(), ParseError> {
+        self.expect(Tok::Typedef)?;
         let ty = self.parse_type()?;
+        let span = self.span();
         let name = self.expect_ident()?;
-        self.expect(Tok::Assign)?;
-        let init = self.parse_expr()?;
+        let ty = self.parse_array_dims(ty)?;
         self.expect(Tok::Semi)?;
-        Ok(s::GlobalConst { name, ty, init })
+        if self.typedef_of(&name).is_some() {
+            return self.err_at(span, format!("redefinition of typedef `{}`", name));
+        }
+        self.typedefs.push((name, ty));
+        Ok(())
     }
 
-    fn parse_function(&mut self) -> Result<s::Function, ParseError> {
-        let return_type = self.parse_type()?;
+    // struct Name { (type declarator (',' declarator)* ';')* } ';'
+    fn parse_struct_def(&mut self) -> Result<(), ParseError> {
+        self.expect(Tok::Struct)?;
+        let span = self.span();
         let name = self.expect_ident()?;
+        if self.structs.iter().any(|d| d.name == name) {
+            return self.err_at(span, format!("redefinition of `struct {}`", name));
+        }
 
-        self.expect(Tok::LParen)?;
-        let mut parameters = Vec::new();
-        if !self.peek_is(&Tok::RParen) {
+        self.expect(Tok::LBrace)?;
+        let mut fields: Vec<(String, s::TypeRef)> = Vec::new();
+        while !self.peek_is(&Tok::RBrace) {
+            let base = self.parse_base_type()?;
             loop {
-                let ty = self.parse_type()?;
-                let pname = self.expect_ident()?;
-                parameters.push(s::Parameter { name: pname, ty });
-
-                if self.peek_is(&Tok::Comma) {
-                    self.bump();
-                    continue;
+                let (fname, ty, fspan) = self.parse_declarator(&base)?;
+                if fields.iter().any(|(n, _)| *n == fname) {
+                    return self.err_at(fspan, format!("duplicate member `{}`", fname));
                 }
-                break;
+                if self.target.size_align(&ty, &self.structs).is_none() {
+                    return self.err_at(fspan, format!("member `{}` has incomplete type", fname));
+                }
+                fields.push((fname, ty));
+                if !self.peek_is(&Tok::Comma) {
+                    break;
+                }
+                self.bump();
             }
+            self.expect(Tok::Semi)?;
         }
-        self.expect(Tok::RParen)?;
+        self.expect(Tok::RBrace)?;
+        self.expect(Tok::Semi)?;
 
-        let body = self.parse_block()?; // 함수는 무조건 { ... }
-        Ok(s::Function { name, parameters, return_type, body })
+        let Some(def) = self.target.layout_struct(name, fields, &self.structs) else {
+            return self.err_at(span, "struct has incomplete member type");
+        };
+        self.structs.push(def);
+        Ok(())
     }
 
-    fn parse_block(&mut self) -> Result<Vec<s::Stmt>, ParseError> {
+    // enum [Name] { A [= C], ... [,] } ';'
+    fn parse_enum_def(&mut self) -> Result<(), ParseError> {
+        self.expect(Tok::Enum)?;
+        if let Tok::Ident(name) = self.peek().clone() {
+            if self.enum_types.contains(&name) {
+                return self.err_at(self.span(), format!("redefinition of `enum {}`", name));
+            }
+            self.bump();
+            self.enum_types.push(name);
+        }
+
         self.expect(Tok::LBrace)?;
-        let mut out = Vec::new();
+        let mut next: i128 = 0;
         while !self.peek_is(&Tok::RBrace) {
-            let mut part = self.parse_stmt()?; // stmt는 Vec로 (블록 flatten)
-            out.append(&mut part);
+            let span = self.span();
+            let name = self.expect_ident()?;
+            if self.enumerators.iter().any(|(n, _)| *n == name) {
+                return self.err_at(span, format!("redefinition of enumerator `{}`", name));
+            }
+            if self.peek_is(&Tok::Assign) {
+                self.bump();
+                next = self.parse_const_int("enumerator value")?;
+            }
+            self.enumerators.push((name, next));
+            next += 1;
+
+            if !self.peek_is(&Tok::Comma) {
+                break;
+            }
+            self.bump();
         }
         self.expect(Tok::RBrace)?;
-        Ok(out)
+        self.expect(Tok::Semi)?;
+        Ok(())
     }
 
-    fn parse_stmt_or_block(&mut self) -> Result<Vec<s::Stmt>, ParseError> {
-        if self.peek_is(&Tok::LBrace) {
-            self.parse_block()
-        } else {
-            self.parse_stmt()
-        }
+    // struct Name
+    fn parse_struct_type(&mut self) -> Result<s::TypeRef, ParseError> {
+        self.expect(Tok::Struct)?;
+        let name = self.expect_ident()?;
+        Ok(s::TypeRef::Struct(name))
     }
 
-    fn parse_stmt(&mut self) -> Result<Vec<s::Stmt>, ParseError> {
-        match self.peek() {
-            Tok::LBrace => return self.parse_block(),
-
-            Tok::Return => {
-                self.bump();
-                if self.peek_is(&Tok::Semi) {
-                    self.bump();
-                    return Ok(vec![s::Stmt::Return(None)]);
-                }
-                let e = self.parse_expr()?;
-                self.expect(Tok::Semi)?;
-                return Ok(vec![s::Stmt::Return(Some(e))]);
+    // base '*'*, for parameters, return types and other abstract uses
+    fn parse_type(&mut self) -> Result<s::TypeRef, ParseError> {
+        let span = self.span();
+        let ty = self.parse_base_type()?;
+        let ty = self.parse_pointers(ty);
+        if let s::TypeRef::Struct(name) = &ty {
+            if !self.structs.iter().any(|d| d.name == *name) {
+                return self.err_at(span, format!("use of undefined `struct {}`", name));
             }
+        }
+        Ok(ty)
+    }
 
-            Tok::Const => {
+    // typedef-name | struct Name | enum Name
+    //   | [signed|unsigned] (void | char | short [int] | int | long [long] [int])
+    //   | float | [long] double
+    fn parse_base_type(&mut self) -> Result<s::TypeRef, ParseError> {
+        if let Tok::Ident(name) = self.peek() {
+            if let Some(ty) = self.typedef_of(name).cloned() {
                 self.bump();
-                let ty = self.parse_type()?;
-                let name = self.expect_ident()?;
-                self.expect(Tok::Assign)?;
-                let init = self.parse_expr()?;
-                self.expect(Tok::Semi)?;
-                return Ok(vec![s::Stmt::ConstDecl { name, ty, init }]);
+                return Ok(ty);
             }
-
-            Tok::Int | Tok::Unsigned => {
-                let ty = self.parse_type()?;
-                let name = self.expect_ident()?;
-                let init = if self.peek_is(&Tok::Assign) {
-                    self.bump();
-                    Some(self.parse_expr()?)
-                } else {
-                    None // C의 "int x;" -> IR에서 undef로 처리(위 패치가 담당)
-                };
-                self.expect(Tok::Semi)?;
-                return Ok(vec![s::Stmt::VarDecl { name, ty, init }]);
+        }
+        if self.peek_is(&Tok::Struct) {
+            return self.parse_struct_type();
+        }
+        if self.peek_is(&Tok::Enum) {
+            // enum types behave as int
+            self.bump();
+            let span = self.span();
+            let name = self.expect_ident()?;
+            if !self.enum_types.contains(&name) {
+                return self.err_at(span, format!("use of undefined `enum {}`", name));
             }
+            return Ok(s::TypeRef::Int { bits: self.target.int_bits, signed: true });
+        }
+        let start = self.span();
+        let (mut signed, mut unsigned) = (false, false);
+        let (mut void, mut char_, mut short, mut int, mut longs) = (false, false, false, false, 0);
+        let (mut float, mut double, mut bool_) = (false, false, false);
 
-            Tok::If => {
-                self.bump();
-                self.expect(Tok::LParen)?;
-                let cond_expr = self.parse_expr()?;
-                let cond = Self::ensure_bool(cond_expr);
-                self.expect(Tok::RParen)?;
-
-                let then_body = self.parse_stmt_or_block()?;
-                let else_body = if self.peek_is(&Tok::Else) {
-                    self.bump();
-                    self.parse_stmt_or_block()?
-                } else {
-                    Vec::new()
-                };
-
-                return Ok(vec![s::Stmt::If { cond, then_body, else_body }]);
+        while self.is_type_start() {
+            let tok = self.bump();
+            let seen = match tok {
+                Tok::Signed => std::mem::replace(&mut signed, true),
+                Tok::Unsigned => std::mem::replace(&mut unsigned, true),
+                Tok::Void => std::mem::replace(&mut void, true),
+                Tok::Char => std::mem::replace(&mut char_, true),
+                Tok::Short => std::mem::replace(&mut short, true),
+                Tok::Int => std::mem::replace(&mut int, true),
+                Tok::Float => std::mem::replace(&mut float, true),
+                Tok::Double => std::mem::replace(&mut double, true),
+                Tok::Bool => std::mem::replace(&mut bool_, true),
+                _ => {
+                    longs += 1;
+                    longs > 2
+                }
+            };
+            if seen {
+                let msg = format!("duplicate type specifier {}", tok.describe());
+                return self.err_at(self.prev_span(), msg);
             }
+        }
 
-            Tok::While => {
-                self.bump();
-                self.expect(Tok::LParen)?;
-                let cond_expr = self.parse_expr()?;
-                let cond = Self::ensure_bool(cond_expr);
-                self.expect(Tok::RParen)?;
-                let body = self.parse_stmt_or_block()?;
-                return Ok(vec![s::Stmt::While { cond, body }]);
+        if signed && unsigned {
+            return self.err_at(start, "`signed` and `unsigned` cannot be combined");
+        }
+        if void {
+            if signed || unsigned || char_ || short || int || longs > 0 {
+                return self.err_at(start, "`void` cannot be combined with other type specifiers");
             }
-
-            Tok::Break => {
-                self.bump();
-                self.expect(Tok::Semi)?;
-                return Ok(vec![s::Stmt::Break]);
+            return Ok(s::TypeRef::Void);
+        }
+        if bool_ {
+            if signed || unsigned || char_ || short || int || longs > 0 || float || double {
+                return self.err_at(start, "conflicting type specifiers");
             }
-
-            Tok::Continue => {
-                self.bump();
-                self.expect(Tok::Semi)?;
-                return Ok(vec![s::Stmt::Continue]);
+            // one bit of value, stored in a byte
+            return Ok(s::TypeRef::Int { bits: 1, signed: false });
+        }
+        if float || double {
+            // `long double` is plain double here, as on MSVC
+            let bits = match (float, double, longs) {
+                (true, false, 0) => self.target.float_bits,
+                (false, true, 0 | 1) => self.target.double_bits,
+                _ => 0,
+            };
+            if bits == 0 || signed || unsigned || char_ || short || int {
+                return self.err_at(start, "conflicting type specifiers");
             }
+            return Ok(s::TypeRef::Float { bits });
+        }
 
-            Tok::Ident(_) => {
-                // assign or exprstmt
-                if matches!((self.peek(), self.peek2()), (Tok::Ident(_), Tok::Assign)) {
-                    let name = self.expect_ident()?;
-                    self.expect(Tok::Assign)?;
-                    let value = self.parse_expr()?;
-                    self.expect(Tok::Semi)?;
-                    return Ok(vec![s::Stmt::Assign { name, value }]);
-                }
-
-                let e = self.parse_expr()?;
-                self.expect(Tok::Semi)?;
-                return Ok(vec![s::Stmt::ExprStmt(e)]);
+        let t = &self.target;
+        let bits = match (char_, short, longs) {
+            (false, false, 0) if signed || unsigned || int => t.int_bits,
+            (false, false, 0) => {
+                let got = self.peek().clone();
+                return self.unexpected("type", ParseErrorKind::ExpectedType { got });
             }
+            (true, false, 0) if !int => t.char_bits,
+            (false, true, 0) => t.short_bits,
+            (false, false, 1) => t.long_bits,
+            (false, false, 2) => t.long_long_bits,
+            _ => return self.err_at(start, "conflicting type specifiers"),
+        };
+        Ok(s::TypeRef::Int { bits, signed: !unsigned })
+    }
 
-            _ => {}
+    // declarator suffix: ('[' N ']')*, outermost dimension first
+    fn parse_array_dims(&mut self, ty: s::TypeRef) -> Result<s::TypeRef, ParseError> {
+        let mut dims = Vec::new();
+        while self.peek_is(&Tok::LBracket) {
+            self.bump();
+            let span = self.span();
+            // `[]` is only meaningful where the array decays; callers check
+            let len = if self.peek_is(&Tok::RBracket) {
+                0
+            } else {
+                match self.parse_const_int("array size")? {
+                    n if n > 0 => n as u64,
+                    _ => return self.err_at(span, "array size must be positive"),
+                }
+            };
+            self.expect(Tok::RBracket)?;
+            dims.push(len);
         }
-
-        // fallback: exprstmt
-        let e = self.parse_expr()?;
-        self.expect(Tok::Semi)?;
-        Ok(vec![s::Stmt::ExprStmt(e)])
+        Ok(dims.into_iter().rev().fold(ty, |elem, len| s::TypeRef::Array { elem: Box::new(elem), len }))
     }
 
-    // expr := cmp
-    fn parse_expr(&mut self) -> Result<s::Expr, ParseError> {
-        self.parse_cmp()
+    // an integer constant expression: array sizes, enumerators, integer const initializers
+    fn parse_const_int(&mut self, what: &str) -> Result<i128, ParseError> {
+        let span = self.span();
+        let mark = self.hoisted.len();
+        let e = self.parse_expr()?;
+        if self.hoisted.len() > mark {
+            self.hoisted.truncate(mark);
+            let msg = format!("{} is not an integer constant: it contains an assignment", what);
+            return self.err_at(span, msg);
+        }
+        match fold::const_eval(&e, &self.consts, &self.target) {
+            Ok(v) => Ok(v),
+            Err(err) => self.err_at(span, format!("{} is not an integer constant: {}", what, err)),
+        }
     }
 
-    // cmp := add ( (==|!=|<|<=|>|>=) add )?
-    fn parse_cmp(&mut self) -> Result<s::Expr, ParseError> {
-        let left = self.parse_add()?;
-        let op = match self.peek() {
-            Tok::EqEq => Some(s::CmpOpRef::Eq),
-            Tok::NotEq => Some(s::CmpOpRef::Ne),
-            Tok::Lt => Some(s::CmpOpRef::Lt),
-            Tok::Le => Some(s::CmpOpRef::Le),
-            Tok::Gt => Some(s::CmpOpRef::Gt),
-            Tok::Ge => Some(s::CmpOpRef::Ge),
-            _ => None,
-        };
-
-        if let Some(op) = op {
-            self.bump();
-            let right = self.parse_add()?;
-            Ok(s::Expr::Cmp { left: Box::new(left), op, right: Box::new(right) })
-        } else {
-            Ok(left)
+    fn has_unsized_dim(ty: &s::TypeRef) -> bool {
+        match ty {
+            s::TypeRef::Array { len: 0, .. } => true,
+            s::TypeRef::Array { elem, .. } => Self::has_unsized_dim(elem),
+            _ => false,
         }
     }
 
-    // add := mul (('+'|'-') mul)*
-    fn parse_add(&mut self) -> Result<s::Expr, ParseError> {
-        let mut e = self.parse_mul()?;
+    // declarator := '*'* ( ident | '(' declarator ')' ) ('[' N ']')*
+    // In `int (*p)[3]` the suffix binds first, so the parenthesized part is
+    // skipped, the suffix read, and then the inner declarator read on top of it.
+    fn parse_declarator(&mut self, base: &s::TypeRef) -> Result<(String, s::TypeRef, Span), ParseError> {
+        let ty = self.parse_pointers(base.clone());
+        if !self.peek_is(&Tok::LParen) {
+            let span = self.span();
+            let name = self.expect_ident()?;
+            let ty = self.parse_array_dims(ty)?;
+            return Ok((name, ty, span));
+        }
+        self.bump();
+        let inner = self.i;
+        let mut depth = 0usize;
         loop {
-            let op = match self.peek() {
-                Tok::Plus => Some(s::BinOpRef::Add),
-                Tok::Minus => Some(s::BinOpRef::Sub),
-                _ => None,
-            };
-            let Some(op) = op else { break; };
+            match self.peek() {
+                Tok::LParen => depth += 1,
+                Tok::RParen if depth == 0 => break,
+                Tok::RParen => depth -= 1,
+                Tok::Semi | Tok::LBrace | Tok::RBrace | Tok::Eof => break,
+                _ => {}
+            }
             self.bump();
-            let r = self.parse_mul()?;
-            e = s::Expr::Binary { left: Box::new(e), op, right: Box::new(r) };
         }
-        Ok(e)
+        self.expect(Tok::RParen)?;
+        if self.peek_is(&Tok::LParen) {
+            let msg = "function pointers are not supported: Whale IR has no function types";
+            return self.err_at(self.span(), msg);
+        }
+        let ty = self.parse_array_dims(ty)?;
+        let end = self.i;
+        self.i = inner;
+        let (name, ty, span) = self.parse_declarator(&ty)?;
+        self.expect(Tok::RParen)?;
+        self.i = end;
+        Ok((name, ty, span))
     }
 
-    // mul := primary (('*') primary)*
-    fn parse_mul(&mut self) -> Result<s::Expr, ParseError> {
-        let mut e = self.parse_primary()?;
-        while self.peek_is(&Tok::Star) {
-            self.bump();
-            let r = self.parse_primary()?;
-            e = s::Expr::Binary { left: Box::new(e), op: s::BinOpRef::Mul, right: Box::new(r) };
+    // a variable needs a complete type: no `[]`, no undefined struct
+    fn check_object_type(&self, name: &str, ty: &s::TypeRef, span: Span) -> Result<(), ParseError> {
+        if self.target.size_align(ty, &self.structs).is_none() {
+            return self.err_at(span, format!("variable `{}` has incomplete type", name));
         }
-        Ok(e)
+        Ok(())
     }
 
-    fn parse_primary(&mut self) -> Result<s::Expr, ParseError> {
-        match self.bump() {
-            Tok::IntLit(v) => Ok(s::Expr::Lit(s::Lit::Int { bits: 32, signed: true, value: v })),
-            Tok::Ident(name) => Ok(s::Expr::Var(name)),
-            Tok::True => Ok(s::Expr::Lit(s::Lit::Bool(true))),
-            Tok::False => Ok(s::Expr::Lit(s::Lit::Bool(false))),
-            Tok::LParen => {
-                let e = self.parse_expr()?;
-                self.expect(Tok::RParen)?;
-                Ok(e)
+    // `*e`; a `void *` points at nothing that can be read or written
+    fn deref(&self, e: s::Expr, span: Span) -> Result<s::Expr, ParseError> {
+        if let Some(s::TypeRef::Pointer(elem)) = self.type_of(&e) {
+            if matches!(*elem, s::TypeRef::Void) {
+                return self.err_at(span, "cannot dereference a `void *`");
             }
-            other => Err(ParseError(format!("expected primary, got {:?}", other))),
         }
+        Ok(s::Expr::Unary { op: s::UnOpRef::Deref, expr: Box::new(e) })
     }
-}
\ No newline at end of file
+
+    // base '*'*
+    fn parse_pointers(&mut self, mut ty: s::TypeRef) -> s::TypeRef {
+        while self.peek_is(&Tok::Star) {
+            self.bump();
+            ty = s::TypeRef::Pointer(Box::new(ty));
+        }
+        ty
+    }
+
+    fn lit_i32(v: i128) -> s::Expr {
+        s::Expr::Lit(s::Lit::Int { bits: 32, signed: true, value: v })
+    }
+
+    // int / unsigned / long / unsigned long / long long,
+    // widened like C when the value doesn't fit the suffixed type
+    fn lit_int(&self, v: i128, sfx: IntSuffix) -> s::Expr {
+        let t = &self.target;
+        let signed = !sfx.unsigned;
+        for bits in [t.int_bits, t.long_bits, t.long_long_bits].into_iter().skip(sfx.longs as usize) {
+            let max = if signed { (1i128 << (bits - 1)) - 1 } else { (1i128 << bits) - 1 };
+            if v <= max {
+                return s::Expr::Lit(s::Lit::Int { bits, signed, value: v });
+            }
+        }
+        s::Expr::Lit(s::Lit::Int { bits: t.long_long_bits, signed: false, value: v })
+    }
+
+    // a condition: comparisons, `&&`/`||` and `_Bool` values are used as they are
+    fn ensure_bool(&self, e: s::Expr) -> s::Expr {
+        match self.type_of(&e) {
+            Some(ty) if is_bool(&ty) => e,
+            _ => self.nonzero(e),
+        }
+    }
+
+    fn nonzero(&self, e: s::Expr) -> s::Expr {
+        match e {
+            s::Expr::Cmp { .. } => e,
+            s::Expr::Logical { .. } => e,
+            s::Expr::Lit(s::Lit::Bool(_)) => e,
+            _ => {
+                let zero = self.zero_of(&e);
+                s::Expr::Cmp { left: Box::new(e), op: s::CmpOpRef::Ne, right: Box::new(zero) }
+            }
+        }
+    }
+
+    // zero of the type of `e`, to test it against: a null pointer for pointers
+    fn zero_of(&self, e: &s::Expr) -> s::Expr {
+        match self.type_of(e) {
+            Some(s::TypeRef::Int { bits, signed }) => {
+                s::Expr::Lit(s::Lit::Int { bits, signed, value: 0 })
+            }
+            Some(s::TypeRef::Float { bits }) => s::Expr::Lit(s::Lit::Float { bits, value: 0.0 }),
+            Some(s::TypeRef::Pointer(elem) | s::TypeRef::Array { elem, .. }) => {
+                self.cast(s::TypeRef::Pointer(elem), Self::lit_i32(0))
+            }
+            _ => Self::lit_i32(0),
+        }
+    }
+
+    fn compound_op(t: &Tok) -> Option<s::BinOpRef> {
+        Some(match t {
+            Tok::PlusAssign => s::BinOpRef::Add,
+            Tok::MinusAssign => s::BinOpRef::Sub,
+            Tok::StarAssign => s::BinOpRef::Mul,
+            Tok::SlashAssign => s::BinOpRef::Div,
+            Tok::PercentAssign => s::BinOpRef::Rem,
+            Tok::AmpAssign => s::BinOpRef::BitAnd,
+            Tok::PipeAssign => s::BinOpRef::BitOr,
+            Tok::CaretAssign => s::BinOpRef::BitXor,
+            Tok::ShlAssign => s::BinOpRef::Shl,
+            Tok::ShrAssign => s::BinOpRef::Shr,
+            _ => return None,
+        })
+    }
+
+    fn parse_storage_class(&mut self) -> Option<Tok> {
+        match self.peek() {
+            Tok::Extern | Tok::Static => Some(self.bump()),
+            _ => None,
+        }
+    }
+
+    // [const] type ident '(' -- a function, as opposed to an object declaration
+    fn is_fn_ahead(&mut self) -> bool {
+        let save = self.i;
+        if self.peek_is(&Tok::Const) {
+            self.bump();
+        }
+        let ahead = self.parse_type().is_ok()
+            && self.expect_ident().is_ok()
+            && self.peek_is(&Tok::LParen);
+        self.i = save;
+        ahead
+    }
+
+    // extern [const] type declarator (',' declarator)* ';'  (storage class already eaten);
+    // also whether they were declared `const`
+    fn parse_extern_decls(&mut self) -> Result<(bool, Vec<s::GlobalDecl>), ParseError> {
+        let read_only = self.peek_is(&Tok::Const);
+        if read_only {
+            self.bump();
+        }
+        let base = self.parse_base_type()?;
+        let mut out = Vec::new();
+        loop {
+            let (name, ty, _) = self.parse_declarator(&base)?;
+            if self.peek_is(&Tok::Assign) {
+                let msg = format!("`extern` declaration of `{}` cannot have an initializer", name);
+                return self.err_at(self.span(), msg);
+            }
+            out.push(s::GlobalDecl { name, ty });
+            if !self.peek_is(&Tok::Comma) {
+                break;
+            }
+            self.bump();
+        }
+        self.expect(Tok::Semi)?;
+        Ok((read_only, out))
+    }
+
+    // type declarator ['=' expr] (',' declarator ['=' expr])* ';'
+    fn parse_global_vars(&mut self, linkage: s::Linkage) -> Result<Vec<s::GlobalVar>, ParseError> {
+        let base = self.parse_base_type()?;
+        let mut out = Vec::new();
+        loop {
+            let (name, ty, span) = self.parse_declarator(&base)?;
+            self.check_object_type(&name, &ty, span)?;
+            let init = if self.peek_is(&Tok::Assign) {
+                self.bump();
+                Some(self.parse_static_init(&ty)?)
+            } else {
+                None // zero-initialized, as C does for file-scope objects
+            };
+            out.push(s::GlobalVar { name, ty, init, linkage });
+            if !self.peek_is(&Tok::Comma) {
+                break;
+            }
+            self.bump();
+        }
+        self.expect(Tok::Semi)?;
+        Ok(out)
+    }
+
+    // objects with static storage are initialized before any code runs, so the
+    // initializer must be an arithmetic constant or the address of such an object
+    fn parse_static_init(&mut self, ty: &s::TypeRef) -> Result<s::Expr, ParseError> {
+        let span = self.span();
+        let init = self.parse_expr()?;
+        if !self.hoisted.is_empty() {
+            return self.err_at(span, "assignment in the initializer of a static object");
+        }
+        self.check_narrowing(ty, &init, span);
+        let init = self.convert_to(ty, init);
+        if let Err(err) = fold::const_lit(&init, &self.consts, &self.target) {
+            if !self.is_address_constant(&init) {
+                return self.err_at(span, format!("initializer is not a constant: {}", err));
+            }
+        }
+        Ok(init)
+    }
+
+    // `&x`, `&s.f`, `&a[2]`, an array `a` or one of those plus or minus a constant,
+    // where the object has static storage
+    fn is_address_constant(&self, e: &s::Expr) -> bool {
+        match e {
+            s::Expr::Var(name) => {
+                matches!(self.type_of(e), Some(s::TypeRef::Array { .. })) && self.is_static_object(name)
+            }
+            s::Expr::Unary { op: s::UnOpRef::AddrOf, expr } => self.is_static_lvalue(expr),
+            s::Expr::Cast { ty: s::TypeRef::Pointer(_), expr } => self.is_address_constant(expr),
+            s::Expr::Binary { left, op: s::BinOpRef::Add | s::BinOpRef::Sub, right } => {
+                let offset = |e| fold::const_eval(e, &self.consts, &self.target).is_ok();
+                (self.is_address_constant(left) && offset(right))
+                    || (self.is_address_constant(right) && offset(left))
+            }
+            _ => false,
+        }
+    }
+
+    fn is_static_lvalue(&self, e: &s::Expr) -> bool {
+        match e {
+            s::Expr::Var(name) => self.is_static_object(name),
+            s::Expr::Member { base, .. } => self.is_static_lvalue(base),
+            s::Expr::Unary { op: s::UnOpRef::Deref, expr } => self.is_address_constant(expr),
+            _ => false,
+        }
+    }
+
+    // file-scope objects and static locals; `fn_locals` holds the current function's
+    // locals, static ones included under their global name
+    fn is_static_object(&self, name: &str) -> bool {
+        !self.fn_locals.iter().any(|l| l == name) || self.global_vars.iter().any(|g| g.name == name)
+    }
+
+    // An integer constant stored into an integer object it doesn't fit: `char c = 300;`,
+    // `unsigned u = -1;`. Values computed at run time aren't checked.
+    fn check_narrowing(&mut self, ty: &s::TypeRef, value: &s::Expr, span: Span) {
+        let &s::TypeRef::Int { bits, signed } = ty else { return };
+        if !matches!(self.type_of(value), Some(s::TypeRef::Int { .. })) {
+            return;
+        }
+        if let Ok(v) = fold::const_eval(value, &self.consts, &self.target) {
+            self.store_int(bits, signed, v, span);
+        }
+    }
+
+    // `v` as an integer object of the given width holds it, warning if that changes it
+    fn store_int(&mut self, bits: u16, signed: bool, v: i128, span: Span) -> i128 {
+        if bits == 1 {
+            return (v != 0) as i128;
+        }
+        let stored = fold::wrap(v, bits, signed);
+        if stored != v {
+            let ty = dump::type_str(&s::TypeRef::Int { bits, signed });
+            let msg = format!("implicit conversion to `{}` changes value from {} to {}", ty, v, stored);
+            self.warn_at(span, msg);
+        }
+        stored
+    }
+
+    // const type declarator '=' expr (',' declarator '=' expr)* ';'
+    // A pointer like `const int *p` is itself a variable, added to `vars`.
+    fn parse_global_const(
+        &mut self,
+        linkage: s::Linkage,
+        vars: &mut Vec<s::GlobalVar>,
+    ) -> Result<Vec<s::GlobalConst>, ParseError> {
+        self.expect(Tok::Const)?;
+        let base = self.parse_base_type()?;
+        let mut out = Vec::new();
+        loop {
+            let (name, ty, span) = self.parse_declarator(&base)?;
+            self.check_object_type(&name, &ty, span)?;
+            if has_pointer(&ty) {
+                let init = if self.peek_is(&Tok::Assign) {
+                    self.bump();
+                    Some(self.parse_static_init(&ty)?)
+                } else {
+                    None
+                };
+                vars.push(s::GlobalVar { name, ty, init, linkage });
+            } else {
+                self.expect(Tok::Assign)?;
+                let init = match ty {
+                    s::TypeRef::Int { bits, signed } => {
+                        let what = format!("initializer of `{}`", name);
+                        let init_span = self.span();
+                        let v = self.parse_const_int(&what)?;
+                        let value = self.store_int(bits, signed, v, init_span);
+                        let lit = s::Lit::Int { bits, signed, value };
+                        self.consts.push((name.clone(), lit.clone()));
+                        s::Expr::Lit(lit)
+                    }
+                    _ => self.parse_static_init(&ty)?,
+                };
+                out.push(s::GlobalConst { name, ty, init, linkage });
+            }
+            if !self.peek_is(&Tok::Comma) {
+                break;
+            }
+            self.bump();
+        }
+        self.expect(Tok::Semi)?;
+        Ok(out)
+    }
+
+    // type name '(' params ')'; parameter names may be omitted here
+    fn parse_fn_signature(&mut self, linkage: s::Linkage) -> Result<s::FunctionDecl, ParseError> {
+        let return_type = self.parse_type()?;
+        let name = self.expect_ident()?;
+
+        self.expect(Tok::LParen)?;
+        let mut parameters = Vec::new();
+        self.const_params.clear();
+        // `(void)` declares no parameters; `()` leaves them unspecified, which is
+        // treated as none when lenient and rejected when strict
+        if matches!((self.peek(), self.peek2()), (Tok::Void, Tok::RParen)) {
+            self.bump();
+        } else if self.peek_is(&Tok::RParen) {
+            if !self.opts.unprototyped {
+                let msg = format!("`{}` is declared without a prototype; write `{}(void)`", name, name);
+                return self.err_at(self.span(), msg);
+            }
+        } else {
+            loop {
+                let start = self.span();
+                let read_only = self.peek_is(&Tok::Const);
+                if read_only {
+                    self.bump();
+                }
+                let ty = self.parse_type()?;
+                if matches!(ty, s::TypeRef::Void) {
+                    return self.err_at(start, "`void` must be the only parameter");
+                }
+                let (pname, ty) = match self.peek() {
+                    Tok::Comma | Tok::RParen | Tok::LBracket => (String::new(), ty),
+                    Tok::LParen => {
+                        let (pname, ty, _) = self.parse_declarator(&ty)?;
+                        (pname, ty)
+                    }
+                    _ => (self.expect_ident()?, ty),
+                };
+                // array parameters decay to pointers; only the outer size may be omitted
+                let ty = match self.parse_array_dims(ty)? {
+                    s::TypeRef::Array { elem, .. } if !Self::has_unsized_dim(&elem) => {
+                        s::TypeRef::Pointer(elem)
+                    }
+                    s::TypeRef::Array { .. } => {
+                        return self.err_at(self.prev_span(), "array has incomplete element type");
+                    }
+                    ty => ty,
+                };
+                if read_only {
+                    self.const_params.push(pname.clone());
+                }
+                parameters.push(s::Parameter { name: pname, ty });
+
+                if self.peek_is(&Tok::Comma) {
+                    self.bump();
+                    if !self.peek_is(&Tok::RParen) {
+                        continue;
+                    }
+                    self.check_trailing_comma()?;
+                }
+                break;
+            }
+        }
+        self.expect(Tok::RParen)?;
+        Ok(s::FunctionDecl { name, parameters, return_type, linkage })
+    }
+
+    // one struct/enum/typedef definition, declaration or function definition
+    fn parse_top_level(
+        &mut self,
+        globals: &mut Vec<s::GlobalConst>,
+        prototypes: &mut Vec<s::FunctionDecl>,
+        functions: &mut Vec<s::Function>,
+    ) -> Result<(), ParseError> {
+        // no function's locals are in scope here
+        self.fn_locals.clear();
+        let ahead = (self.peek(), self.peek2(), self.peek3());
+        if matches!(ahead, (Tok::Struct, Tok::Ident(_), Tok::LBrace)) {
+            self.parse_struct_def()?;
+        } else if matches!(ahead, (Tok::Enum, Tok::LBrace, _))
+            || matches!(ahead, (Tok::Enum, Tok::Ident(_), Tok::LBrace))
+        {
+            self.parse_enum_def()?;
+        } else if self.peek_is(&Tok::Typedef) {
+            self.parse_typedef()?;
+        } else {
+            let storage = self.parse_storage_class();
+            let linkage = match storage {
+                Some(Tok::Static) => s::Linkage::Internal,
+                _ => s::Linkage::External,
+            };
+
+            if storage == Some(Tok::Extern) && !self.is_fn_ahead() {
+                let (read_only, mut decls) = self.parse_extern_decls()?;
+                self.vars.extend(decls.iter().map(|d| Binding::new(&d.name, &d.ty, read_only)));
+                self.global_decls.append(&mut decls);
+            } else if self.peek_is(&Tok::Const) {
+                let mut vars = Vec::new();
+                let mut consts = self.parse_global_const(linkage, &mut vars)?;
+                self.vars.extend(consts.iter().map(|g| Binding::new(&g.name, &g.ty, true)));
+                self.vars.extend(vars.iter().map(|v| Binding::new(&v.name, &v.ty, true)));
+                globals.append(&mut consts);
+                self.global_vars.append(&mut vars);
+            } else if !self.is_fn_ahead() {
+                let mut vars = self.parse_global_vars(linkage)?;
+                self.vars.extend(vars.iter().map(|v| Binding::new(&v.name, &v.ty, false)));
+                self.global_vars.append(&mut vars);
+            } else {
+                let sig = self.parse_fn_signature(linkage)?;
+                self.fns.push(sig.name.clone());
+                if self.peek_is(&Tok::Semi) {
+                    self.bump();
+                    prototypes.push(sig);
+                } else {
+                    functions.push(self.parse_fn_body(sig)?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_fn_body(&mut self, sig: s::FunctionDecl) -> Result<s::Function, ParseError> {
+        let s::FunctionDecl { name, parameters, return_type, linkage } = sig;
+        if parameters.iter().any(|p| p.name.is_empty()) {
+            let msg = format!("parameter name omitted in definition of `{}`", name);
+            return self.err_at(self.span(), msg);
+        }
+
+        self.fn_name = name.clone();
+        self.fn_ret = return_type.clone();
+        self.fn_locals = parameters.iter().map(|p| p.name.clone()).collect();
+        self.labels.clear();
+        self.gotos.clear();
+        // the parameters share a scope with the outermost block of the body
+        let depth = self.vars.len();
+        let const_params = std::mem::take(&mut self.const_params);
+        self.vars.extend(
+            parameters.iter().map(|p| Binding::new(&p.name, &p.ty, const_params.contains(&p.name))),
+        );
+        let body = self.parse_block_in(depth)?; // 함수는 무조건 { ... }
+        // `main` returns 0 when it runs off the end
+        if !matches!(return_type, s::TypeRef::Void) && name != "main" && Self::falls_through(&body) {
+            let msg = format!("control reaches end of non-void function `{}`", name);
+            self.warn_at(self.prev_span(), msg);
+        }
+        if let Some((label, span)) = self.gotos.iter().find(|(l, _)| !self.labels.contains(l)) {
+            let msg = format!("use of undefined label `{}`", label);
+            let e = ParseError { kind: ParseErrorKind::Other(msg), line: span.line, col: span.col };
+            self.record(e);
+        }
+        Ok(s::Function { name, parameters, return_type, body, linkage })
+    }
+
+    fn parse_block(&mut self) -> Result<Vec<s::Stmt>, ParseError> {
+        self.parse_block_in(self.vars.len())
+    }
+
+    // a block whose scope begins at `vars[scope]`; everything declared from there on
+    // goes out of scope at the closing brace
+    fn parse_block_in(&mut self, scope: usize) -> Result<Vec<s::Stmt>, ParseError> {
+        self.expect(Tok::LBrace)?;
+        let outer = std::mem::replace(&mut self.scope_start, scope);
+        let mut out = Vec::new();
+        let mut seen_stmt = false;
+        while !self.peek_is(&Tok::RBrace) && !self.is_eof() {
+            let span = self.span();
+            let decl = self.is_decl_start()
+                || matches!(self.peek(), Tok::Const | Tok::Typedef | Tok::Extern | Tok::Static);
+            if decl && seen_stmt && !self.opts.mixed_decls {
+                let msg = "declaration after a statement (not allowed with --std=c89)".to_string();
+                let e = ParseError { kind: ParseErrorKind::Other(msg), line: span.line, col: span.col };
+                self.record(e);
+            }
+            seen_stmt |= !decl;
+            let mut part = self.parse_stmt_recovering(); // stmt는 Vec로 (블록 flatten)
+            self.check_reachable(out.last(), &part, span);
+            out.append(&mut part);
+        }
+        self.scope_start = outer;
+        self.vars.truncate(scope);
+        self.expect(Tok::RBrace)?;
+        Ok(out)
+    }
+
+    // Brings a local into the current block's scope and returns the name it lowers to:
+    // `base`, or `base.1`, `base.2`, ... if another object visible in the function has it.
+    fn declare_local(
+        &mut self,
+        name: &str,
+        base: String,
+        ty: &s::TypeRef,
+        read_only: bool,
+        span: Span,
+    ) -> Result<String, ParseError> {
+        if self.vars[self.scope_start..].iter().any(|b| b.name == name) {
+            return self.err_at(span, format!("redeclaration of `{}`", name));
+        }
+        let mut lowered = base.clone();
+        let mut n = 0;
+        while self.fn_locals.contains(&lowered) || self.vars.iter().any(|b| b.lowered == lowered) {
+            n += 1;
+            lowered = format!("{}.{}", base, n);
+        }
+        self.fn_locals.push(lowered.clone());
+        self.vars.push(Binding { lowered: lowered.clone(), ..Binding::new(name, ty, read_only) });
+        Ok(lowered)
+    }
+
+    // Whether control can run off the end of `body`. Loops other than `while (true)`
+    // are assumed to exit, so this can only err towards warning.
+    fn falls_through(body: &[s::Stmt]) -> bool {
+        match body.last() {
+            Some(s::Stmt::Return(_) | s::Stmt::Goto(_)) => false,
+            Some(s::Stmt::If { then_body, else_body, .. }) => {
+                Self::falls_through(then_body) || Self::falls_through(else_body)
+            }
+            Some(s::Stmt::While { cond: s::Expr::Lit(s::Lit::Bool(true)), body }) => Self::breaks(body),
+            // without a `break`, a switch with a default is only left through its last arm
+            Some(s::Stmt::Switch { cases, .. }) => {
+                !cases.iter().any(|c| c.label.is_none())
+                    || cases.iter().any(|c| Self::breaks(&c.body))
+                    || cases.last().is_none_or(|c| Self::falls_through(&c.body))
+            }
+            _ => true,
+        }
+    }
+
+    // A loop condition that is a nonzero constant becomes a literal `true`, so
+    // `while (1)` lowers like `for (;;)`: an unconditional branch into the body.
+    fn loop_cond(&self, cond: s::Expr) -> s::Expr {
+        let cond = self.ensure_bool(cond);
+        if Self::always_true(&cond) {
+            s::Expr::Lit(s::Lit::Bool(true))
+        } else {
+            cond
+        }
+    }
+
+    // `while (1)` as `ensure_bool` leaves it
+    fn always_true(cond: &s::Expr) -> bool {
+        match cond {
+            s::Expr::Lit(s::Lit::Bool(b)) => *b,
+            s::Expr::Cmp { left, op: s::CmpOpRef::Ne, right } => matches!(
+                (&**left, &**right),
+                (s::Expr::Lit(s::Lit::Int { value: l, .. }), s::Expr::Lit(s::Lit::Int { value: 0, .. }))
+                    if *l != 0
+            ),
+            _ => false,
+        }
+    }
+
+    // a `break` that leaves the statement containing `body`, not a nested loop or switch
+    fn breaks(body: &[s::Stmt]) -> bool {
+        body.iter().any(|st| match st {
+            s::Stmt::Break => true,
+            s::Stmt::If { then_body, else_body, .. } => {
+                Self::breaks(then_body) || Self::breaks(else_body)
+            }
+            _ => false,
+        })
+    }
+
+    // `return e;` takes the values an assignment to the return type would
+    fn check_return(&self, e: &s::Expr, span: Span) -> Result<(), ParseError> {
+        if matches!(self.fn_ret, s::TypeRef::Void) {
+            let msg = format!("void function `{}` should not return a value", self.fn_name);
+            return self.err_at(span, msg);
+        }
+        // calls and the like aren't typed here; lowering checks those
+        let Some(ty) = self.type_of(e) else { return Ok(()) };
+        use s::TypeRef as T;
+        let fits = match (&self.fn_ret, &ty) {
+            (T::Int { .. } | T::Float { .. }, T::Int { .. } | T::Float { .. }) => true,
+            (T::Pointer(_), T::Pointer(_) | T::Array { .. }) => true,
+            // a null pointer constant
+            (T::Pointer(_), _) => matches!(e, s::Expr::Lit(s::Lit::Int { value: 0, .. })),
+            (T::Struct(want), T::Struct(got)) => want == got,
+            _ => false,
+        };
+        if fits {
+            return Ok(());
+        }
+        let msg = format!(
+            "returning `{}` from `{}`, which returns `{}`",
+            dump::type_str(&ty),
+            self.fn_name,
+            dump::type_str(&self.fn_ret),
+        );
+        self.err_at(span, msg)
+    }
+
+    // `prev; part` where `prev` jumps away: `part` can only run if it starts with a label
+    fn check_reachable(&mut self, prev: Option<&s::Stmt>, part: &[s::Stmt], span: Span) {
+        let jump = match prev {
+            Some(s::Stmt::Return(_)) => "return",
+            Some(s::Stmt::Break) => "break",
+            Some(s::Stmt::Continue) => "continue",
+            Some(s::Stmt::Goto(_)) => "goto",
+            _ => return,
+        };
+        if !part.is_empty() && !matches!(part[0], s::Stmt::Label(_)) {
+            self.warn_at(span, format!("unreachable statement after `{}`", jump));
+        }
+    }
+
+    fn parse_stmt_or_block(&mut self) -> Result<Vec<s::Stmt>, ParseError> {
+        if self.peek_is(&Tok::LBrace) {
+            self.parse_block()
+        } else {
+            self.parse_stmt()
+        }
+    }
+
+    fn parse_stmt(&mut self) -> Result<Vec<s::Stmt>, ParseError> {
+        match self.peek() {
+            Tok::LBrace => return self.parse_block(),
+
+            // empty statement, e.g. the body of `while (*p++) ;`
+            Tok::Semi => {
+                self.bump();
+                return Ok(Vec::new());
+            }
+
+            Tok::Return => {
+                self.bump();
+                if self.peek_is(&Tok::Semi) {
+                    if !matches!(self.fn_ret, s::TypeRef::Void) {
+                        let msg = format!("non-void function `{}` should return a value", self.fn_name);
+                        return self.err_at(self.prev_span(), msg);
+                    }
+                    self.bump();
+                    return Ok(vec![s::Stmt::Return(None)]);
+                }
+                let span = self.span();
+                let e = self.parse_comma()?;
+                self.check_return(&e, span)?;
+                let e = self.convert_to(&self.fn_ret, e);
+                self.expect(Tok::Semi)?;
+                let mut out = std::mem::take(&mut self.hoisted);
+                out.push(s::Stmt::Return(Some(e)));
+                return Ok(out);
+            }
+
+            Tok::Const => {
+                self.bump();
+                let base = self.parse_base_type()?;
+                let mut out = Vec::new();
+                loop {
+                    let (name, ty, span) = self.parse_declarator(&base)?;
+                    self.check_object_type(&name, &ty, span)?;
+                    let name = self.declare_local(&name, name.clone(), &ty, true, span)?;
+                    // `const int *p` is a variable pointing at const ints, so it
+                    // needn't be initialized
+                    let pointee_const = has_pointer(&ty);
+                    let init = if pointee_const && !self.peek_is(&Tok::Assign) {
+                        None
+                    } else {
+                        self.expect(Tok::Assign)?;
+                        let init_span = self.span();
+                        let init = self.parse_expr()?;
+                        self.check_narrowing(&ty, &init, init_span);
+                        Some(self.convert_to(&ty, init))
+                    };
+                    out.append(&mut self.hoisted);
+                    out.push(match init {
+                        Some(init) if !pointee_const => s::Stmt::ConstDecl { name, ty, init },
+                        init => s::Stmt::VarDecl { name, ty, init },
+                    });
+                    if !self.peek_is(&Tok::Comma) {
+                        break;
+                    }
+                    self.bump();
+                }
+                self.expect(Tok::Semi)?;
+                return Ok(out);
+            }
+
+            Tok::Typedef => {
+                self.parse_typedef()?;
+                return Ok(Vec::new());
+            }
+
+            Tok::Extern => {
+                self.bump();
+                // names the global itself, so it's bound without renaming
+                let (read_only, mut decls) = self.parse_extern_decls()?;
+                self.vars.extend(decls.iter().map(|d| Binding::new(&d.name, &d.ty, read_only)));
+                self.global_decls.append(&mut decls);
+                return Ok(Vec::new());
+            }
+
+            Tok::Static => {
+                // keeps its value across calls: hoist to an internal global `fn.name`
+                self.bump();
+                let read_only = self.peek_is(&Tok::Const);
+                if read_only {
+                    self.bump();
+                }
+                let span = self.span();
+                for mut var in self.parse_global_vars(s::Linkage::Internal)? {
+                    let global = format!("{}.{}", self.fn_name, var.name);
+                    var.name = self.declare_local(&var.name, global, &var.ty, read_only, span)?;
+                    self.global_vars.push(var);
+                }
+                return Ok(Vec::new());
+            }
+
+            _ if self.is_decl_start() => {
+                let base = self.parse_base_type()?;
+                let mut out = Vec::new();
+                loop {
+                    let (name, ty, span) = self.parse_declarator(&base)?;
+                    self.check_object_type(&name, &ty, span)?;
+                    let name = self.declare_local(&name, name.clone(), &ty, false, span)?;
+                    let init = if self.peek_is(&Tok::Assign) {
+                        self.bump();
+                        let span = self.span();
+                        let init = self.parse_expr()?;
+                        self.check_narrowing(&ty, &init, span);
+                        Some(self.convert_to(&ty, init))
+                    } else {
+                        None // C의 "int x;" -> IR에서 undef로 처리(위 패치가 담당)
+                    };
+                    out.append(&mut self.hoisted);
+                    out.push(s::Stmt::VarDecl { name, ty, init });
+                    if !self.peek_is(&Tok::Comma) {
+                        break;
+                    }
+                    self.bump();
+                }
+                self.expect(Tok::Semi)?;
+                return Ok(out);
+            }
+
+            Tok::If => {
+                // an `else if` chain is read in a loop rather than by recursion, so a long
+                // chain doesn't take stack per link; it still nests as `else { if ... }`
+                let mut links = Vec::new();
+                let mut else_body = Vec::new();
+                loop {
+                    self.bump();
+                    self.expect(Tok::LParen)?;
+                    let cond_expr = self.parse_comma()?;
+                    let cond = self.ensure_bool(cond_expr);
+                    self.expect(Tok::RParen)?;
+                    let pre = std::mem::take(&mut self.hoisted);
+                    let then_body = self.parse_stmt_or_block()?;
+                    links.push((pre, cond, then_body));
+                    if !self.peek_is(&Tok::Else) {
+                        break;
+                    }
+                    self.bump();
+                    if !self.peek_is(&Tok::If) {
+                        else_body = self.parse_stmt_or_block()?;
+                        break;
+                    }
+                }
+
+                for (mut out, cond, then_body) in links.into_iter().rev() {
+                    out.push(s::Stmt::If { cond, then_body, else_body });
+                    else_body = out;
+                }
+                return Ok(else_body);
+            }
+
+            Tok::While => {
+                self.bump();
+                self.expect(Tok::LParen)?;
+                let cond_expr = self.parse_comma()?;
+                let cond = self.loop_cond(cond_expr);
+                self.expect(Tok::RParen)?;
+                let pre = std::mem::take(&mut self.hoisted);
+                let body = self.parse_stmt_or_block()?;
+                return Ok(vec![self.while_loop(pre, cond, body)]);
+            }
+
+            Tok::Break => {
+                self.bump();
+                self.expect(Tok::Semi)?;
+                return Ok(vec![s::Stmt::Break]);
+            }
+
+            Tok::Continue => {
+                self.bump();
+                self.expect(Tok::Semi)?;
+                return Ok(vec![s::Stmt::Continue]);
+            }
+
+            Tok::Goto => {
+                self.bump();
+                let span = self.span();
+                let label = self.expect_ident()?;
+                self.expect(Tok::Semi)?;
+                self.gotos.push((label.clone(), span));
+                return Ok(vec![s::Stmt::Goto(label)]);
+            }
+
+            Tok::Ident(_) if matches!(self.peek2(), Tok::Colon) => {
+                let span = self.span();
+                let label = self.expect_ident()?;
+                self.bump();
+                if self.labels.contains(&label) {
+                    return self.err_at(span, format!("redefinition of label `{}`", label));
+                }
+                self.labels.push(label.clone());
+                return Ok(vec![s::Stmt::Label(label)]);
+            }
+
+            Tok::Switch => {
+                self.bump();
+                self.expect(Tok::LParen)?;
+                let value = self.parse_comma()?;
+                self.expect(Tok::RParen)?;
+                let mut out = std::mem::take(&mut self.hoisted);
+                let cases = self.parse_switch_body()?;
+                out.push(s::Stmt::Switch { value, cases });
+                return Ok(out);
+            }
+
+            Tok::Do => {
+                self.bump();
+                let body = self.parse_stmt_or_block()?;
+                self.expect(Tok::While)?;
+                self.expect(Tok::LParen)?;
+                let cond = self.parse_comma()?;
+                let cond = self.ensure_bool(cond);
+                self.expect(Tok::RParen)?;
+                if !self.peek_is(&Tok::Semi) {
+                    return self.err_at(self.prev_span(), "expected ';' after do-while condition");
+                }
+                self.bump();
+
+                // do body while (cond);  ==>  while (true) { body; next: if (!cond) break; }
+                // with `continue` as `goto next`. Copying the test in front of each
+                // `continue` would be wrong inside a `switch`, where its `break` binds.
+                let mut n = 0;
+                let mut next = "do.next".to_string();
+                while self.labels.contains(&next) {
+                    n += 1;
+                    next = format!("do.next.{}", n);
+                }
+                let mut continued = false;
+                let goto = [s::Stmt::Goto(next.clone())];
+                let mut body = Self::replace_continue(body, &goto, &mut continued);
+                if continued {
+                    self.labels.push(next.clone());
+                    body.push(s::Stmt::Label(next));
+                }
+                body.append(&mut self.hoisted);
+                body.push(s::Stmt::If {
+                    cond: self.logical_not(cond),
+                    then_body: vec![s::Stmt::Break],
+                    else_body: Vec::new(),
+                });
+                return Ok(vec![s::Stmt::While { cond: s::Expr::Lit(s::Lit::Bool(true)), body }]);
+            }
+
+            Tok::For => {
+                self.bump();
+                self.expect(Tok::LParen)?;
+                // the init clause declares into a scope of its own
+                let depth = self.vars.len();
+                let outer = std::mem::replace(&mut self.scope_start, depth);
+                let mut out = if self.peek_is(&Tok::Semi) {
+                    self.bump();
+                    Vec::new()
+                } else {
+                    self.parse_stmt()? // decl or exprstmt, eats the ';'
+                };
+
+                let cond = if self.peek_is(&Tok::Semi) {
+                    s::Expr::Lit(s::Lit::Bool(true))
+                } else {
+                    let cond = self.parse_comma()?;
+                    self.loop_cond(cond)
+                };
+                self.expect(Tok::Semi)?;
+                let pre = std::mem::take(&mut self.hoisted);
+
+                let step = if self.peek_is(&Tok::RParen) {
+                    Vec::new()
+                } else {
+                    self.parse_simple_stmts()?
+                };
+                self.expect(Tok::RParen)?;
+
+                // for (init; cond; step) body  ==>  init; while (cond) { body; step; }
+                let mut body = self.parse_stmt_or_block()?;
+                if !step.is_empty() {
+                    let mut with = step.clone();
+                    with.push(s::Stmt::Continue);
+                    body = Self::replace_continue(body, &with, &mut false);
+                }
+                body.extend(step);
+                self.scope_start = outer;
+                self.vars.truncate(depth);
+                out.push(self.while_loop(pre, cond, body));
+                return Ok(out);
+            }
+
+            _ => {}
+        }
+
+        // fallback: assign or exprstmt
+        let out = self.parse_simple_stmts()?;
+        self.expect(Tok::Semi)?;
+        Ok(out)
+    }
+
+    // { (case C: | default:) stmt* ... }
+    // A bad label is reported and skipped, and the statements under it are parsed
+    // for their own errors but dropped, so recovery stays inside the switch.
+    fn parse_switch_body(&mut self) -> Result<Vec<s::SwitchCase>, ParseError> {
+        self.expect(Tok::LBrace)?;
+        let depth = self.vars.len();
+        let outer = std::mem::replace(&mut self.scope_start, depth);
+        let mut cases: Vec<s::SwitchCase> = Vec::new();
+        // statements are appended to the last case unless its label was bad
+        let mut bad_label = false;
+        while !self.peek_is(&Tok::RBrace) && !self.is_eof() {
+            match self.peek() {
+                Tok::Case | Tok::Default => match self.parse_case_label(&cases) {
+                    Ok(label) => {
+                        cases.push(s::SwitchCase { label, body: Vec::new() });
+                        bad_label = false;
+                    }
+                    Err(e) => {
+                        self.record(e);
+                        self.skip_case_label();
+                        bad_label = true;
+                    }
+                },
+                _ => {
+                    let span = self.span();
+                    let mut part = self.parse_stmt_recovering();
+                    match cases.last_mut() {
+                        _ if bad_label => {}
+                        Some(arm) => {
+                            self.check_reachable(arm.body.last(), &part, span);
+                            arm.body.append(&mut part);
+                        }
+                        None => {
+                            let msg = "statement in switch before any case label".to_string();
+                            let kind = ParseErrorKind::Other(msg);
+                            self.record(ParseError { kind, line: span.line, col: span.col });
+                            bad_label = true;
+                        }
+                    }
+                }
+            }
+        }
+        self.scope_start = outer;
+        self.vars.truncate(depth);
+        self.expect(Tok::RBrace)?;
+        Ok(cases)
+    }
+
+    // (case C | default) ':' -- the label's value, `None` for default
+    fn parse_case_label(&mut self, cases: &[s::SwitchCase]) -> Result<Option<i128>, ParseError> {
+        let label = if self.bump() == Tok::Case {
+            let label_span = self.prev_span();
+            let value = self.parse_const_int("case label")?;
+            if cases.iter().any(|c| c.label == Some(value)) {
+                return self.err_at(label_span, format!("duplicate case value {}", value));
+            }
+            Some(value)
+        } else {
+            let label_span = self.prev_span();
+            if cases.iter().any(|c| c.label.is_none()) {
+                return self.err_at(label_span, "multiple default labels in one switch");
+            }
+            None
+        };
+        self.expect(Tok::Colon)?;
+        Ok(label)
+    }
+
+    // skips the rest of a bad label through its ':', stopping before anything that
+    // starts or ends a statement
+    fn skip_case_label(&mut self) {
+        self.hoisted.clear();
+        loop {
+            match self.peek() {
+                Tok::Colon => {
+                    self.bump();
+                    return;
+                }
+                Tok::Semi | Tok::LBrace | Tok::RBrace | Tok::Case | Tok::Default | Tok::Eof => return,
+                _ => {
+                    self.bump();
+                }
+            }
+        }
+    }
+
+    // simple (',' simple)*  -- a statement-level comma just sequences its operands
+    fn parse_simple_stmts(&mut self) -> Result<Vec<s::Stmt>, ParseError> {
+        let mut out = Vec::new();
+        loop {
+            let st = self.parse_simple_stmt()?;
+            out.append(&mut self.hoisted);
+            out.push(st);
+            if !self.peek_is(&Tok::Comma) {
+                return Ok(out);
+            }
+            self.bump();
+        }
+    }
+
+    // assign | compound assign | expr, without the trailing ';'
+    fn parse_simple_stmt(&mut self) -> Result<s::Stmt, ParseError> {
+        let target_span = self.span();
+        let target = self.parse_ternary()?;
+        let Some(op) = self.assign_op() else { return self.expr_stmt(target, target_span) };
+        self.bump();
+        let rhs_span = self.span();
+        let rhs = self.parse_expr()?;
+        self.assignment(target, target_span, op, (rhs, rhs_span))
+    }
+
+    // a variable declared `const`, a member of one, or what a pointer to const
+    // points at can't be assigned or incremented
+    fn check_writable(&self, target: &s::Expr, span: Span) -> Result<(), ParseError> {
+        let Some((name, derefs)) = self.lvalue_base(target) else { return Ok(()) };
+        let Some(b) = self.vars.iter().rev().find(|b| b.lowered == name) else { return Ok(()) };
+        match b.const_level {
+            Some(level) if level as isize == derefs && has_pointer(&b.ty) => {
+                self.err_at(span, format!("cannot assign to a const object through `{}`", b.name))
+            }
+            Some(level) if level as isize == derefs => {
+                self.err_at(span, format!("cannot assign to const variable `{}`", b.name))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // The variable an lvalue is reached from, and through how many dereferences:
+    // `p->x` and `p[1]` are one away from `p`. Casts and calls end the search.
+    fn lvalue_base<'e>(&self, e: &'e s::Expr) -> Option<(&'e str, isize)> {
+        match e {
+            s::Expr::Var(name) => Some((name, 0)),
+            s::Expr::Member { base, .. } => self.lvalue_base(base),
+            s::Expr::Unary { op: s::UnOpRef::Deref, expr } => {
+                self.lvalue_base(expr).map(|(name, n)| (name, n + 1))
+            }
+            s::Expr::Unary { op: s::UnOpRef::AddrOf, expr } => {
+                self.lvalue_base(expr).map(|(name, n)| (name, n - 1))
+            }
+            // pointer arithmetic: follow the pointer operand
+            s::Expr::Binary { left, op: s::BinOpRef::Add | s::BinOpRef::Sub, right } => {
+                let is_ptr = |e| {
+                    matches!(self.type_of(e), Some(s::TypeRef::Pointer(_) | s::TypeRef::Array { .. }))
+                };
+                if is_ptr(left) {
+                    self.lvalue_base(left)
+                } else if is_ptr(right) {
+                    self.lvalue_base(right)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // `Some(None)` for `=`, `Some(Some(op))` for `op=`
+    fn assign_op(&self) -> Option<Option<s::BinOpRef>> {
+        match self.peek() {
+            Tok::Assign => Some(None),
+            t => Self::compound_op(t).map(Some),
+        }
+    }
+
+    fn assignment(
+        &mut self,
+        target: s::Expr,
+        target_span: Span,
+        op: Option<s::BinOpRef>,
+        rhs: (s::Expr, Span),
+    ) -> Result<s::Stmt, ParseError> {
+        let (rhs, rhs_span) = rhs;
+        self.check_writable(&target, target_span)?;
+        // x op= e  ==>  x = (T)(x op e), T being the type of x; the target is read
+        // and written, so one with side effects is only addressed once
+        let target = match op {
+            Some(_) if !dce::is_pure(&target) => self.address_once(target, target_span)?,
+            _ => target,
+        };
+        let value = match op {
+            Some(op) => {
+                let value = self.binary(target.clone(), op, rhs);
+                self.check_divisor(&value, rhs_span)?;
+                self.check_shift_amount(&value, rhs_span)?;
+                match (self.type_of(&target), self.type_of(&value)) {
+                    (Some(to), Some(from)) if is_arith(&to) && !same_arith(&to, &from) => {
+                        self.cast(to, value)
+                    }
+                    _ => value,
+                }
+            }
+            None => {
+                match self.type_of(&target) {
+                    Some(ty) => {
+                        self.check_narrowing(&ty, &rhs, rhs_span);
+                        self.convert_to(&ty, rhs)
+                    }
+                    None => rhs,
+                }
+            }
+        };
+        match target {
+            s::Expr::Var(name) => Ok(s::Stmt::Assign { name, value }),
+            s::Expr::Unary { op: s::UnOpRef::Deref, .. } | s::Expr::Member { .. } => {
+                Ok(s::Stmt::Store { target, value })
+            }
+            _ => self.err_at(target_span, "expression is not assignable"),
+        }
+    }
+
+    // `*tmp` in place of an lvalue with side effects, with `T *tmp = &target;` hoisted
+    fn address_once(&mut self, target: s::Expr, span: Span) -> Result<s::Expr, ParseError> {
+        let (s::Expr::Unary { op: s::UnOpRef::Deref, .. } | s::Expr::Member { .. }) = target else {
+            return self.err_at(span, "expression is not assignable");
+        };
+        let Some(ty) = self.type_of(&target) else {
+            return self.err_at(span, "cannot determine the type of the assignment target");
+        };
+        let ty = s::TypeRef::Pointer(Box::new(ty));
+        // the '.' keeps it apart from every source name
+        let name = format!("addr.tmp.{}", self.fn_locals.len());
+        let tmp = self.declare_local(&name, "addr.tmp".to_string(), &ty, false, span)?;
+        let init = s::Expr::Unary { op: s::UnOpRef::AddrOf, expr: Box::new(target) };
+        self.hoisted.push(s::Stmt::VarDecl { name: tmp.clone(), ty, init: Some(init) });
+        Ok(s::Expr::Unary { op: s::UnOpRef::Deref, expr: Box::new(s::Expr::Var(tmp)) })
+    }
+
+    // Each `continue` of a desugared `for`/`do` replaced by `with`, setting `replaced`:
+    // a `for` runs its step first, a `do` jumps to its test. The `continue`s in `if`
+    // and `switch` bodies belong to the loop too; nested loops own theirs, so those
+    // aren't descended into.
+    fn replace_continue(body: Vec<s::Stmt>, with: &[s::Stmt], replaced: &mut bool) -> Vec<s::Stmt> {
+        let mut out = Vec::with_capacity(body.len());
+        for st in body {
+            match st {
+                s::Stmt::Continue => {
+                    out.extend(with.iter().cloned());
+                    *replaced = true;
+                }
+                s::Stmt::If { cond, then_body, else_body } => out.push(s::Stmt::If {
+                    cond,
+                    then_body: Self::replace_continue(then_body, with, replaced),
+                    else_body: Self::replace_continue(else_body, with, replaced),
+                }),
+                s::Stmt::Switch { value, cases } => out.push(s::Stmt::Switch {
+                    value,
+                    cases: cases
+                        .into_iter()
+                        .map(|c| s::SwitchCase {
+                            label: c.label,
+                            body: Self::replace_continue(c.body, with, replaced),
+                        })
+                        .collect(),
+                }),
+                st => out.push(st),
+            }
+        }
+        out
+    }
+
+    // a bare `x++;` / `--x;` only needs the store, not the value: it is `x += 1;`,
+    // converted like any compound assignment
+    fn expr_stmt(&mut self, e: s::Expr, span: Span) -> Result<s::Stmt, ParseError> {
+        match e {
+            s::Expr::IncDec { name, op } => {
+                let op = match op {
+                    s::IncDecOpRef::PreInc | s::IncDecOpRef::PostInc => s::BinOpRef::Add,
+                    s::IncDecOpRef::PreDec | s::IncDecOpRef::PostDec => s::BinOpRef::Sub,
+                };
+                self.assignment(s::Expr::Var(name), span, Some(op), (Self::lit_i32(1), span))
+            }
+            e => Ok(s::Stmt::ExprStmt(e)),
+        }
+    }
+
+    // comma := expr (',' expr)*  -- only where a bare ',' can't be a separator
+    fn parse_comma(&mut self) -> Result<s::Expr, ParseError> {
+        let mut e = self.parse_expr()?;
+        while self.peek_is(&Tok::Comma) {
+            let span = self.span();
+            self.bump();
+            let mark = self.hoisted.len();
+            let right = self.parse_expr()?;
+            self.check_unhoisted(mark, span, "the right operand of `,`")?;
+            e = s::Expr::Comma { left: Box::new(e), right: Box::new(right) };
+        }
+        Ok(e)
+    }
+
+    // expr := assign
+    fn parse_expr(&mut self) -> Result<s::Expr, ParseError> {
+        self.parse_assign()
+    }
+
+    // assign := ternary (('=' | op'=') assign)?
+    // The frontend has no assignment expression, so one used as a value becomes a
+    // statement run just before the statement containing it, and its target is
+    // read back in its place. Only `&&`, `||`, `?:` and `,` sequence their
+    // operands, so everywhere else running it first is an allowed order; the first
+    // three move their test out along with it (see `branch_once`).
+    fn parse_assign(&mut self) -> Result<s::Expr, ParseError> {
+        let span = self.span();
+        let target = self.parse_ternary()?;
+        let Some(op) = self.assign_op() else { return Ok(target) };
+        self.bump();
+        let rhs_span = self.span();
+        let rhs = self.parse_assign()?;
+        if !dce::is_pure(&target) {
+            let msg = "an assignment whose target has side effects can't be used as a value";
+            return self.err_at(span, msg);
+        }
+        let st = self.assignment(target.clone(), span, op, (rhs, rhs_span))?;
+        self.hoisted.push(st);
+        Ok(target)
+    }
+
+    // `tmp` in place of a conditional whose skipped operand assigns: the assignment can't
+    // be hoisted past the test that skips it, so the test is hoisted too, as
+    // `T tmp; if (cond) { then; tmp = a; } else { else; tmp = b; }`
+    fn branch_once(
+        &mut self,
+        cond: s::Expr,
+        ty: s::TypeRef,
+        arms: [(Vec<s::Stmt>, s::Expr); 2],
+        span: Span,
+    ) -> Result<s::Expr, ParseError> {
+        // the '.' keeps it apart from every source name
+        let name = format!("cond.tmp.{}", self.fn_locals.len());
+        let tmp = self.declare_local(&name, "cond.tmp".to_string(), &ty, false, span)?;
+        let [then_body, else_body] = arms.map(|(mut body, value)| {
+            body.push(s::Stmt::Assign { name: tmp.clone(), value: self.convert_to(&ty, value) });
+            body
+        });
+        self.hoisted.push(s::Stmt::VarDecl { name: tmp.clone(), ty, init: None });
+        self.hoisted.push(s::Stmt::If { cond, then_body, else_body });
+        Ok(s::Expr::Var(tmp))
+    }
+
+    // an operand that may not run can't contain an assignment, which would be hoisted out of it
+    fn check_unhoisted(&self, mark: usize, span: Span, what: &str) -> Result<(), ParseError> {
+        if self.hoisted.len() > mark {
+            return self.err_at(span, format!("assignment inside {} is not supported", what));
+        }
+        Ok(())
+    }
+
+    // `while (cond) body`, with the assignments hoisted out of `cond` rerun before each test:
+    // while (true) { pre; if (!cond) break; body }
+    fn while_loop(&self, pre: Vec<s::Stmt>, cond: s::Expr, body: Vec<s::Stmt>) -> s::Stmt {
+        if pre.is_empty() {
+            return s::Stmt::While { cond, body };
+        }
+        let mut looped = pre;
+        looped.push(s::Stmt::If {
+            cond: self.logical_not(cond),
+            then_body: vec![s::Stmt::Break],
+            else_body: Vec::new(),
+        });
+        looped.extend(body);
+        s::Stmt::While { cond: s::Expr::Lit(s::Lit::Bool(true)), body: looped }
+    }
+
+    // ternary := or ('?' expr ':' ternary)?
+    fn parse_ternary(&mut self) -> Result<s::Expr, ParseError> {
+        let cond = self.parse_logical_or()?;
+        if !self.peek_is(&Tok::Question) {
+            return Ok(cond);
+        }
+        let span = self.span();
+        self.bump();
+        let mark = self.hoisted.len();
+        let then_expr = self.parse_comma()?;
+        self.expect(Tok::Colon)?;
+        let then_pre = self.hoisted.split_off(mark);
+        let else_expr = self.parse_ternary()?;
+        let else_pre = self.hoisted.split_off(mark);
+        let e = s::Expr::Ternary {
+            cond: Box::new(self.ensure_bool(cond)),
+            then_expr: Box::new(then_expr),
+            else_expr: Box::new(else_expr),
+        };
+        if then_pre.is_empty() && else_pre.is_empty() {
+            return Ok(e);
+        }
+        let ty = self.type_of(&e);
+        let s::Expr::Ternary { cond, then_expr, else_expr } = e else { unreachable!() };
+        match ty {
+            Some(ty) if !matches!(ty, s::TypeRef::Void) => {
+                self.branch_once(*cond, ty, [(then_pre, *then_expr), (else_pre, *else_expr)], span)
+            }
+            // nothing to hold the value in
+            _ => {
+                let msg = "assignment inside a `?:` arm of unknown or void type is not supported";
+                self.err_at(span, msg)
+            }
+        }
+    }
+
+    // or := and ('||' and)*
+    fn parse_logical_or(&mut self) -> Result<s::Expr, ParseError> {
+        let mut e = self.parse_logical_and()?;
+        while self.peek_is(&Tok::OrOr) {
+            let span = self.span();
+            self.bump();
+            let mark = self.hoisted.len();
+            let r = self.parse_logical_and()?;
+            let (l, r) = (self.ensure_bool(e), self.ensure_bool(r));
+            if self.hoisted.len() > mark {
+                let pre = self.hoisted.split_off(mark);
+                let int = s::TypeRef::Int { bits: self.target.int_bits, signed: true };
+                let arms = [(Vec::new(), s::Expr::Lit(s::Lit::Bool(true))), (pre, r)];
+                e = self.branch_once(l, int, arms, span)?;
+                continue;
+            }
+            e = s::Expr::Logical { left: Box::new(l), op: s::LogicOpRef::Or, right: Box::new(r) };
+        }
+        Ok(e)
+    }
+
+    // and := bitor ('&&' bitor)*
+    fn parse_logical_and(&mut self) -> Result<s::Expr, ParseError> {
+        let mut e = self.parse_bitor()?;
+        while self.peek_is(&Tok::AndAnd) {
+            let span = self.span();
+            self.bump();
+            let mark = self.hoisted.len();
+            let r = self.parse_bitor()?;
+            let (l, r) = (self.ensure_bool(e), self.ensure_bool(r));
+            if self.hoisted.len() > mark {
+                let pre = self.hoisted.split_off(mark);
+                let int = s::TypeRef::Int { bits: self.target.int_bits, signed: true };
+                let arms = [(pre, r), (Vec::new(), s::Expr::Lit(s::Lit::Bool(false)))];
+                e = self.branch_once(l, int, arms, span)?;
+                continue;
+            }
+            e = s::Expr::Logical { left: Box::new(l), op: s::LogicOpRef::And, right: Box::new(r) };
+        }
+        Ok(e)
+    }
+
+    // bitor := bitxor ('|' bitxor)*
+    fn parse_bitor(&mut self) -> Result<s::Expr, ParseError> {
+        let mut e = self.parse_bitxor()?;
+        while self.peek_is(&Tok::Pipe) {
+            self.bump();
+            let r = self.parse_bitxor()?;
+            e = self.binary(e, s::BinOpRef::BitOr, r);
+        }
+        Ok(e)
+    }
+
+    // bitxor := bitand ('^' bitand)*
+    fn parse_bitxor(&mut self) -> Result<s::Expr, ParseError> {
+        let mut e = self.parse_bitand()?;
+        while self.peek_is(&Tok::Caret) {
+            self.bump();
+            let r = self.parse_bitand()?;
+            e = self.binary(e, s::BinOpRef::BitXor, r);
+        }
+        Ok(e)
+    }
+
+    // bitand := cmp ('&' cmp)*
+    fn parse_bitand(&mut self) -> Result<s::Expr, ParseError> {
+        let mut e = self.parse_cmp()?;
+        while self.peek_is(&Tok::Amp) {
+            self.bump();
+            let r = self.parse_cmp()?;
+            e = self.binary(e, s::BinOpRef::BitAnd, r);
+        }
+        Ok(e)
+    }
+
+    // cmp := relational (('=='|'!=') relational)*
+    fn parse_cmp(&mut self) -> Result<s::Expr, ParseError> {
+        let mut e = self.parse_relational()?;
+        loop {
+            let op = match self.peek() {
+                Tok::EqEq => s::CmpOpRef::Eq,
+                Tok::NotEq => s::CmpOpRef::Ne,
+                _ => break,
+            };
+            self.bump();
+            let r = self.parse_relational()?;
+            e = self.compare(e, op, r);
+        }
+        Ok(e)
+    }
+
+    // relational := shift (('<'|'<='|'>'|'>=') shift)*
+    // `a < b < c` is `(a < b) < c`, comparing the 0 or 1 on the left with `c`
+    fn parse_relational(&mut self) -> Result<s::Expr, ParseError> {
+        let mut e = self.parse_shift()?;
+        loop {
+            let op = match self.peek() {
+                Tok::Lt => s::CmpOpRef::Lt,
+                Tok::Le => s::CmpOpRef::Le,
+                Tok::Gt => s::CmpOpRef::Gt,
+                Tok::Ge => s::CmpOpRef::Ge,
+                _ => break,
+            };
+            self.bump();
+            let r = self.parse_shift()?;
+            e = self.compare(e, op, r);
+        }
+        Ok(e)
+    }
+
+    fn compare(&self, left: s::Expr, op: s::CmpOpRef, right: s::Expr) -> s::Expr {
+        // both sides end up with one type; lowering reads its signedness to choose
+        // between the signed and unsigned predicate, so `-1 < 1u` is false as in C.
+        // A comparison operand is an `int`, like any other comparison result.
+        let (left, right) = self.convert_operands(left, right);
+        s::Expr::Cmp { left: Box::new(left), op, right: Box::new(right) }
+    }
+
+    // shift := add (('<<'|'>>') add)*
+    fn parse_shift(&mut self) -> Result<s::Expr, ParseError> {
+        let mut e = self.parse_add()?;
+        loop {
+            let op = match self.peek() {
+                Tok::Shl => Some(s::BinOpRef::Shl),
+                Tok::Shr => Some(s::BinOpRef::Shr),
+                _ => None,
+            };
+            let Some(op) = op else { break; };
+            self.bump();
+            let op_span = self.prev_span();
+            let r = self.parse_add()?;
+            e = self.binary(e, op, r);
+            self.check_shift_amount(&e, op_span)?;
+        }
+        Ok(e)
+    }
+
+    // add := mul (('+'|'-') mul)*
+    fn parse_add(&mut self) -> Result<s::Expr, ParseError> {
+        let mut e = self.parse_mul()?;
+        loop {
+            let op = match self.peek() {
+                Tok::Plus => Some(s::BinOpRef::Add),
+                Tok::Minus => Some(s::BinOpRef::Sub),
+                _ => None,
+            };
+            let Some(op) = op else { break; };
+            self.bump();
+            let r = self.parse_mul()?;
+            e = self.binary(e, op, r);
+        }
+        Ok(e)
+    }
+
+    // mul := unary (('*'|'/'|'%') unary)*
+    fn parse_mul(&mut self) -> Result<s::Expr, ParseError> {
+        let mut e = self.parse_unary()?;
+        loop {
+            let op = match self.peek() {
+                Tok::Star => Some(s::BinOpRef::Mul),
+                Tok::Slash => Some(s::BinOpRef::Div),
+                Tok::Percent => Some(s::BinOpRef::Rem),
+                _ => None,
+            };
+            let Some(op) = op else { break; };
+            self.bump();
+            let op_span = self.prev_span();
+            let r = self.parse_unary()?;
+            e = self.binary(e, op, r);
+            self.check_divisor(&e, op_span)?;
+        }
+        Ok(e)
+    }
+
+    // integer `/` or `%` by a constant zero is undefined, so it's rejected rather than
+    // left to trap at run time; floating-point division by zero is well defined
+    fn check_divisor(&self, e: &s::Expr, span: Span) -> Result<(), ParseError> {
+        let s::Expr::Binary { op: s::BinOpRef::Div | s::BinOpRef::Rem, right, .. } = e else {
+            return Ok(());
+        };
+        if !matches!(self.type_of(right), Some(s::TypeRef::Int { .. })) {
+            return Ok(());
+        }
+        match fold::const_eval(right, &self.consts, &self.target) {
+            Ok(0) => self.err_at(span, "division by zero"),
+            _ => Ok(()),
+        }
+    }
+
+    // a constant shift amount outside [0, width) is undefined. The width is that of
+    // the promoted left operand, since `char` and `short` shift as `int`.
+    fn check_shift_amount(&self, e: &s::Expr, span: Span) -> Result<(), ParseError> {
+        let s::Expr::Binary { left, op: s::BinOpRef::Shl | s::BinOpRef::Shr, right } = e else {
+            return Ok(());
+        };
+        let Some(s::TypeRef::Int { bits, .. }) = self.type_of(left) else { return Ok(()) };
+        match fold::const_eval(right, &self.consts, &self.target) {
+            Ok(n) if n < 0 || n >= bits as i128 => {
+                let msg = format!("shift amount {} is out of range for a {}-bit operand", n, bits);
+                self.err_at(span, msg)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // !e  ==>  e == 0, with literals and integer comparisons inverted in place.
+    // A floating-point comparison is not: `!(a < b)` is true when either is NaN.
+    fn logical_not(&self, e: s::Expr) -> s::Expr {
+        let int_operands = |l: &s::Expr| {
+            matches!(
+                self.type_of(l),
+                Some(s::TypeRef::Int { .. } | s::TypeRef::Pointer(_) | s::TypeRef::Array { .. })
+            )
+        };
+        match e {
+            s::Expr::Cmp { left, op, right } if int_operands(&left) => {
+                let op = match op {
+                    s::CmpOpRef::Eq => s::CmpOpRef::Ne,
+                    s::CmpOpRef::Ne => s::CmpOpRef::Eq,
+                    s::CmpOpRef::Lt => s::CmpOpRef::Ge,
+                    s::CmpOpRef::Le => s::CmpOpRef::Gt,
+                    s::CmpOpRef::Gt => s::CmpOpRef::Le,
+                    s::CmpOpRef::Ge => s::CmpOpRef::Lt,
+                };
+                s::Expr::Cmp { left, op, right }
+            }
+            // De Morgan keeps the short-circuit order intact
+            s::Expr::Logical { left, op, right } => {
+                let op = match op {
+                    s::LogicOpRef::And => s::LogicOpRef::Or,
+                    s::LogicOpRef::Or => s::LogicOpRef::And,
+                };
+                s::Expr::Logical {
+                    left: Box::new(self.logical_not(*left)),
+                    op,
+                    right: Box::new(self.logical_not(*right)),
+                }
+            }
+            s::Expr::Lit(s::Lit::Bool(b)) => s::Expr::Lit(s::Lit::Bool(!b)),
+            s::Expr::Lit(s::Lit::Int { value, .. }) => s::Expr::Lit(s::Lit::Bool(value == 0)),
+            s::Expr::Lit(s::Lit::Float { value, .. }) => s::Expr::Lit(s::Lit::Bool(value == 0.0)),
+            e @ s::Expr::Cmp { .. } => s::Expr::Cmp {
+                left: Box::new(e),
+                op: s::CmpOpRef::Eq,
+                right: Box::new(s::Expr::Lit(s::Lit::Bool(false))),
+            },
+            e => {
+                let zero = self.zero_of(&e);
+                s::Expr::Cmp { left: Box::new(e), op: s::CmpOpRef::Eq, right: Box::new(zero) }
+            }
+        }
+    }
+
+    // unary := ('-'|'+'|'!'|'~'|'&'|'*'|'++'|'--') unary | '(' type ')' unary | sizeof | postfix
+    fn parse_unary(&mut self) -> Result<s::Expr, ParseError> {
+        match self.peek() {
+            Tok::Sizeof => self.parse_sizeof(),
+            Tok::LParen => {
+                let save = self.i;
+                self.bump();
+                if !self.is_type_name() {
+                    self.i = save;
+                    return self.parse_postfix();
+                }
+                if self.peek_is(&Tok::Const) {
+                    self.bump();
+                }
+                let ty = self.parse_type()?;
+                self.expect(Tok::RParen)?;
+                let e = self.parse_unary()?;
+                if is_bool(&ty) {
+                    return Ok(self.convert_to(&ty, e));
+                }
+                Ok(self.cast(ty, e))
+            }
+            Tok::Minus => {
+                self.bump();
+                let e = self.parse_unary()?;
+                // `char` and `short` operands are negated as `int`, like binary operands
+                Ok(match self.promote_operand(e) {
+                    s::Expr::Lit(s::Lit::Int { bits, signed: true, value }) => {
+                        s::Expr::Lit(s::Lit::Int { bits, signed: true, value: -value })
+                    }
+                    s::Expr::Lit(s::Lit::Float { bits, value }) => {
+                        s::Expr::Lit(s::Lit::Float { bits, value: -value })
+                    }
+                    e => s::Expr::Unary { op: s::UnOpRef::Neg, expr: Box::new(e) },
+                })
+            }
+            Tok::Plus => {
+                self.bump();
+                self.parse_unary()
+            }
+            Tok::Bang => {
+                self.bump();
+                let e = self.parse_unary()?;
+                Ok(self.logical_not(e))
+            }
+            Tok::Tilde => {
+                self.bump();
+                let e = self.parse_unary()?;
+                let e = self.promote_operand(e);
+                Ok(s::Expr::Unary { op: s::UnOpRef::BitNot, expr: Box::new(e) })
+            }
+            Tok::Amp => {
+                self.bump();
+                let op_span = self.prev_span();
+                match self.parse_unary()? {
+                    e @ (s::Expr::Var(_)
+                    | s::Expr::Unary { op: s::UnOpRef::Deref, .. }
+                    | s::Expr::Member { .. }) => {
+                        Ok(s::Expr::Unary { op: s::UnOpRef::AddrOf, expr: Box::new(e) })
+                    }
+                    _ => self.err_at(op_span, "cannot take the address of an rvalue"),
+                }
+            }
+            Tok::Star => {
+                self.bump();
+                let op_span = self.prev_span();
+                let e = self.parse_unary()?;
+                self.deref(e, op_span)
+            }
+            Tok::PlusPlus | Tok::MinusMinus => {
+                let op = if self.bump() == Tok::PlusPlus {
+                    s::IncDecOpRef::PreInc
+                } else {
+                    s::IncDecOpRef::PreDec
+                };
+                let op_span = self.prev_span();
+                match self.parse_unary()? {
+                    s::Expr::Var(name) => {
+                        self.check_writable(&s::Expr::Var(name.clone()), op_span)?;
+                        Ok(s::Expr::IncDec { name, op })
+                    }
+                    _ => self.err_at(op_span, "operand of increment/decrement must be a variable"),
+                }
+            }
+            _ => self.parse_postfix(),
+        }
+    }
+
+    // literals are converted in place so casts stay usable in constant expressions
+    // `l op r` with the implicit conversions C applies to the operands made explicit
+    fn binary(&self, l: s::Expr, op: s::BinOpRef, r: s::Expr) -> s::Expr {
+        let (l, r) = if matches!(op, s::BinOpRef::Shl | s::BinOpRef::Shr) {
+            // shift operands are promoted separately; the result has the left one's type
+            (self.promote_operand(l), self.promote_operand(r))
+        } else {
+            self.convert_operands(l, r)
+        };
+        s::Expr::Binary { left: Box::new(l), op, right: Box::new(r) }
+    }
+
+    // Converts two arithmetic operands to their common type (C's usual arithmetic
+    // conversions). Pointer operands, and ones whose type isn't known here, are
+    // left as they are.
+    fn convert_operands(&self, l: s::Expr, r: s::Expr) -> (s::Expr, s::Expr) {
+        match (self.type_of(&l), self.type_of(&r)) {
+            (Some(lt), Some(rt)) if is_arith(&lt) && is_arith(&rt) => {
+                let ty = self.target.arith_type(lt.clone(), rt.clone());
+                (self.convert(l, &lt, ty.clone()), self.convert(r, &rt, ty))
+            }
+            _ => (l, r),
+        }
+    }
+
+    fn promote_operand(&self, e: s::Expr) -> s::Expr {
+        match self.type_of(&e) {
+            Some(ty) if is_arith(&ty) => {
+                let to = self.target.promote(ty.clone());
+                self.convert(e, &ty, to)
+            }
+            _ => e,
+        }
+    }
+
+    // `e` stored into an object of type `ty`. Lowering converts between numeric
+    // types itself, but not to `_Bool`, which needs a comparison rather than truncation.
+    fn convert_to(&self, ty: &s::TypeRef, e: s::Expr) -> s::Expr {
+        match self.type_of(&e) {
+            Some(from) if is_bool(ty) && !is_bool(&from) => self.cast(ty.clone(), e),
+            _ => e,
+        }
+    }
+
+    // `e`, of arithmetic type `from`, as a `to`
+    fn convert(&self, e: s::Expr, from: &s::TypeRef, to: s::TypeRef) -> s::Expr {
+        if same_arith(from, &to) {
+            e
+        } else {
+            self.cast(to, e)
+        }
+    }
+
+    // `_Bool` has no truncating conversion: its value is whether `e` is nonzero
+    fn cast(&self, ty: s::TypeRef, e: s::Expr) -> s::Expr {
+        let e = if is_bool(&ty) && !matches!(e, s::Expr::Lit(_)) { self.nonzero(e) } else { e };
+        match e {
+            s::Expr::Lit(lit) => match fold::cast_lit(&ty, &lit) {
+                Some(lit) => s::Expr::Lit(lit),
+                None => s::Expr::Cast { ty, expr: Box::new(s::Expr::Lit(lit)) },
+            },
+            e => s::Expr::Cast { ty, expr: Box::new(e) },
+        }
+    }
+
+    // sizeof '(' type ')' | sizeof unary  -- folded to a size_t constant; the operand is never evaluated
+    fn parse_sizeof(&mut self) -> Result<s::Expr, ParseError> {
+        self.expect(Tok::Sizeof)?;
+        let span = self.prev_span();
+        let save = self.i;
+        if self.peek_is(&Tok::LParen) {
+            self.bump();
+        }
+        let ty = if self.i > save && self.is_type_name() {
+            if self.peek_is(&Tok::Const) {
+                self.bump();
+            }
+            let ty = self.parse_type()?;
+            let ty = self.parse_array_dims(ty)?;
+            self.expect(Tok::RParen)?;
+            ty
+        } else {
+            self.i = save;
+            // the operand is never evaluated, so nothing it would hoist may run either
+            let mark = self.hoisted.len();
+            let e = self.parse_unary()?;
+            self.hoisted.truncate(mark);
+            match self.type_of(&e) {
+                Some(ty) => ty,
+                None => return self.err_at(span, "cannot determine the type of the `sizeof` operand"),
+            }
+        };
+
+        let Some((size, _)) = self.target.size_align(&ty, &self.structs) else {
+            return self.err_at(span, "invalid application of `sizeof` to an incomplete type");
+        };
+        let bits = self.target.ptr_bits;
+        Ok(s::Expr::Lit(s::Lit::Int { bits, signed: false, value: size as i128 }))
+    }
+
+    // static type of an expression, as far as the parser can tell without lowering
+    fn type_of(&self, e: &s::Expr) -> Option<s::TypeRef> {
+        let int = s::TypeRef::Int { bits: self.target.int_bits, signed: true };
+        match e {
+            s::Expr::Lit(s::Lit::Int { bits, signed, .. }) => {
+                Some(s::TypeRef::Int { bits: *bits, signed: *signed })
+            }
+            s::Expr::Lit(s::Lit::Float { bits, .. }) => Some(s::TypeRef::Float { bits: *bits }),
+            s::Expr::Lit(s::Lit::Bool(_)) | s::Expr::Cmp { .. } | s::Expr::Logical { .. } => Some(int),
+            s::Expr::Var(name) | s::Expr::IncDec { name, .. } => {
+                self.vars.iter().rev().find(|b| b.lowered == *name).map(|b| b.ty.clone())
+            }
+            s::Expr::Unary { op, expr } => {
+                let ty = self.type_of(expr)?;
+                match op {
+                    s::UnOpRef::AddrOf => Some(s::TypeRef::Pointer(Box::new(ty))),
+                    s::UnOpRef::Deref => match ty {
+                        s::TypeRef::Pointer(elem) | s::TypeRef::Array { elem, .. } => Some(*elem),
+                        _ => None,
+                    },
+                    s::UnOpRef::Neg | s::UnOpRef::BitNot => Some(self.target.promote(ty)),
+                }
+            }
+            s::Expr::Binary { left, op, right } => {
+                let (l, r) = (self.type_of(left)?, self.type_of(right)?);
+                let decay = |ty: s::TypeRef| match ty {
+                    s::TypeRef::Array { elem, .. } => s::TypeRef::Pointer(elem),
+                    ty => ty,
+                };
+                match (decay(l), decay(r)) {
+                    (s::TypeRef::Pointer(_), s::TypeRef::Pointer(_)) if *op == s::BinOpRef::Sub => {
+                        Some(s::TypeRef::Int { bits: self.target.ptr_bits, signed: true })
+                    }
+                    (p @ s::TypeRef::Pointer(_), _) | (_, p @ s::TypeRef::Pointer(_)) => Some(p),
+                    (l, _) if matches!(op, s::BinOpRef::Shl | s::BinOpRef::Shr) => {
+                        Some(self.target.promote(l))
+                    }
+                    (l, r) => Some(self.target.arith_type(l, r)),
+                }
+            }
+            s::Expr::Member { base, field } => {
+                let s::TypeRef::Struct(name) = self.type_of(base)? else {
+                    return None;
+                };
+                let def = self.structs.iter().find(|d| d.name == name)?;
+                def.fields.iter().find(|f| f.name == *field).map(|f| f.ty.clone())
+            }
+            s::Expr::Ternary { then_expr, else_expr, .. } => {
+                let (l, r) = (self.type_of(then_expr)?, self.type_of(else_expr)?);
+                if is_arith(&l) && is_arith(&r) {
+                    Some(self.target.arith_type(l, r))
+                } else {
+                    Some(l)
+                }
+            }
+            s::Expr::Cast { ty, .. } => Some(ty.clone()),
+            s::Expr::Comma { right, .. } => self.type_of(right),
+            s::Expr::Call { .. } => None,
+        }
+    }
+
+    // postfix := primary ( '(' args ')' | '[' expr ']' | '.' ident | '->' ident | '++' | '--' )*
+    fn parse_postfix(&mut self) -> Result<s::Expr, ParseError> {
+        let mut e = self.parse_primary()?;
+        loop {
+            let op = match self.peek() {
+                Tok::LParen => {
+                    let s::Expr::Var(callee) = e else {
+                        return self.err_at(self.span(), "called object is not a function");
+                    };
+                    self.bump();
+                    let args = self.parse_call_args()?;
+                    e = s::Expr::Call { callee, args };
+                    continue;
+                }
+                Tok::LBracket => {
+                    // a[i]  ==>  *(a + i)
+                    self.bump();
+                    let op_span = self.prev_span();
+                    let index = self.parse_expr()?;
+                    self.expect(Tok::RBracket)?;
+                    let addr = s::Expr::Binary {
+                        left: Box::new(e),
+                        op: s::BinOpRef::Add,
+                        right: Box::new(index),
+                    };
+                    e = self.deref(addr, op_span)?;
+                    continue;
+                }
+                Tok::Dot | Tok::Arrow => {
+                    // p->f  ==>  (*p).f
+                    if self.bump() == Tok::Arrow {
+                        e = self.deref(e, self.prev_span())?;
+                    }
+                    let field = self.expect_ident()?;
+                    e = s::Expr::Member { base: Box::new(e), field };
+                    continue;
+                }
+                Tok::PlusPlus => s::IncDecOpRef::PostInc,
+                Tok::MinusMinus => s::IncDecOpRef::PostDec,
+                _ => break,
+            };
+            self.bump();
+            e = match e {
+                s::Expr::Var(name) => {
+                    self.check_writable(&s::Expr::Var(name.clone()), self.prev_span())?;
+                    s::Expr::IncDec { name, op }
+                }
+                _ => {
+                    let msg = "operand of increment/decrement must be a variable";
+                    return self.err_at(self.prev_span(), msg);
+                }
+            };
+        }
+        Ok(e)
+    }
+
+    // args := (expr (',' expr)*)? ')'
+    // `,)`, just consumed up to the `)`
+    fn check_trailing_comma(&self) -> Result<(), ParseError> {
+        if self.opts.trailing_commas {
+            Ok(())
+        } else {
+            self.err_at(self.prev_span(), "trailing comma before `)` (allowed with --std=lenient)")
+        }
+    }
+
+    fn parse_call_args(&mut self) -> Result<Vec<s::Expr>, ParseError> {
+        let mut args = Vec::new();
+        if !self.peek_is(&Tok::RParen) {
+            loop {
+                args.push(self.parse_expr()?);
+                if self.peek_is(&Tok::Comma) {
+                    self.bump();
+                    if !self.peek_is(&Tok::RParen) {
+                        continue;
+                    }
+                    self.check_trailing_comma()?;
+                }
+                break;
+            }
+        }
+        self.expect(Tok::RParen)?;
+        Ok(args)
+    }
+
+    fn parse_primary(&mut self) -> Result<s::Expr, ParseError> {
+        match self.bump() {
+            Tok::IntLit(v, sfx) => Ok(self.lit_int(v, sfx)),
+            Tok::CharLit(v) => Ok(s::Expr::Lit(s::Lit::Int { bits: 8, signed: true, value: v })),
+            Tok::FloatLit(v) => {
+                Ok(s::Expr::Lit(s::Lit::Float { bits: self.target.double_bits, value: v }))
+            }
+            Tok::Ident(name) => {
+                if let Some(b) = self.vars.iter().rev().find(|b| b.name == name) {
+                    return Ok(s::Expr::Var(b.lowered.clone()));
+                }
+                match self.enumerators.iter().find(|(n, _)| *n == name) {
+                    Some(&(_, v)) => {
+                        let bits = self.target.int_bits;
+                        Ok(s::Expr::Lit(s::Lit::Int { bits, signed: true, value: v }))
+                    }
+                    None if self.peek_is(&Tok::LParen) && self.fns.contains(&name) => {
+                        Ok(s::Expr::Var(name))
+                    }
+                    None => {
+                        self.err_at(self.prev_span(), format!("use of undeclared identifier `{}`", name))
+                    }
+                }
+            }
+            Tok::True => Ok(s::Expr::Lit(s::Lit::Bool(true))),
+            Tok::False => Ok(s::Expr::Lit(s::Lit::Bool(false))),
+            Tok::LParen => {
+                let e = self.parse_comma()?;
+                self.expect(Tok::RParen)?;
+                Ok(e)
+            }
+            other => {
+                // put it back: it may be the `;` or `}` that recovery resumes at
+                self.i -= 1;
+                self.unexpected("expression", ParseErrorKind::ExpectedExpr { got: other })
+            }
+        }
+    }
+}
+
+// pointer and array levels above the base type; `const` before the base type
+// qualifies what is left after that many dereferences
+fn indirections(ty: &s::TypeRef) -> usize {
+    match ty {
+        s::TypeRef::Pointer(inner) | s::TypeRef::Array { elem: inner, .. } => 1 + indirections(inner),
+        _ => 0,
+    }
+}
+
+// whether a leading `const` is under a pointer, leaving the object itself writable
+fn has_pointer(ty: &s::TypeRef) -> bool {
+    match ty {
+        s::TypeRef::Pointer(_) => true,
+        s::TypeRef::Array { elem, .. } => has_pointer(elem),
+        _ => false,
+    }
+}
+
+fn is_bool(ty: &s::TypeRef) -> bool {
+    matches!(ty, s::TypeRef::Int { bits: 1, .. })
+}
+
+fn is_arith(ty: &s::TypeRef) -> bool {
+    matches!(ty, s::TypeRef::Int { .. } | s::TypeRef::Float { .. })
+}
+
+fn same_arith(a: &s::TypeRef, b: &s::TypeRef) -> bool {
+    match (a, b) {
+        (s::TypeRef::Int { bits: x, signed: sx }, s::TypeRef::Int { bits: y, signed: sy }) => {
+            x == y && sx == sy
+        }
+        (s::TypeRef::Float { bits: x }, s::TypeRef::Float { bits: y }) => x == y,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the program, or the first error
+    fn parse_with(src: &str, opts: ParseOptions) -> Result<s::Program, ParseError> {
+        parse_translation_unit(src, &Target::X86_64, opts).map(|(p, _)| p).map_err(|mut e| e.remove(0))
+    }
+
+    fn parse(src: &str) -> Result<s::Program, ParseError> {
+        parse_with(src, ParseOptions::LENIENT)
+    }
+
+    fn errors(src: &str) -> Vec<String> {
+        match parse_translation_unit(src, &Target::X86_64, ParseOptions::LENIENT) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    fn warnings(src: &str) -> Vec<String> {
+        let (_, warnings) = parse_translation_unit(src, &Target::X86_64, ParseOptions::LENIENT).unwrap();
+        warnings.iter().map(|w| w.to_string()).collect()
+    }
+
+    /// Compares `--emit=ast` output against an indented expected dump,
+    /// ignoring the indentation the raw string picks up from this file.
+    fn assert_ast(src: &str, want: &str) {
+        let lines: Vec<&str> = want.lines().filter(|l| !l.trim().is_empty()).collect();
+        let indent = lines.iter().map(|l| l.len() - l.trim_start().len()).min().unwrap_or(0);
+        let want: String = lines.iter().map(|l| format!("{}\n", &l[indent..])).collect();
+        assert_eq!(crate::dump::dump_program(&parse(src).unwrap()), want, "{src}");
+    }
+
+    fn returned(src: &str) -> s::Expr {
+        let p = parse(src).unwrap();
+        match p.functions[0].body.last() {
+            Some(s::Stmt::Return(Some(e))) => e.clone(),
+            other => panic!("{src}: {other:?}"),
+        }
+    }
+
+    // a compact prefix form of `e`, e.g. `(+ a (* b 2))`
+    fn sexp(e: &s::Expr) -> String {
+        match e {
+            s::Expr::Lit(s::Lit::Int { value, .. }) => value.to_string(),
+            s::Expr::Lit(s::Lit::Float { value, .. }) => format!("{value:?}"),
+            s::Expr::Lit(s::Lit::Bool(b)) => b.to_string(),
+            s::Expr::Var(name) => name.clone(),
+            s::Expr::Unary { op, expr } => {
+                let op = match op {
+                    s::UnOpRef::Neg => "neg",
+                    s::UnOpRef::BitNot => "~",
+                    s::UnOpRef::AddrOf => "&",
+                    s::UnOpRef::Deref => "*",
+                };
+                format!("({} {})", op, sexp(expr))
+            }
+            s::Expr::Binary { left, op, right } => {
+                let op = match op {
+                    s::BinOpRef::Add => "+",
+                    s::BinOpRef::Sub => "-",
+                    s::BinOpRef::Mul => "*",
+                    s::BinOpRef::Div => "/",
+                    s::BinOpRef::Rem => "%",
+                    s::BinOpRef::BitAnd => "&",
+                    s::BinOpRef::BitOr => "|",
+                    s::BinOpRef::BitXor => "^",
+                    s::BinOpRef::Shl => "<<",
+                    s::BinOpRef::Shr => ">>",
+                };
+                format!("({} {} {})", op, sexp(left), sexp(right))
+            }
+            s::Expr::Cmp { left, op, right } => {
+                let op = match op {
+                    s::CmpOpRef::Eq => "==",
+                    s::CmpOpRef::Ne => "!=",
+                    s::CmpOpRef::Lt => "<",
+                    s::CmpOpRef::Le => "<=",
+                    s::CmpOpRef::Gt => ">",
+                    s::CmpOpRef::Ge => ">=",
+                };
+                format!("({} {} {})", op, sexp(left), sexp(right))
+            }
+            s::Expr::Logical { left, op, right } => {
+                let op = match op {
+                    s::LogicOpRef::And => "&&",
+                    s::LogicOpRef::Or => "||",
+                };
+                format!("({} {} {})", op, sexp(left), sexp(right))
+            }
+            s::Expr::IncDec { name, op } => match op {
+                s::IncDecOpRef::PreInc => format!("++{name}"),
+                s::IncDecOpRef::PreDec => format!("--{name}"),
+                s::IncDecOpRef::PostInc => format!("{name}++"),
+                s::IncDecOpRef::PostDec => format!("{name}--"),
+            },
+            s::Expr::Call { callee, args } => {
+                format!("{callee}({})", args.iter().map(sexp).collect::<Vec<_>>().join(", "))
+            }
+            s::Expr::Comma { left, right } => format!("(, {} {})", sexp(left), sexp(right)),
+            s::Expr::Cast { ty, expr } => format!("({} {})", tyname(ty), sexp(expr)),
+            s::Expr::Member { base, field } => format!("{}.{field}", sexp(base)),
+            s::Expr::Ternary { cond, then_expr, else_expr } => {
+                format!("(? {} {} {})", sexp(cond), sexp(then_expr), sexp(else_expr))
+            }
+        }
+    }
+
+    fn tyname(ty: &s::TypeRef) -> String {
+        match ty {
+            s::TypeRef::Int { bits, signed: true } => format!("i{bits}"),
+            s::TypeRef::Int { bits, signed: false } => format!("u{bits}"),
+            s::TypeRef::Float { bits } => format!("f{bits}"),
+            s::TypeRef::Pointer(elem) => format!("*{}", tyname(elem)),
+            other => format!("{other:?}"),
+        }
+    }
+
+    // `g` and `h` are declared for the calls
+    fn expr(text: &str) -> String {
+        sexp(&returned(&format!("int g(); int h(); int f(int a, int b, int c) {{ return {text}; }}")))
+    }
+
+    fn expr_err(text: &str) -> String {
+        let src = format!("int g(); int h(); int f(int a, int b, int c) {{ return {text}; }}");
+        parse(&src).unwrap_err().kind.to_string()
+    }
+
+    // (bits, signed, value) of an integer literal
+    fn lit(text: &str) -> (u16, bool, i128) {
+        match returned(&format!("int f() {{ return {text}; }}")) {
+            s::Expr::Lit(s::Lit::Int { bits, signed, value }) => (bits, signed, value),
+            other => panic!("{text}: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn literals_are_typed_by_their_suffix() {
+        assert_eq!(lit("42"), (32, true, 42));
+        assert_eq!(lit("42U"), (32, false, 42));
+        assert_eq!(lit("100L"), (64, true, 100));
+        assert_eq!(lit("7ULL"), (64, false, 7));
+    }
+
+    #[test]
+    fn literals_widen_to_fit() {
+        assert_eq!(lit("2147483647"), (32, true, 2147483647));
+        assert_eq!(lit("2147483648"), (64, true, 2147483648));
+        assert_eq!(lit("4294967295U"), (32, false, 4294967295));
+        assert_eq!(lit("4294967296U"), (64, false, 4294967296));
+        assert_eq!(lit("18446744073709551615"), (64, false, u64::MAX as i128));
+    }
+
+    #[test]
+    fn char_literals_are_signed_chars() {
+        assert_eq!(lit("'A'"), (8, true, 65));
+        assert_eq!(lit(r"'\0'"), (8, true, 0));
+        assert_eq!(lit(r"'\xff'"), (8, true, -1));
+    }
+
+    #[test]
+    fn errors_carry_the_location() {
+        let e = parse("int f() {\n  return 1\n}").unwrap_err();
+        assert_eq!((e.line, e.col), (3, 1));
+        assert!(e.to_string().ends_with("(3:1)"), "{e}");
+        let e = parse("int f() { return 0x; }").unwrap_err();
+        assert_eq!((e.line, e.col), (1, 20));
+    }
+
+    #[test]
+    fn division_and_remainder() {
+        assert_eq!(expr("7 / 2"), "(/ 7 2)");
+        assert_eq!(expr("7 % 2"), "(% 7 2)");
+        assert_eq!(expr("a + b / c % 2"), "(+ a (% (/ b c) 2))");
+        assert_eq!(expr("7u / 2u"), "(/ 7 2)");
+    }
+
+    #[test]
+    fn unary_minus_and_plus() {
+        assert_eq!(expr("-a"), "(neg a)");
+        assert_eq!(expr("+a"), "a");
+        assert_eq!(expr("- -a"), "(neg (neg a))");
+        assert_eq!(expr("-a * b"), "(* (neg a) b)");
+        assert_eq!(expr("a - -b"), "(- a (neg b))");
+        // a signed literal folds its sign; an unsigned one keeps the negation
+        assert_eq!(expr("-5"), "-5");
+        assert_eq!(expr("-5u"), "(neg 5)");
+    }
+
+    #[test]
+    fn logical_not() {
+        assert_eq!(expr("!a"), "(== a 0)");
+        assert_eq!(expr("!!a"), "(!= a 0)");
+        assert_eq!(expr("!(a < b)"), "(>= a b)");
+        assert_eq!(expr("!(a == b)"), "(!= a b)");
+        assert_eq!(expr("!0"), "true");
+        assert_eq!(expr("!7"), "false");
+        assert_eq!(expr("!a + 1"), "(+ (== a 0) 1)");
+    }
+
+    #[test]
+    fn logical_and_or() {
+        assert_eq!(expr("a && b"), "(&& (!= a 0) (!= b 0))");
+        assert_eq!(expr("a < b || c"), "(|| (< a b) (!= c 0))");
+        // && binds tighter than ||
+        assert_eq!(expr("a || b && c"), "(|| (!= a 0) (&& (!= b 0) (!= c 0)))");
+        assert_eq!(expr("!(a && b < c)"), "(|| (== a 0) (>= b c))");
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        assert_eq!(expr("~a"), "(~ a)");
+        assert_eq!(expr("a | b ^ c & 1"), "(| a (^ b (& c 1)))");
+        // & binds looser than ==, and tighter than &&
+        assert_eq!(expr("a & b == c"), "(& a (== b c))");
+        assert_eq!(expr("a & b && c"), "(&& (!= (& a b) 0) (!= c 0))");
+    }
+
+    #[test]
+    fn shifts() {
+        assert_eq!(expr("a << 2 + b"), "(<< a (+ 2 b))");
+        assert_eq!(expr("a >> b < c"), "(< (>> a b) c)");
+        assert_eq!(expr("1 << 31"), "(<< 1 31)");
+        assert_eq!(expr("a << 31"), "(<< a 31)");
+    }
+
+    #[test]
+    fn constant_shift_amounts_are_range_checked() {
+        let e = parse("int f() { return 1 << 32; }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "shift amount 32 is out of range for a 32-bit operand");
+        assert_eq!((e.line, e.col), (1, 20));
+        let e = parse("int f() { return 1L >> -1; }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "shift amount -1 is out of range for a 64-bit operand");
+        // a `char` operand shifts as a promoted `int`
+        assert_eq!(expr("(char)1 << 20"), "(<< 1 20)");
+        let e = parse("int f() { return (char)1 << 32; }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "shift amount 32 is out of range for a 32-bit operand");
+
+        // variables are checked against their own promoted width
+        assert_eq!(expr_err("a << 40"), "shift amount 40 is out of range for a 32-bit operand");
+        assert_eq!(expr_err("a >> (30 + 2)"), "shift amount 32 is out of range for a 32-bit operand");
+        let e = parse("int f(char c) { return c << 32; }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "shift amount 32 is out of range for a 32-bit operand");
+        assert!(parse("long f(long l, char c) { return (l << 40) + (c << 20); }").is_ok());
+
+        // and so are compound assignments
+        let e = parse("void f(int x) { x <<= 40; }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "shift amount 40 is out of range for a 32-bit operand");
+        assert_eq!((e.line, e.col), (1, 23));
+        let e = parse("void f(short s) { s >>= -1; }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "shift amount -1 is out of range for a 32-bit operand");
+        assert!(parse("void f(long l) { l <<= 40; }").is_ok());
+    }
+
+    #[test]
+    fn conditional_operator() {
+        assert_eq!(expr("a ? b : c"), "(? (!= a 0) b c)");
+        assert_eq!(expr("a < b ? a : b"), "(? (< a b) a b)");
+        // right-associative, and the middle operand is a full expression
+        assert_eq!(expr("a ? b : c ? 1 : 2"), "(? (!= a 0) b (? (!= c 0) 1 2))");
+        assert_eq!(expr("a ? b ? 1 : 2 : c"), "(? (!= a 0) (? (!= b 0) 1 2) c)");
+        assert_eq!(expr("a || b ? 1 : 2"), "(? (|| (!= a 0) (!= b 0)) 1 2)");
+        assert!(parse("int f(int a) { return a ? 1; }").is_err());
+    }
+
+    #[test]
+    fn compound_assignment_expands_in_place() {
+        let src = "int f(int a) { int x = 1; x += a * 2; x <<= 3; x ^= a; return x; }";
+        let p = parse(src).unwrap();
+        let assigns: Vec<String> = p.functions[0]
+            .body
+            .iter()
+            .filter_map(|st| match st {
+                s::Stmt::Assign { name, value } => Some(format!("{name} = {}", sexp(value))),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(assigns, ["x = (+ x (* a 2))", "x = (<< x 3)", "x = (^ x a)"]);
+    }
+
+    #[test]
+    fn increment_and_decrement() {
+        assert_eq!(expr("a++ + --b"), "(+ a++ --b)");
+        assert_eq!(expr("-a--"), "(neg a--)");
+        assert_eq!(expr("a+++b"), "(+ a++ b)");
+        let p = parse("int f(int a) { a++; --a; return a; }").unwrap();
+        assert!(matches!(&p.functions[0].body[..2], [
+            s::Stmt::Assign { value: s::Expr::Binary { op: s::BinOpRef::Add, .. }, .. },
+            s::Stmt::Assign { value: s::Expr::Binary { op: s::BinOpRef::Sub, .. }, .. },
+        ]));
+        let e = parse("int f(int a) { return 1++; }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "operand of increment/decrement must be a variable");
+        assert!(parse("int f(int a) { return ++(a + 1); }").is_err());
+    }
+
+    #[test]
+    fn for_desugars_to_while() {
+        let src = "int f(int a) { int x = 0; for (int i = 0; i < a; i++) { x += i; } return x; }";
+        let p = parse(src).unwrap();
+        let body = &p.functions[0].body;
+        let [_, s::Stmt::VarDecl { name, .. }, s::Stmt::While { cond, body }, _] = &body[..] else {
+            panic!("{body:?}")
+        };
+        assert_eq!(name, "i");
+        assert_eq!(sexp(cond), "(< i a)");
+        assert!(matches!(&body[..], [
+            s::Stmt::Assign { name: x, .. },
+            s::Stmt::Assign { name: i, value: s::Expr::Binary { op: s::BinOpRef::Add, .. } },
+        ] if x == "x" && i == "i"));
+
+        let p = parse("int f() { for (;;) { break; } return 0; }").unwrap();
+        assert!(matches!(&p.functions[0].body[0], s::Stmt::While {
+            cond: s::Expr::Lit(s::Lit::Bool(true)),
+            body,
+        } if matches!(body[..], [s::Stmt::Break])));
+    }
+
+    #[test]
+    fn do_while_tests_after_the_body() {
+        let src = "int f(int a) { do { a--; if (a == 5) continue; } while (a > 0); return a; }";
+        let p = parse(src).unwrap();
+        let body = &p.functions[0].body;
+        let s::Stmt::While { cond: s::Expr::Lit(s::Lit::Bool(true)), body } = &body[0] else {
+            panic!("{body:?}")
+        };
+        let [_, s::Stmt::If { then_body, .. }, s::Stmt::Label(next), test] = &body[..] else {
+            panic!("{body:?}")
+        };
+        let s::Stmt::If { cond, then_body: brk, .. } = test else {
+            panic!("{body:?}")
+        };
+        assert_eq!(sexp(cond), "(<= a 0)");
+        assert!(matches!(brk[..], [s::Stmt::Break]));
+        // `continue` goes through the condition too, by jumping to it
+        assert!(matches!(&then_body[..], [s::Stmt::Goto(l)] if l == next), "{then_body:?}");
+
+        let e = parse("int f(int a) { do a--; while (a) return a; }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "expected ';' after do-while condition");
+    }
+
+    #[test]
+    fn switch_arms() {
+        let p = parse(
+            "int f(int a) { switch (a) { case 1: case -2: a = 3; break; default: a = 0; } return a; }",
+        )
+        .unwrap();
+        let s::Stmt::Switch { value, cases } = &p.functions[0].body[0] else { panic!() };
+        assert_eq!(sexp(value), "a");
+        let labels: Vec<_> = cases.iter().map(|c| (c.label, c.body.len())).collect();
+        assert_eq!(labels, [(Some(1), 0), (Some(-2), 2), (None, 1)]);
+    }
+
+    #[test]
+    fn switch_label_errors() {
+        let err = |body: &str| {
+            parse(&format!("int f(int a) {{ switch (a) {{ {body} }} return a; }}"))
+                .unwrap_err()
+                .kind.to_string()
+        };
+        assert_eq!(err("case 1: case 1: break;"), "duplicate case value 1");
+        assert_eq!(err("default: default: break;"), "multiple default labels in one switch");
+        assert_eq!(
+            err("case a: break;"),
+            "case label is not an integer constant: `a` is not a constant"
+        );
+        assert_eq!(err("case 4: case 2 * 2: break;"), "duplicate case value 4");
+        assert_eq!(err("a = 1; case 1: break;"), "statement in switch before any case label");
+    }
+
+    #[test]
+    fn goto_and_labels() {
+        let p = parse("int f(int a) { goto out; a = 1; out: return a; }").unwrap();
+        assert!(matches!(&p.functions[0].body[..], [
+            s::Stmt::Goto(g),
+            s::Stmt::Assign { .. },
+            s::Stmt::Label(l),
+            s::Stmt::Return(_),
+        ] if g == "out" && l == "out"));
+        // labels are per function
+        assert!(parse("int f() { l: return 0; } int g() { l: return 1; }").is_ok());
+    }
+
+    #[test]
+    fn label_errors() {
+        let e = parse("int f() { goto nowhere; return 0; }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "use of undefined label `nowhere`");
+        assert_eq!((e.line, e.col), (1, 16));
+        let e = parse("int f() { l: l: return 0; }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "redefinition of label `l`");
+        assert_eq!((e.line, e.col), (1, 14));
+        assert!(parse("int f() { l: return 0; } int g() { goto l; }").is_err());
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(expr("g()"), "g()");
+        assert_eq!(expr("g(a, b + 1, h(c))"), "g(a, (+ b 1), h(c))");
+        assert_eq!(expr("-g(a) * 2"), "(* (neg g(a)) 2)");
+        let e = parse("int f(int a) { return 1(a); }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "called object is not a function");
+        assert!(parse("int g(); int f(int a) { return g(a,,); }").is_err());
+    }
+
+    #[test]
+    fn calls_to_undeclared_functions() {
+        let e = parse("int f(int a) { return g(a); }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "use of undeclared identifier `g`");
+        assert_eq!((e.line, e.col), (1, 23));
+        // a prototype or an earlier definition declares it, and so does the function's own signature
+        assert!(parse("int g(int); int f(int a) { return g(a); } int g(int a) { return a; }").is_ok());
+        assert!(parse("int g(int a) { return a; } int f(int a) { return g(a); }").is_ok());
+        assert!(parse("int f(int a) { return a ? f(a - 1) : 0; }").is_ok());
+        assert!(parse("int f(int a) { return g(a); } int g(int a) { return a; }").is_err());
+    }
+
+    // (bits, signed) of a local declared with `spec`
+    fn ty(spec: &str) -> Result<(u16, bool), String> {
+        let p = parse(&format!("int f() {{ {spec} x; return 0; }}")).map_err(|e| e.kind.to_string())?;
+        match &p.functions[0].body[0] {
+            s::Stmt::VarDecl { ty: s::TypeRef::Int { bits, signed }, .. } => Ok((*bits, *signed)),
+            other => panic!("{spec}: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn integer_types_follow_the_target() {
+        assert_eq!(ty("char"), Ok((8, true)));
+        assert_eq!(ty("unsigned char"), Ok((8, false)));
+        assert_eq!(ty("short"), Ok((16, true)));
+        assert_eq!(ty("short int"), Ok((16, true)));
+        assert_eq!(ty("unsigned"), Ok((32, false)));
+        assert_eq!(ty("long"), Ok((64, true)));
+        assert_eq!(ty("long unsigned int"), Ok((64, false)));
+        assert_eq!(ty("long long"), Ok((64, true)));
+        assert_eq!(ty("unsigned long long int"), Ok((64, false)));
+    }
+
+    #[test]
+    fn bad_type_specifier_combinations() {
+        assert_eq!(ty("long long long").unwrap_err(), "duplicate type specifier 'long'");
+        assert_eq!(ty("int int").unwrap_err(), "duplicate type specifier 'int'");
+        assert_eq!(ty("short long").unwrap_err(), "conflicting type specifiers");
+        assert_eq!(ty("char int").unwrap_err(), "conflicting type specifiers");
+        assert_eq!(
+            parse("unsigned void f() { return; }").unwrap_err().kind.to_string(),
+            "`void` cannot be combined with other type specifiers"
+        );
+    }
+
+    #[test]
+    fn signed_specifier() {
+        assert_eq!(ty("signed"), Ok((32, true)));
+        assert_eq!(ty("signed char"), Ok((8, true)));
+        assert_eq!(ty("long signed"), Ok((64, true)));
+        assert_eq!(ty("signed unsigned").unwrap_err(), "`signed` and `unsigned` cannot be combined");
+        assert_eq!(ty("signed signed").unwrap_err(), "duplicate type specifier 'signed'");
+    }
+
+    #[test]
+    fn pointers() {
+        let p = parse("int *f(int **pp) { int *p = *pp; *p = 1; **pp += 2; return &*p; }").unwrap();
+        let f = &p.functions[0];
+        let ptr = |t: &s::TypeRef| match t {
+            s::TypeRef::Pointer(inner) => (**inner).clone(),
+            other => panic!("{other:?}"),
+        };
+        assert!(matches!(ptr(&f.return_type), s::TypeRef::Int { bits: 32, .. }));
+        assert!(matches!(ptr(&ptr(&f.parameters[0].ty)), s::TypeRef::Int { .. }));
+        let stores: Vec<String> = f
+            .body
+            .iter()
+            .filter_map(|st| match st {
+                s::Stmt::Store { target, value } => Some(format!("{} = {}", sexp(target), sexp(value))),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(stores, ["(* p) = 1", "(* (* pp)) = (+ (* (* pp)) 2)"]);
+        assert_eq!(expr("*&a"), "(* (& a))");
+    }
+
+    #[test]
+    fn pointer_errors() {
+        assert_eq!(expr_err("&1"), "cannot take the address of an rvalue");
+        assert_eq!(
+            parse("int f(int a) { a + 1 = 2; return a; }").unwrap_err().kind.to_string(),
+            "expression is not assignable"
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        let p = parse("int f(int a[], int m[][3]) { int b[2][4]; b[1][a[0]] = 5; return m[1][2]; }")
+            .unwrap();
+        let f = &p.functions[0];
+        let pointee = |t: &s::TypeRef| match t {
+            s::TypeRef::Pointer(e) => (**e).clone(),
+            other => panic!("{other:?}"),
+        };
+        assert!(matches!(pointee(&f.parameters[0].ty), s::TypeRef::Int { .. }));
+        assert!(matches!(pointee(&f.parameters[1].ty), s::TypeRef::Array { len: 3, .. }));
+        let s::Stmt::VarDecl { ty: s::TypeRef::Array { elem, len: 2 }, .. } = &f.body[0] else {
+            panic!("{:?}", f.body)
+        };
+        assert!(matches!(**elem, s::TypeRef::Array { len: 4, .. }));
+        let s::Stmt::Store { target, .. } = &f.body[1] else { panic!() };
+        assert_eq!(sexp(target), "(* (+ (* (+ b 1)) (* (+ a 0))))");
+    }
+
+    #[test]
+    fn array_size_errors() {
+        let err = |body: &str| {
+            parse(&format!("int f(int n) {{ {body} return 0; }}")).unwrap_err().kind.to_string()
+        };
+        assert_eq!(err("int a[0];"), "array size must be positive");
+        assert_eq!(err("int a[n];"), "array size is not an integer constant: `n` is not a constant");
+        assert_eq!(err("int a[];"), "variable `a` has incomplete type");
+        assert_eq!(
+            parse("int f(int a[][]) { return 0; }").unwrap_err().kind.to_string(),
+            "array has incomplete element type"
+        );
+    }
+
+    #[test]
+    fn struct_layout() {
+        let p = parse(
+            "struct s { char c; int i; short h; };
+             struct t { char c; struct s s; long l[2]; struct t *next; };
+             int f() { return 0; }",
+        )
+        .unwrap();
+        let layout: Vec<_> = p
+            .structs
+            .iter()
+            .map(|d| (d.fields.iter().map(|f| f.offset).collect::<Vec<_>>(), d.size, d.align))
+            .collect();
+        assert_eq!(layout, [(vec![0, 4, 8], 12, 4), (vec![0, 4, 16, 32], 40, 8)]);
+    }
+
+    #[test]
+    fn member_access() {
+        let src = "struct p { int x; int y; };
+                   int f(struct p *q) { struct p r; r.x = q->y; return (*q).x + r.y; }";
+        let p = parse(src).unwrap();
+        let body = &p.functions[0].body;
+        let s::Stmt::Store { target, value } = &body[1] else { panic!("{body:?}") };
+        assert_eq!((sexp(target), sexp(value)), ("r.x".into(), "(* q).y".into()));
+        assert_eq!(sexp(&returned(src)), "(+ (* q).x r.y)");
+    }
+
+    #[test]
+    fn struct_errors() {
+        let err = |src: &str| parse(src).unwrap_err().kind.to_string();
+        assert_eq!(err("struct s { int a; int a; };"), "duplicate member `a`");
+        assert_eq!(err("struct s { int a; }; struct s { int b; };"), "redefinition of `struct s`");
+        assert_eq!(err("struct s { struct s inner; };"), "member `inner` has incomplete type");
+        assert_eq!(err("struct s { int a[]; };"), "member `a` has incomplete type");
+        assert_eq!(err("int f() { struct u v; return 0; }"), "variable `v` has incomplete type");
+        assert_eq!(err("int f(struct u v) { return 0; }"), "use of undefined `struct u`");
+        // a pointer to a not-yet-defined struct is fine
+        assert!(parse("int f(struct u *v) { return 0; }").is_ok());
+    }
+
+    #[test]
+    fn enumerators_are_int_constants() {
+        let src = "enum color { RED, GREEN = 5, BLUE, };
+                   enum { LAST = -1 };
+                   int f(enum color c) { return RED + GREEN * BLUE + LAST; }";
+        assert_eq!(sexp(&returned(src)), "(+ (+ 0 (* 5 6)) -1)");
+        let p = parse(src).unwrap();
+        assert!(matches!(p.functions[0].parameters[0].ty, s::TypeRef::Int { bits: 32, signed: true }));
+    }
+
+    #[test]
+    fn enum_errors() {
+        let err = |src: &str| parse(src).unwrap_err().kind.to_string();
+        assert_eq!(err("enum e { A }; enum e { B };"), "redefinition of `enum e`");
+        assert_eq!(err("enum { A, A };"), "redefinition of enumerator `A`");
+        assert_eq!(
+            err("int g; enum { A = 1 + g };"),
+            "enumerator value is not an integer constant: `g` is not a constant"
+        );
+        assert_eq!(err("int f(enum e x) { return 0; }"), "use of undefined `enum e`");
+    }
+
+    #[test]
+    fn typedefs() {
+        let src = "typedef unsigned long size_t;
+                   struct pt { int x; };
+                   typedef struct pt *pt_ref;
+                   size_t f(pt_ref p) { typedef int row[4]; row r; size_t *n; r[0] = p->x; return 0; }";
+        let p = parse(src).unwrap();
+        let f = &p.functions[0];
+        assert!(matches!(f.return_type, s::TypeRef::Int { bits: 64, signed: false }));
+        assert!(matches!(&f.parameters[0].ty, s::TypeRef::Pointer(t)
+            if matches!(**t, s::TypeRef::Struct(_))));
+        assert!(matches!(&f.body[..3], [
+            s::Stmt::VarDecl { ty: s::TypeRef::Array { len: 4, .. }, .. },
+            s::Stmt::VarDecl { ty: s::TypeRef::Pointer(_), .. },
+            s::Stmt::Store { .. },
+        ]));
+        let e = parse("typedef int t; typedef long t;").unwrap_err();
+        assert_eq!(e.kind.to_string(), "redefinition of typedef `t`");
+    }
+
+    #[test]
+    fn declarator_lists() {
+        let p = parse(
+            "struct v { int x, y[2], *z; };
+             const int A = 1, B[2] = 0;
+             int f() { int a = A, *p, b[3]; const long c = 1, d = 2; return a; }",
+        )
+        .unwrap();
+        let offsets: Vec<_> = p.structs[0].fields.iter().map(|f| (f.name.as_str(), f.offset)).collect();
+        assert_eq!(offsets, [("x", 0), ("y", 4), ("z", 16)]);
+        let globals: Vec<_> = p.globals.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(globals, ["A", "B"]);
+        assert!(matches!(&p.functions[0].body[..5], [
+            s::Stmt::VarDecl { init: Some(_), ty: s::TypeRef::Int { .. }, .. },
+            s::Stmt::VarDecl { init: None, ty: s::TypeRef::Pointer(_), .. },
+            s::Stmt::VarDecl { ty: s::TypeRef::Array { len: 3, .. }, .. },
+            s::Stmt::ConstDecl { .. },
+            s::Stmt::ConstDecl { .. },
+        ]));
+        let e = parse("int f() { struct u a, *b; return 0; }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "variable `a` has incomplete type");
+    }
+
+    #[test]
+    fn prototypes() {
+        let p = parse("long g(int, char *s, int[]); int f() { return 0; } void h(void *);").unwrap();
+        let protos: Vec<_> = p
+            .prototypes
+            .iter()
+            .map(|d| (d.name.as_str(), d.parameters.iter().map(|p| p.name.as_str()).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(protos, [("g", vec!["", "s", ""]), ("h", vec![""])]);
+        assert!(matches!(p.prototypes[0].parameters[2].ty, s::TypeRef::Pointer(_)));
+        assert_eq!(p.functions.len(), 1);
+
+        let e = parse("int f(int) { return 0; }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "parameter name omitted in definition of `f`");
+    }
+
+    #[test]
+    fn storage_classes() {
+        let p = parse(
+            "extern int n, *p;
+             static const int K = 3;
+             static int helper(void);
+             int f() { extern long m; static int calls = 0, hits; calls++; return calls + K; }",
+        )
+        .unwrap();
+        let decls: Vec<_> = p.global_decls.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(decls, ["n", "p", "m"]);
+        assert_eq!(p.globals[0].linkage, s::Linkage::Internal);
+        assert_eq!(p.prototypes[0].linkage, s::Linkage::Internal);
+        assert_eq!(p.functions[0].linkage, s::Linkage::External);
+
+        // static locals live on as internal globals named after their function
+        let vars: Vec<_> = p.global_vars.iter().map(|v| (v.name.as_str(), v.init.is_some())).collect();
+        assert_eq!(vars, [("f.calls", true), ("f.hits", false)]);
+        assert!(p.global_vars.iter().all(|v| v.linkage == s::Linkage::Internal));
+        assert!(matches!(&p.functions[0].body[0], s::Stmt::Assign { name, .. } if name == "f.calls"));
+    }
+
+    #[test]
+    fn extern_cannot_be_initialized() {
+        let e = parse("extern int n = 1;").unwrap_err();
+        assert_eq!(e.kind.to_string(), "`extern` declaration of `n` cannot have an initializer");
+        assert_eq!((e.line, e.col), (1, 14));
+    }
+
+    #[test]
+    fn file_scope_variables() {
+        let p = parse(
+            "int counter, table[4];
+             static long total = 10;
+             unsigned *cursor;
+             int bump() { counter += 1; return counter; }",
+        )
+        .unwrap();
+        let vars: Vec<_> = p
+            .global_vars
+            .iter()
+            .map(|v| (v.name.as_str(), v.init.as_ref().map(sexp), v.linkage))
+            .collect();
+        assert_eq!(vars, [
+            ("counter", None, s::Linkage::External),
+            ("table", None, s::Linkage::External),
+            ("total", Some("10".into()), s::Linkage::Internal),
+            ("cursor", None, s::Linkage::External),
+        ]);
+        assert!(matches!(p.global_vars[1].ty, s::TypeRef::Array { len: 4, .. }));
+        assert!(p.globals.is_empty());
+    }
+
+    #[test]
+    fn floating_types_and_literals() {
+        let p = parse("double f(float x) { long double d = 2.5; return -1.5e3; }").unwrap();
+        let f = &p.functions[0];
+        assert!(matches!(f.return_type, s::TypeRef::Float { bits: 64 }));
+        assert!(matches!(f.parameters[0].ty, s::TypeRef::Float { bits: 32 }));
+        assert!(matches!(f.body[0], s::Stmt::VarDecl { ty: s::TypeRef::Float { bits: 64 }, .. }));
+        let lit = returned("int f() { return 0.5; }");
+        assert!(matches!(lit, s::Expr::Lit(s::Lit::Float { bits: 64, .. })));
+        assert_eq!(expr("-1.5e3"), "-1500.0");
+        assert_eq!(expr("!0.0"), "true");
+
+        assert_eq!(ty("unsigned float").unwrap_err(), "conflicting type specifiers");
+        assert_eq!(ty("long float").unwrap_err(), "conflicting type specifiers");
+        assert_eq!(ty("float double").unwrap_err(), "conflicting type specifiers");
+    }
+
+    #[test]
+    fn sizeof_folds_to_a_size_t() {
+        let src = |e: &str| {
+            format!(
+                "struct s {{ char c; long l; }};
+                 long f(short h, int *p, struct s v) {{ char buf[10]; return {e}; }}"
+            )
+        };
+        let size = |e: &str| match returned(&src(e)) {
+            s::Expr::Lit(s::Lit::Int { bits: 64, signed: false, value }) => value,
+            other => panic!("{e}: {other:?}"),
+        };
+        assert_eq!(size("sizeof(int)"), 4);
+        assert_eq!(size("sizeof(const unsigned long long)"), 8);
+        assert_eq!(size("sizeof(int[3][2])"), 24);
+        assert_eq!(size("sizeof(struct s)"), 16);
+        assert_eq!(size("sizeof buf"), 10);
+        assert_eq!(size("sizeof(buf[0])"), 1);
+        assert_eq!(size("sizeof h"), 2);
+        assert_eq!(size("sizeof -h"), 4);
+        assert_eq!(size("sizeof *p"), 4);
+        assert_eq!(size("sizeof &v"), 8);
+        assert_eq!(size("sizeof v.l"), 8);
+        assert_eq!(size("sizeof(h + 1L)"), 8);
+        assert_eq!(size("sizeof(p - p)"), 8);
+        assert_eq!(size("sizeof 1.5"), 8);
+    }
+
+    #[test]
+    fn sizeof_errors() {
+        let e = parse("void g(); int f() { return sizeof(void); }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "invalid application of `sizeof` to an incomplete type");
+        let e = parse("int g(); int f() { return sizeof g(); }").unwrap_err();
+        assert_eq!(e.kind.to_string(), "cannot determine the type of the `sizeof` operand");
+    }
+
+    #[test]
+    fn casts() {
+        assert_eq!(expr("(long)a"), "(i64 a)");
+        assert_eq!(expr("*(unsigned char *)&a"), "(* (*u8 (& a)))");
+        assert_eq!(expr("(short)a + b"), "(+ (i32 (i16 a)) b)");
+        assert_eq!(expr("(double)(a)"), "(f64 a)");
+        // a parenthesized expression is not a cast
+        assert_eq!(expr("(a) + b"), "(+ a b)");
+    }
+
+    #[test]
+    fn casts_of_literals_fold() {
+        assert_eq!(lit("(unsigned char)300"), (8, false, 44));
+        assert_eq!(lit("(signed char)200"), (8, true, -56));
+        assert_eq!(lit("(unsigned)-1"), (32, false, u32::MAX as i128));
+        assert_eq!(lit("(int)2.9"), (32, true, 2));
+        assert_eq!(lit("(int)-2.9"), (32, true, -2));
+        assert_eq!(expr("(float)0.1"), format!("{:?}", 0.1f32 as f64));
+        assert_eq!(expr("(double)3"), "3.0");
+    }
+
+    #[test]
+    fn comma_operator() {
+        assert_eq!(expr("a, b, c"), "(, (, a b) c)");
+        assert_eq!(expr("a ? b, c : 1"), "(? (!= a 0) (, b c) 1)");
+        // in an argument list the comma separates
+        assert_eq!(expr("g(a, (b, c))"), "g(a, (, b c))");
+        assert_eq!(lit("sizeof(1, (char)2)"), (64, false, 1));
+
+        // statement level: the operands become separate statements
+        let p = parse("int f(int a, int b) { for (a = 0, b = 9; a < b; a++, b--) {} return a; }")
+            .unwrap();
+        let body = &p.functions[0].body;
+        assert!(matches!(&body[..2], [s::Stmt::Assign { .. }, s::Stmt::Assign { .. }]), "{body:?}");
+        let s::Stmt::While { body, .. } = &body[2] else { panic!("{body:?}") };
+        assert_eq!(body.len(), 2);
+    }
+
+    #[test]
+    fn ast_dump() {
+        assert_ast(
+            "struct p { char c; int *n; };
+             extern int seen;
+             static const long K = 2;
+             unsigned total;
+             int g(int, char *s);
+             int f(struct p *q, int a[]) {
+                 while (a[0] < K) { if (q->c) break; else a[0] += g(1, 0); }
+                 return (char)total + -seen;
+             }",
+            r#"
+            struct p (size 16, align 8)
+              c: i8 @0
+              n: i32* @8
+            extern seen: i32
+            static const K: i64
+              Int 2: i64
+            var total: u32
+            fn g(i32, s: i8*) -> i32;
+            fn f(q: struct p*, a: i32*) -> i32
+              While
+                Cmp Lt
+                  Cast i64
+                    Unary Deref
+                      Binary Add
+                        Var a
+                        Int 0: i32
+                  Var K
+                body
+                  If
+                    Cmp Ne
+                      Member .c
+                        Unary Deref
+                          Var q
+                      Int 0: i8
+                    then
+                      Break
+                    else
+                      Store
+                        Unary Deref
+                          Binary Add
+                            Var a
+                            Int 0: i32
+                        Binary Add
+                          Unary Deref
+                            Binary Add
+                              Var a
+                              Int 0: i32
+                          Call g
+                            Int 1: i32
+                            Int 0: i32
+              Return
+                Binary Add
+                  Cast i32
+                    Cast i8
+                      Var total
+                  Unary Neg
+                    Var seen
+            "#,
+        );
+    }
+
+    #[test]
+    fn unreachable_statements() {
+        assert_eq!(
+            warnings("int f(int x) {\n  return x;\n  x = 1;\n  return 2;\n}"),
+            ["unreachable statement after `return` (3:3)"]
+        );
+        assert_eq!(
+            warnings("int f(int x) { while (x) { break; x--; } return x; }"),
+            ["unreachable statement after `break` (1:35)"]
+        );
+        assert_eq!(
+            warnings("int f(int x) { switch (x) { case 1: return 1; x = 2; } return x; }"),
+            ["unreachable statement after `return` (1:47)"]
+        );
+        // a label can be jumped to
+        assert!(warnings("int f(int x) { goto l; x = 1; l: return x; }").len() == 1);
+        assert!(warnings("int f(int x) { goto l; l: x = 1; return x; }").is_empty());
+        assert!(warnings("int f(int x) { if (x) return 1; return 2; }").is_empty());
+    }
+
+    #[test]
+    fn i686_is_ilp32() {
+        let src = "struct s { char c; long long l; int *p; };
+                   long f(long a) { return sizeof(struct s) + sizeof(int *) + sizeof a + 2147483648; }";
+        let (p, _) = parse_translation_unit(src, &Target::I686, ParseOptions::LENIENT).unwrap();
+        assert_eq!((p.structs[0].fields[1].offset, p.structs[0].size, p.structs[0].align), (4, 16, 4));
+        assert!(matches!(p.functions[0].return_type, s::TypeRef::Int { bits: 32, signed: true }));
+        let s::Stmt::Return(Some(e)) = &p.functions[0].body[0] else { panic!() };
+        // size_t is 32 bits, and a literal too big for long is long long
+        assert_eq!(sexp(e), "(+ (i64 (+ (+ 16 4) 4)) 2147483648)");
+        let s::Expr::Binary { left, right, .. } = e else { panic!() };
+        assert!(matches!(**right, s::Expr::Lit(s::Lit::Int { bits: 64, signed: true, .. })));
+        let s::Expr::Cast { expr: sum, .. } = &**left else { panic!() };
+        let s::Expr::Binary { right: size, .. } = &**sum else { panic!() };
+        assert!(matches!(**size, s::Expr::Lit(s::Lit::Int { bits: 32, signed: false, .. })));
+    }
+
+    #[test]
+    fn recovery_reports_every_error() {
+        assert_eq!(
+            errors("int f() { return 1 + ; } int g() { int x = ; return 0; } int h() { return 2; }"),
+            ["expected expression, got ';' (1:22)", "expected expression, got ';' (1:44)"]
+        );
+        // statements after a bad one in the same body are still checked
+        assert_eq!(
+            errors("int f() { int a = ; a = 1 +; return a; }"),
+            ["expected expression, got ';' (1:19)", "expected expression, got ';' (1:28)"]
+        );
+        assert_eq!(errors("int f() { return 1; }"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn shadowing_renames_inner_locals() {
+        assert_ast(
+            "int f() { int x = 1; { int x = 2; x = 3; } return x; }",
+            "
+            fn f() -> i32
+              VarDecl x: i32
+                Int 1: i32
+              VarDecl x.1: i32
+                Int 2: i32
+              Assign x.1
+                Int 3: i32
+              Return
+                Var x
+            ",
+        );
+        // a sibling block reusing a name still gets a fresh one, and parameters count too
+        assert_eq!(
+            sexp(&returned("int f(int a) { { int a = 1; } { int a = 2; return a; } }")),
+            "a.2"
+        );
+    }
+
+    #[test]
+    fn redeclarations_in_one_scope() {
+        assert_eq!(expr_err("0; int x; int x; return x"), "redeclaration of `x`");
+        assert_eq!(expr_err("0; int a; return a"), "redeclaration of `a`");
+    }
+
+    #[test]
+    fn undeclared_identifiers() {
+        let err = |src: &str| parse(src).unwrap_err().kind.to_string();
+        assert_eq!(expr_err("a + d"), "use of undeclared identifier `d`");
+        assert_eq!(err("int f() { { int x = 1; } return x; }"), "use of undeclared identifier `x`");
+        assert_eq!(err("int f() { int y = y0; return y; }"), "use of undeclared identifier `y0`");
+        // globals and enumerators declared earlier are in scope
+        assert!(parse("int g; enum { E = 3 }; int f() { return g + E; }").is_ok());
+    }
+
+    #[test]
+    fn return_types_are_checked() {
+        let err = |src: &str| parse(src).unwrap_err().kind.to_string();
+        assert_eq!(err("void f() { return 1; }"), "void function `f` should not return a value");
+        assert_eq!(err("int f() { return; }"), "non-void function `f` should return a value");
+        assert_eq!(
+            err("int g; int *f() { return g; }"),
+            "returning `i32` from `f`, which returns `i32*`"
+        );
+        // arithmetic converts, arrays decay and 0 is a null pointer
+        assert!(parse("long f() { return 'a'; } double g() { return 1; }").is_ok());
+        assert!(parse("int a[2]; int *f() { return a; } char *g() { return 0; }").is_ok());
+    }
+
+    #[test]
+    fn falling_off_a_non_void_function() {
+        assert_eq!(
+            warnings("int f(int x) {\n  if (x) return 1;\n}"),
+            ["control reaches end of non-void function `f` (3:1)"]
+        );
+        assert!(warnings("int f(int x) { if (x) return 1; else return 2; }").is_empty());
+        assert!(warnings("int f(int x) { for (;;) { x++; } }").is_empty());
+        assert!(warnings("int f(int x) { switch (x) { default: return 1; } }").is_empty());
+        assert_eq!(warnings("int f(int x) { while (1) { if (x) break; } }").len(), 1);
+        // `main` returns 0, and void functions have nothing to return
+        assert!(warnings("int main() { } void g() { }").is_empty());
+    }
+
+    #[test]
+    fn usual_arithmetic_conversions() {
+        assert_eq!(expr("a + 1L"), "(+ (i64 a) 1)");
+        assert_eq!(expr("a < 1u"), "(< (u32 a) 1)");
+        assert_eq!(expr("a * 2.5"), "(* (f64 a) 2.5)");
+        assert_eq!(expr("(char)a & (short)b"), "(& (i32 (i8 a)) (i32 (i16 b)))");
+        assert_eq!(expr("(long)a - (unsigned)b"), "(- (i64 a) (i64 (u32 b)))");
+        // shift operands are promoted on their own
+        assert_eq!(expr("(char)a << 1L"), "(<< (i32 (i8 a)) 1)");
+        // compound assignment converts back to the target's type
+        assert_ast(
+            "char f(char c) { c += 1; return c; }",
+            "
+            fn f(c: i8) -> i8
+              Assign c
+                Cast i8
+                  Binary Add
+                    Cast i32
+                      Var c
+                    Int 1: i32
+              Return
+                Var c
+            ",
+        );
+    }
+
+    #[test]
+    fn empty_statements() {
+        assert_ast(
+            "int f(int x) { ; ; {} while (x) ; return 0; }",
+            "
+            fn f(x: i32) -> i32
+              While
+                Cmp Ne
+                  Var x
+                  Int 0: i32
+                body
+              Return
+                Int 0: i32
+            ",
+        );
+        assert_eq!(sexp(&returned("int f(int x) { if (x) ; else x = 1; for (;;) ; return x; }")), "x");
+    }
+
+    #[test]
+    fn void_parameter_lists() {
+        let p = parse("int f(void); int f(void) { return 0; } int g(void *p) { return 0; }").unwrap();
+        assert!(p.prototypes[0].parameters.is_empty() && p.functions[0].parameters.is_empty());
+        assert_eq!(p.functions[1].parameters.len(), 1);
+        let err = |src: &str| parse(src).unwrap_err().kind.to_string();
+        assert_eq!(err("int f(int a, void) { return a; }"), "`void` must be the only parameter");
+        assert_eq!(err("int f(void, int a) { return a; }"), "`void` must be the only parameter");
+    }
+
+    #[test]
+    fn trailing_commas_in_lists() {
+        assert_eq!(expr("g(a, b,)"), "g(a, b)");
+        let p = parse("int g(int a, char *b,); int f(int a,) { return g(a, 0,); }").unwrap();
+        assert_eq!((p.prototypes[0].parameters.len(), p.functions[0].parameters.len()), (2, 1));
+        // only after an item
+        assert!(parse("int g(); int f(int a) { return g(,); }").is_err());
+        assert!(parse("int f(,) { return 0; }").is_err());
+    }
+
+    #[test]
+    fn strict_rejects_lenient_extensions() {
+        let strict = |src: &str| {
+            parse_with(src, ParseOptions::STRICT).map(|_| ()).map_err(|e| e.kind.to_string())
+        };
+        assert_eq!(
+            strict("int g(int a); int f(void) { return g(1,); }"),
+            Err("trailing comma before `)` (allowed with --std=lenient)".to_string())
+        );
+        assert_eq!(
+            strict("int f(int a,) { return a; }"),
+            Err("trailing comma before `)` (allowed with --std=lenient)".to_string())
+        );
+        assert_eq!(
+            strict("int f() { return 0; }"),
+            Err("`f` is declared without a prototype; write `f(void)`".to_string())
+        );
+        assert_eq!(strict("int g(int a); int f(void) { return g(1); }"), Ok(()));
+        assert!(parse("int g(); int f() { return g(1,); }").is_ok());
+    }
+
+    #[test]
+    fn constant_true_loop_conditions() {
+        let cond = |src: &str| {
+            let p = parse(src).unwrap();
+            let s::Stmt::While { cond, .. } = &p.functions[0].body[0] else { panic!("{src}") };
+            sexp(cond)
+        };
+        assert_eq!(cond("void f(void) { while (1) {} }"), "true");
+        assert_eq!(cond("void f(void) { while (-2) {} }"), "true");
+        assert_eq!(cond("void f(void) { for (;;) {} }"), "true");
+        assert_eq!(cond("void f(void) { do {} while (1); }"), "true");
+        assert_eq!(cond("void f(void) { while (0) {} }"), "(!= 0 0)");
+        assert_eq!(cond("void f(int x) { while (x) {} }"), "(!= x 0)");
+    }
+
+    #[test]
+    fn error_kinds() {
+        let kind = |src: &str| parse(src).unwrap_err().kind;
+        assert!(matches!(
+            kind("int f(void) { return 1 }"),
+            ParseErrorKind::Expected { want: Tok::Semi, got: Tok::RBrace }
+        ));
+        assert!(matches!(kind("int f(void) { return 1 + ; }"), ParseErrorKind::ExpectedExpr { .. }));
+        assert!(matches!(kind("int 1;"), ParseErrorKind::ExpectedIdent { got: Tok::IntLit(..) }));
+        assert!(matches!(kind("int f(int a, 2)"), ParseErrorKind::ExpectedType { .. }));
+        assert!(matches!(kind("int f(void) { return @; }"), ParseErrorKind::Lex(_)));
+        assert!(matches!(kind("int f(void) { return x; }"), ParseErrorKind::Other(_)));
+        // running out of input says so instead of naming `Eof`
+        let e = parse("int f(void) { return 1;").unwrap_err();
+        assert!(matches!(&e.kind, ParseErrorKind::UnexpectedEof { want } if want == "'}'"), "{e}");
+        assert_eq!(e.to_string(), "expected '}', got end of input (1:24)");
+    }
+
+    #[test]
+    fn constant_expressions() {
+        let p = parse(
+            "const int N = 2 * 3 + 1;
+             enum { A = N - 1, B = A << 2, C = sizeof(long) / 4 };
+             int table[N + B];
+             static const unsigned char M = 300;
+             int f(void) { return C + M; }",
+        )
+        .unwrap();
+        assert!(matches!(p.globals[0].init, s::Expr::Lit(s::Lit::Int { value: 7, .. })));
+        assert!(matches!(p.global_vars[0].ty, s::TypeRef::Array { len: 31, .. }));
+        // the initializer wraps to the const's type before other constants read it
+        assert!(matches!(p.globals[1].init, s::Expr::Lit(s::Lit::Int { value: 44, bits: 8, .. })));
+        assert_eq!(sexp(&returned("enum { A = -1 ? 4 : 5 }; int f(void) { return A; }")), "4");
+    }
+
+    #[test]
+    fn non_constant_expressions() {
+        let err = |src: &str| parse(src).unwrap_err().kind.to_string();
+        assert_eq!(
+            err("int g(void); enum { A = g() };"),
+            "enumerator value is not an integer constant: call to `g`"
+        );
+        assert_eq!(
+            err("int a[(-2147483647 - 1) / -1];"),
+            "array size is not an integer constant: division by zero or overflow"
+        );
+        assert_eq!(
+            err("const int K = 1 << 40;"),
+            "shift amount 40 is out of range for a 32-bit operand"
+        );
+        assert_eq!(
+            err("int a[2.5];"),
+            "array size is not an integer constant: floating-point value"
+        );
+    }
+
+    #[test]
+    fn chained_comparisons() {
+        assert_eq!(expr("a < b < c"), "(< (< a b) c)");
+        assert_eq!(expr("a == b < c"), "(== a (< b c))");
+        assert_eq!(expr("a < b == b > c"), "(== (< a b) (> b c))");
+        assert_eq!(expr("a != b == c"), "(== (!= a b) c)");
+    }
+
+    #[test]
+    fn assignment_expressions() {
+        assert_ast(
+            "int f(int a, int b) { a = b = 2; return (a += 1) * b; }",
+            "
+            fn f(a: i32, b: i32) -> i32
+              Assign b
+                Int 2: i32
+              Assign a
+                Var b
+              Assign a
+                Binary Add
+                  Var a
+                  Int 1: i32
+              Return
+                Binary Mul
+                  Var a
+                  Var b
+            ",
+        );
+        // a loop condition reruns its assignment before every test
+        assert_ast(
+            "int g(void); void f(int c) { while ((c = g()) > 0) c--; }",
+            "
+            fn g() -> i32;
+            fn f(c: i32) -> void
+              While
+                Bool true
+                body
+                  Assign c
+                    Call g
+                  If
+                    Cmp Le
+                      Var c
+                      Int 0: i32
+                    then
+                      Break
+                  Assign c
+                    Binary Sub
+                      Var c
+                      Int 1: i32
+            ",
+        );
+    }
+
+    #[test]
+    fn assignments_in_conditional_operands() {
+        // the test is hoisted with the assignment, so it still only runs when reached
+        assert_ast(
+            "int f(int a, int b) { return a && (b = a); }",
+            "
+            fn f(a: i32, b: i32) -> i32
+              VarDecl cond.tmp: i32
+              If
+                Cmp Ne
+                  Var a
+                  Int 0: i32
+                then
+                  Assign b
+                    Var a
+                  Assign cond.tmp
+                    Cmp Ne
+                      Var b
+                      Int 0: i32
+                else
+                  Assign cond.tmp
+                    Bool false
+              Return
+                Var cond.tmp
+            ",
+        );
+        assert_ast(
+            "int f(int a, int b) { return a ? 1 : (b += a); }",
+            "
+            fn f(a: i32, b: i32) -> i32
+              VarDecl cond.tmp: i32
+              If
+                Cmp Ne
+                  Var a
+                  Int 0: i32
+                then
+                  Assign cond.tmp
+                    Int 1: i32
+                else
+                  Assign b
+                    Binary Add
+                      Var b
+                      Var a
+                  Assign cond.tmp
+                    Var b
+              Return
+                Var cond.tmp
+            ",
+        );
+        let p = parse("int f(int a, int b) { while (a || (b = a - 1)) a--; return b; }").unwrap();
+        let s::Stmt::While { body, .. } = &p.functions[0].body[0] else { panic!("{p:?}") };
+        assert!(matches!(&body[..3], [s::Stmt::VarDecl { .. }, s::Stmt::If { .. }, s::Stmt::If { .. }]));
+        assert!(parse("int f(int a, int b) { return a && (b || (a = b)) ? (b = 2) : a; }").is_ok());
+    }
+
+    #[test]
+    fn assignment_expression_errors() {
+        let err = |body: &str| {
+            parse(&format!("int g(void); int f(int a, int *p) {{ {body} return 0; }}"))
+                .unwrap_err()
+                .kind
+                .to_string()
+        };
+        assert_eq!(
+            err("a = a ? g() : (a = 1);"),
+            "assignment inside a `?:` arm of unknown or void type is not supported"
+        );
+        assert_eq!(
+            err("a = (*(p + g()) = 1);"),
+            "an assignment whose target has side effects can't be used as a value"
+        );
+        assert_eq!(
+            parse("int a; int b = (a = 1);").unwrap_err().kind.to_string(),
+            "assignment in the initializer of a static object"
+        );
+    }
+
+    #[test]
+    fn narrowing_warnings() {
+        assert_eq!(
+            warnings("void f(void) { char c = 300; }"),
+            ["implicit conversion to `i8` changes value from 300 to 44 (1:25)"]
+        );
+        assert!(warnings("void f(void) { char c = 100; }").is_empty());
+        assert_eq!(
+            warnings("void f(int x) { unsigned char e; e = -1; x = 4294967296; }"),
+            [
+                "implicit conversion to `u8` changes value from -1 to 255 (1:38)",
+                "implicit conversion to `i32` changes value from 4294967296 to 0 (1:46)",
+            ]
+        );
+        // file-scope objects and integer consts are checked too
+        assert_eq!(warnings("short s = 70000; const unsigned K = -2;").len(), 2);
+        // values only known at run time aren't
+        assert!(warnings("void f(int x) { char c = x; c = x * 1000; }").is_empty());
+    }
+
+    #[test]
+    fn const_objects_are_read_only() {
+        let err = |src: &str| parse(src).unwrap_err().kind.to_string();
+        let k = "cannot assign to const variable `K`";
+        assert_eq!(err("const int K = 1; void f(void) { K = 2; }"), k);
+        assert_eq!(err("void f(void) { const int K = 1; K++; }"), k);
+        assert_eq!(err("void f(const int K) { K += 1; }"), k);
+        assert_eq!(err("extern const int K; void f(void) { --K; }"), k);
+        assert_eq!(
+            err("struct p { int x; }; void f(const struct p q) { q.x = 1; }"),
+            "cannot assign to const variable `q`"
+        );
+        assert_eq!(
+            err("void f(void) { static const int s = 0; s = 1; }"),
+            "cannot assign to const variable `s`"
+        );
+        // a non-const one shadowing it is writable
+        assert!(parse("const int K = 1; void f(void) { int K; K = 2; }").is_ok());
+    }
+
+    #[test]
+    fn division_by_constant_zero() {
+        let err = |body: &str| {
+            parse(&format!("const int Z = 0; int f(int a) {{ {body} return a; }}"))
+                .unwrap_err()
+                .kind
+                .to_string()
+        };
+        assert_eq!(err("a = a / 0;"), "division by zero");
+        assert_eq!(err("a = a % (1 - 1);"), "division by zero");
+        assert_eq!(err("a %= Z;"), "division by zero");
+        assert_eq!(err("a /= '\\0';"), "division by zero");
+        let e = parse("int f(int a) { return a / 0; }").unwrap_err();
+        assert_eq!((e.line, e.col), (1, 25));
+        // only integer division; floating-point division by zero gives an infinity
+        assert!(parse("double f(double d) { return d / 0 + 1.0 / 0.0; }").is_ok());
+        assert!(parse("int f(int a) { return a / (a - 1); }").is_ok());
+    }
+
+    #[test]
+    fn bool_type() {
+        assert_eq!(ty("_Bool"), Ok((1, false)));
+        assert_eq!(ty("bool"), Ok((1, false)));
+        assert_eq!(ty("unsigned _Bool"), Err("conflicting type specifiers".to_string()));
+        assert_eq!(ty("_Bool int"), Err("conflicting type specifiers".to_string()));
+        let out = crate::dump::dump_program(
+            &parse("int f(void) { _Bool b = 5; _Bool z = 0.0; return b + z; }").unwrap(),
+        );
+        assert!(out.contains("VarDecl b: bool\n    Int 1: bool"), "{out}");
+        assert!(out.contains("VarDecl z: bool\n    Int 0: bool"), "{out}");
+        // a `_Bool` is a condition as it is, without a `!= 0`
+        assert_eq!(sexp(&returned("int f(_Bool b) { return b ? 1 : 2; }")), "(? b 1 2)");
+        assert_eq!(sexp(&returned("int f(int a) { return a ? 1 : 2; }")), "(? (!= a 0) 1 2)");
+    }
+
+    #[test]
+    fn errors_quote_source_tokens() {
+        let err = |src: &str| parse(src).unwrap_err().kind.to_string();
+        assert_eq!(err("int g(int); int f(void) { return g(1; }"), "expected ')', got ';'");
+        assert_eq!(err("int f(void) { int 3; }"), "expected identifier, got '3'");
+        assert_eq!(err("int f(void) { return ); }"), "expected expression, got ')'");
+        assert_eq!(err("int f(int a, while) { }"), "expected type, got 'while'");
+        assert_eq!(err("int f(void) { return 1"), "expected ';', got end of input");
+    }
+
+    #[test]
+    fn c89_declarations_come_first() {
+        let c89 = |src: &str| {
+            parse_translation_unit(src, &Target::X86_64, ParseOptions::C89)
+                .map(|_| ())
+                .map_err(|errors| errors.iter().map(|e| e.to_string()).collect::<Vec<_>>())
+        };
+        assert_eq!(
+            c89("int f(int a) { a++; int b = a; const int c = 1; return b + c; }"),
+            Err(vec![
+                "declaration after a statement (not allowed with --std=c89) (1:21)".to_string(),
+                "declaration after a statement (not allowed with --std=c89) (1:32)".to_string(),
+            ])
+        );
+        // a nested block starts over, and the default modes allow mixing
+        assert_eq!(c89("int f(int a) { int b; a++; { int c = a; b = c; } return b; }"), Ok(()));
+        assert!(parse("int f(int a) { a++; int b = a; return b; }").is_ok());
+        assert!(c89("int g(int); int f(int a) { return g(a,); }").is_err());
+    }
+
+    #[test]
+    fn else_if_chains() {
+        assert_ast(
+            "int g(void);
+             int f(int a) { if (a) return 1; else if ((a = g()) > 2) return 2; else return 3; }",
+            "
+            fn g() -> i32;
+            fn f(a: i32) -> i32
+              If
+                Cmp Ne
+                  Var a
+                  Int 0: i32
+                then
+                  Return
+                    Int 1: i32
+                else
+                  Assign a
+                    Call g
+                  If
+                    Cmp Gt
+                      Var a
+                      Int 2: i32
+                    then
+                      Return
+                        Int 2: i32
+                    else
+                      Return
+                        Int 3: i32
+            ",
+        );
+    }
+
+    #[test]
+    fn long_else_if_chains_do_not_recurse() {
+        let mut src = String::from("int f(int a) { if (a == 0) return 0;");
+        for i in 1..1000 {
+            src += &format!(" else if (a == {i}) return {i};");
+        }
+        src += " return -1; }";
+        let p = parse(&src).unwrap();
+        let mut depth = 0;
+        let mut st = &p.functions[0].body[0];
+        while let s::Stmt::If { else_body, .. } = st {
+            depth += 1;
+            let [next] = else_body.as_slice() else { break };
+            st = next;
+        }
+        assert_eq!(depth, 1000);
+    }
+
+    #[test]
+    fn void_pointers() {
+        assert!(parse("int f(void) { int x; void *v = &x; int *p = v; v = p; return *p; }").is_ok());
+        let err = |body: &str| {
+            parse(&format!("int f(void *q) {{ void *p = q; {body} return 0; }}"))
+                .unwrap_err()
+                .kind
+                .to_string()
+        };
+        assert_eq!(err("*p = 1;"), "cannot dereference a `void *`");
+        assert_eq!(err("return p[1];"), "cannot dereference a `void *`");
+        assert_eq!(err("return *q;"), "cannot dereference a `void *`");
+        assert_eq!(err("void v;"), "variable `v` has incomplete type");
+    }
+
+    #[test]
+    fn continue_in_for_runs_the_step() {
+        let src = "int f(int a) { for (a = 0; a < 9; a++) { if (a == 2) continue; } return a; }";
+        let p = parse_translation_unit(src, &Target::X86_64, ParseOptions::LENIENT).unwrap().0;
+        let s::Stmt::While { body, .. } = &p.functions[0].body[1] else { panic!() };
+        let [s::Stmt::If { then_body, .. }, s::Stmt::Assign { .. }] = &body[..] else {
+            panic!("{body:?}")
+        };
+        assert!(
+            matches!(&then_body[..], [s::Stmt::Assign { name, .. }, s::Stmt::Continue] if name == "a"),
+            "{then_body:?}"
+        );
+    }
+
+    #[test]
+    fn parenthesized_declarators() {
+        assert_ast(
+            "int (*g)[3]; struct S { int (*f)[2]; };
+             int h(int (*q)[4]) { int a[3]; int (*p)[3] = &a; int *(*pp)[2]; return 0; }",
+            "
+            struct S (size 8, align 8)
+              f: i32[2]* @0
+            var g: i32[3]*
+            fn h(q: i32[4]*) -> i32
+              VarDecl a: i32[3]
+              VarDecl p: i32[3]*
+                Unary AddrOf
+                  Var a
+              VarDecl pp: i32*[2]*
+              Return
+                Int 0: i32
+            ",
+        );
+        assert_eq!(errors("int f(void) { int (*p[3]; return 0; }"), ["expected ')', got ';' (1:25)"]);
+    }
+
+    #[test]
+    fn function_pointers_are_rejected_clearly() {
+        assert!(errors("int (*fp)(int);")[0].contains("function pointers are not supported"));
+    }
+
+    #[test]
+    fn recovery_keeps_valid_functions() {
+        let out = parse_recovering(
+            "int f(void) { return 1 + ; } int g(void) { int x = ; return 0; } int h(void) { return 2; }",
+            &Target::X86_64,
+            ParseOptions::LENIENT,
+        );
+        let errors: Vec<String> = out.errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            ["expected expression, got ';' (1:26)", "expected expression, got ';' (1:52)"]
+        );
+        assert_eq!(crate::dump::dump_program(&out.program), "fn h() -> i32\n  Return\n    Int 2: i32\n");
+    }
+
+    #[test]
+    fn unary_operands_are_promoted() {
+        let p = parse("int f(void) { char c = 1; return ~c + -c; }").unwrap();
+        let out = crate::dump::dump_program(&p);
+        assert_eq!(out.matches("Cast i32").count(), 2, "{out}");
+        // a literal operand is converted in place rather than cast
+        let e = returned("int f(void) { return ~(char)0; }");
+        let s::Expr::Unary { expr, .. } = e else { panic!("{e:?}") };
+        assert!(matches!(*expr, s::Expr::Lit(s::Lit::Int { bits: 32, value: 0, .. })), "{expr:?}");
+    }
+
+    #[test]
+    fn sizeof_does_not_evaluate_its_operand() {
+        assert_ast(
+            "int f(void) { int x = 1; return x + sizeof(x = 3); }",
+            "
+            fn f() -> i32
+              VarDecl x: i32
+                Int 1: i32
+              Return
+                Binary Add
+                  Cast u64
+                    Var x
+                  Int 4: u64
+            ",
+        );
+        assert!(errors("enum { A = sizeof(int) + 1 };").is_empty());
+        assert!(errors("int x; enum { A = (x = 1) };")[0].contains("assignment"));
+    }
+
+    #[test]
+    fn case_labels_are_constant_expressions() {
+        let p = parse(
+            "enum { N = 4 }; int f(int x) { switch (x) { case 1+2: return 1; case N: return 2; \
+             case -N: return 3; } return 0; }",
+        )
+        .unwrap();
+        let s::Stmt::Switch { cases, .. } = &p.functions[0].body[0] else { panic!() };
+        let labels: Vec<_> = cases.iter().map(|c| c.label).collect();
+        assert_eq!(labels, [Some(3), Some(4), Some(-4)]);
+    }
+
+    #[test]
+    fn bad_case_labels_recover_inside_the_switch() {
+        let src = "int f(int x) { switch (x) { case x: return 1; case 1 return 2; \
+                   default: default: return y; case 3: break; } return 0; } int g(void) { return 0; }";
+        assert_eq!(
+            errors(src),
+            [
+                "case label is not an integer constant: `x` is not a constant (1:34)",
+                "expected ':', got 'return' (1:54)",
+                "multiple default labels in one switch (1:73)",
+                "use of undeclared identifier `y` (1:89)",
+            ]
+        );
+    }
+
+    #[test]
+    fn do_while_continue_inside_a_switch() {
+        let src = "int f(int i) { do { switch (i) { case 1: continue; } i++; } while (i < 3); \
+                   do { do { continue; } while (i); continue; } while (i); return i; }";
+        let out = crate::dump::dump_program(&parse(src).unwrap());
+        assert!(out.contains("Goto do.next\n") && out.contains("Label do.next\n"), "{out}");
+        assert!(out.contains("Goto do.next.1\n") && out.contains("Label do.next.2\n"), "{out}");
+        assert!(!out.contains("Continue"), "{out}");
+    }
+
+    #[test]
+    fn compound_assignment_targets_are_evaluated_once() {
+        let dump = |src: &str| crate::dump::dump_program(&parse(src).unwrap());
+        let out = dump("int f(int *a, int i) { a[i++] += 1; return i; }");
+        assert_eq!(out.matches("IncDec PostInc i").count(), 1, "{out}");
+        assert!(out.contains("VarDecl addr.tmp: i32*"), "{out}");
+        // a pure target is just repeated
+        let out = dump("int f(int *a, int i) { a[i] += 2; return i; }");
+        assert!(!out.contains("addr.tmp"), "{out}");
+    }
+
+    #[test]
+    fn increment_statements_convert_like_compound_assignment() {
+        assert_ast(
+            "int f(long l, _Bool b) { l--; b++; return 0; }",
+            "
+            fn f(l: i64, b: bool) -> i32
+              Assign l
+                Binary Sub
+                  Var l
+                  Int 1: i64
+              Assign b
+                Cast bool
+                  Cmp Ne
+                    Binary Add
+                      Cast i32
+                        Var b
+                      Int 1: i32
+                    Int 0: i32
+              Return
+                Int 0: i32
+            ",
+        );
+    }
+
+    #[test]
+    fn conditions_compare_with_a_zero_of_their_type() {
+        assert_ast(
+            "int f(double d, int *p, int a, int b) { if (!(d < 1.0)) return 1; if (!(a < b)) return 2; \
+             if (p) return 3; return !d; }",
+            "
+            fn f(d: f64, p: i32*, a: i32, b: i32) -> i32
+              If
+                Cmp Eq
+                  Cmp Lt
+                    Var d
+                    Float 1.0: f64
+                  Bool false
+                then
+                  Return
+                    Int 1: i32
+              If
+                Cmp Ge
+                  Var a
+                  Var b
+                then
+                  Return
+                    Int 2: i32
+              If
+                Cmp Ne
+                  Var p
+                  Cast i32*
+                    Int 0: i32
+                then
+                  Return
+                    Int 3: i32
+              Return
+                Cmp Eq
+                  Var d
+                  Float 0.0: f64
+            ",
+        );
+    }
+
+    #[test]
+    fn const_before_a_pointer_qualifies_the_pointee() {
+        let ok = |src: &str| {
+            let r = parse(src);
+            assert!(r.is_ok(), "{src}: {r:?}");
+        };
+        ok("int len(const char *s) { int n = 0; while (*s) { s++; n++; } return n; }");
+        ok("int x; int f(void) { const int *p; p = &x; return *p; }");
+        ok("int x; const int *g; int f(void) { g = &x; return *g; }");
+        ok("int x; int f(void) { static const int *p; const int **pp = &p; *pp = &x; return **pp; }");
+        ok("int x; int f(void) { const int *a[2]; a[1] = &x; return *a[1]; }");
+
+        let err = |src: &str| parse(src).unwrap_err().kind.to_string();
+        let through_p = "cannot assign to a const object through `p`";
+        assert_eq!(err("void f(const int *p) { *p = 1; }"), through_p);
+        assert_eq!(err("void f(const int *p) { p[2] += 1; }"), through_p);
+        assert_eq!(err("int x; void f(void) { const int *p = &x; *p -= 1; }"), through_p);
+        assert_eq!(err("struct s { int v; }; void f(const struct s *p) { p->v = 1; }"), through_p);
+        assert_eq!(
+            err("int x; void f(void) { const int *a[2]; *a[1] = 0; }"),
+            "cannot assign to a const object through `a`"
+        );
+        assert_eq!(
+            err("void f(const int a[]) { a[0] = 1; }"),
+            "cannot assign to a const object through `a`"
+        );
+        // elements of a const array are const too
+        assert_eq!(
+            err("void f(void) { const int a[2] = 0; a[1] = 1; }"),
+            "cannot assign to const variable `a`"
+        );
+    }
+
+    #[test]
+    fn static_initializers_are_constant() {
+        let ok = "int a[2]; int x; struct S { int f; }; struct S sv;
+                  int *p = a; int *q = &a[1]; int *r = &x + 1; int *m = &sv.f; int *n = 0;
+                  double d = 1.5 * 2; const double h = 2.0 + 1; const int *c = &x;
+                  int f(void) { static int k; static int *pk = &k; static long n = sizeof k;
+                  return *pk; }";
+        assert!(errors(ok).is_empty(), "{:?}", errors(ok));
+
+        let err = |src: &str| parse(src).unwrap_err().kind.to_string();
+        assert_eq!(err("int g(void); int x = g();"), "initializer is not a constant: call to `g`");
+        assert_eq!(err("int y; int x = y + 1;"), "initializer is not a constant: `y` is not a constant");
+        assert_eq!(
+            err("int y; const double d = y;"),
+            "initializer is not a constant: `y` is not a constant"
+        );
+        assert_eq!(
+            err("int f(int a) { static int s = a; return s; }"),
+            "initializer is not a constant: `a` is not a constant"
+        );
+        assert_eq!(
+            err("int f(void) { int l; static int *p = &l; return 0; }"),
+            "initializer is not a constant: address or memory access"
+        );
+        // locals of an earlier function are out of scope by then
+        assert!(parse("int f(void) { int x = 1; return x; } int x; int *p = &x;").is_ok());
+    }
+}
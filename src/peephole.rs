@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ir::lower_ast::frontend as s;
+
+/// Rewrites integer algebraic identities where one operand is a literal or both
+/// are the same variable: `x + 0`, `x - 0`, `x * 1`, `x / 1`, `x | 0`, `x ^ 0`,
+/// `x << 0`, `x & x`, `x | x` become `x`; `x * 0`, `x & 0`, `x - x`, `x ^ x`
+/// become `0`. A dropped operand with side effects is kept as `(x, 0)`.
+/// Comparing a variable with itself folds too: `x == x`, `x <= x` and `x >= x`
+/// are true, `x != x`, `x < x` and `x > x` false.
+/// Operands that may be floating-point are left alone, since `x + 0` and
+/// `x * 0` aren't identities for `-0.0`, NaN or infinities, and `x == x` is
+/// false for NaN.
+/// Returns how many expressions were rewritten, since a rewrite can expose more folding.
+pub fn simplify_program(p: &mut s::Program) -> usize {
+    let mut types: Vec<(String, s::TypeRef)> = Vec::new();
+    types.extend(p.globals.iter().map(|g| (g.name.clone(), g.ty.clone())));
+    types.extend(p.global_vars.iter().map(|g| (g.name.clone(), g.ty.clone())));
+    types.extend(p.global_decls.iter().map(|g| (g.name.clone(), g.ty.clone())));
+    // a function's entry is its return type, for calls
+    types.extend(p.prototypes.iter().map(|f| (f.name.clone(), f.return_type.clone())));
+    types.extend(p.functions.iter().map(|f| (f.name.clone(), f.return_type.clone())));
+
+    let mut ph = Peephole { types, rewrites: 0 };
+    for g in &mut p.globals {
+        ph.expr(&mut g.init);
+    }
+    for g in &mut p.global_vars {
+        if let Some(init) = &mut g.init {
+            ph.expr(init);
+        }
+    }
+    let globals = ph.types.len();
+    for func in &mut p.functions {
+        // locals are unique within a function (the parser renames shadowing ones),
+        // so one flat list per function is enough
+        ph.types.extend(func.parameters.iter().map(|p| (p.name.clone(), p.ty.clone())));
+        declared(&func.body, &mut ph.types);
+        ph.stmts(&mut func.body);
+        ph.types.truncate(globals);
+    }
+    ph.rewrites
+}
+
+fn declared(body: &[s::Stmt], out: &mut Vec<(String, s::TypeRef)>) {
+    for st in body {
+        match st {
+            s::Stmt::VarDecl { name, ty, .. } | s::Stmt::ConstDecl { name, ty, .. } => {
+                out.push((name.clone(), ty.clone()));
+            }
+            s::Stmt::If { then_body, else_body, .. } => {
+                declared(then_body, out);
+                declared(else_body, out);
+            }
+            s::Stmt::While { body, .. } => declared(body, out),
+            s::Stmt::Switch { cases, .. } => {
+                for case in cases {
+                    declared(&case.body, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+struct Peephole {
+    // objects and functions by name, innermost last
+    types: Vec<(String, s::TypeRef)>,
+    rewrites: usize,
+}
+
+impl Peephole {
+    fn stmts(&mut self, body: &mut [s::Stmt]) {
+        for st in body {
+            self.stmt(st);
+        }
+    }
+
+    fn stmt(&mut self, st: &mut s::Stmt) {
+        match st {
+            s::Stmt::VarDecl { init: Some(e), .. }
+            | s::Stmt::ConstDecl { init: e, .. }
+            | s::Stmt::Assign { value: e, .. }
+            | s::Stmt::ExprStmt(e)
+            | s::Stmt::Return(Some(e)) => self.expr(e),
+            s::Stmt::Store { target, value } => {
+                self.expr(target);
+                self.expr(value);
+            }
+            s::Stmt::If { cond, then_body, else_body } => {
+                self.expr(cond);
+                self.stmts(then_body);
+                self.stmts(else_body);
+            }
+            s::Stmt::While { cond, body } => {
+                self.expr(cond);
+                self.stmts(body);
+            }
+            s::Stmt::Switch { value, cases } => {
+                self.expr(value);
+                for case in cases {
+                    self.stmts(&mut case.body);
+                }
+            }
+            s::Stmt::VarDecl { init: None, .. }
+            | s::Stmt::Break
+            | s::Stmt::Continue
+            | s::Stmt::Goto(_)
+            | s::Stmt::Label(_)
+            | s::Stmt::Return(None) => {}
+        }
+    }
+
+    fn expr(&mut self, e: &mut s::Expr) {
+        match e {
+            s::Expr::Lit(_) | s::Expr::Var(_) | s::Expr::IncDec { .. } => {}
+            s::Expr::Unary { expr, .. }
+            | s::Expr::Member { base: expr, .. }
+            | s::Expr::Cast { expr, .. } => self.expr(expr),
+            s::Expr::Binary { left, right, .. }
+            | s::Expr::Cmp { left, right, .. }
+            | s::Expr::Logical { left, right, .. }
+            | s::Expr::Comma { left, right } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            s::Expr::Call { args, .. } => {
+                for a in args {
+                    self.expr(a);
+                }
+            }
+            s::Expr::Ternary { cond, then_expr, else_expr } => {
+                self.expr(cond);
+                self.expr(then_expr);
+                self.expr(else_expr);
+            }
+        }
+        let simpler = match e {
+            s::Expr::Binary { left, op, right } if self.is_int(left) && self.is_int(right) => {
+                self.simplify(left, *op, right)
+            }
+            s::Expr::Cmp { left, op, right } if same_var(left, right) && self.is_int(left) => {
+                use s::CmpOpRef as C;
+                Some(s::Expr::Lit(s::Lit::Bool(matches!(op, C::Eq | C::Le | C::Ge))))
+            }
+            _ => None,
+        };
+        if let Some(simpler) = simpler {
+            *e = simpler;
+            self.rewrites += 1;
+        }
+    }
+
+    fn type_of(&self, name: &str) -> Option<&s::TypeRef> {
+        self.types.iter().rev().find(|(n, _)| n == name).map(|(_, ty)| ty)
+    }
+
+    // known to be an integer or pointer, never floating-point
+    fn is_int(&self, e: &s::Expr) -> bool {
+        let int_type = |ty: Option<&s::TypeRef>| {
+            matches!(ty, Some(s::TypeRef::Int { .. } | s::TypeRef::Pointer(_)))
+        };
+        match e {
+            s::Expr::Lit(lit) => matches!(lit, s::Lit::Int { .. }),
+            s::Expr::Var(name) | s::Expr::IncDec { name, .. } => int_type(self.type_of(name)),
+            s::Expr::Call { callee, .. } => int_type(self.type_of(callee)),
+            s::Expr::Cast { ty, .. } => int_type(Some(ty)),
+            s::Expr::Cmp { .. } | s::Expr::Logical { .. } => true,
+            s::Expr::Unary { op: s::UnOpRef::AddrOf, .. } => true,
+            s::Expr::Unary { op: s::UnOpRef::Neg | s::UnOpRef::BitNot, expr } => self.is_int(expr),
+            s::Expr::Binary { left, right, .. } => self.is_int(left) && self.is_int(right),
+            s::Expr::Comma { right, .. } => self.is_int(right),
+            s::Expr::Ternary { then_expr, else_expr, .. } => {
+                self.is_int(then_expr) && self.is_int(else_expr)
+            }
+            s::Expr::Unary { op: s::UnOpRef::Deref, .. } | s::Expr::Member { .. } => false,
+        }
+    }
+
+    fn simplify(&self, l: &mut s::Expr, op: s::BinOpRef, r: &mut s::Expr) -> Option<s::Expr> {
+        use s::BinOpRef as B;
+        // a literal operand has the expression's type, as the parser converted both to it
+        let take = |e: &mut s::Expr| std::mem::replace(e, s::Expr::Lit(s::Lit::Bool(false)));
+        match (op, int_lit(l), int_lit(r)) {
+            (B::Add | B::Sub | B::BitOr | B::BitXor | B::Shl | B::Shr, _, Some((0, _)))
+            | (B::Mul | B::Div, _, Some((1, _))) => Some(take(l)),
+            (B::Add | B::BitOr | B::BitXor, Some((0, _)), _) | (B::Mul, Some((1, _)), _) => {
+                Some(take(r))
+            }
+            (B::Mul | B::BitAnd, _, Some((0, zero))) => Some(keep_effects(take(l), zero)),
+            (B::Mul | B::BitAnd, Some((0, zero)), _) => Some(keep_effects(take(r), zero)),
+            (B::BitAnd | B::BitOr, _, _) if same_var(l, r) => Some(take(l)),
+            (B::Sub | B::BitXor, _, _) if same_var(l, r) => {
+                let s::Expr::Var(name) = &*l else { return None };
+                match self.type_of(name) {
+                    Some(&s::TypeRef::Int { bits, signed }) => {
+                        Some(s::Expr::Lit(s::Lit::Int { bits, signed, value: 0 }))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+// `(value, the literal itself)` for an integer literal
+fn int_lit(e: &s::Expr) -> Option<(i128, s::Expr)> {
+    match e {
+        s::Expr::Lit(s::Lit::Int { value, .. }) => Some((*value, e.clone())),
+        _ => None,
+    }
+}
+
+// `zero` in place of `dropped`, still evaluating `dropped` if it has side effects
+fn keep_effects(dropped: s::Expr, zero: s::Expr) -> s::Expr {
+    if crate::dce::is_pure(&dropped) {
+        zero
+    } else {
+        s::Expr::Comma { left: Box::new(dropped), right: Box::new(zero) }
+    }
+}
+
+fn same_var(l: &s::Expr, r: &s::Expr) -> bool {
+    matches!((l, r), (s::Expr::Var(a), s::Expr::Var(b)) if a == b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::dump_program;
+    use crate::parse::{parse_translation_unit, ParseOptions};
+    use crate::target::Target;
+
+    // what `return e;` simplifies to, one node per line
+    fn simplified(params: &str, e: &str) -> String {
+        let src = format!("int g(void); int f({params}) {{ return {e}; }}");
+        let mut p = parse_translation_unit(&src, &Target::X86_64, ParseOptions::LENIENT).unwrap().0;
+        simplify_program(&mut p);
+        let out = dump_program(&p);
+        out.lines().skip(3).map(|l| format!("{}\n", l.trim())).collect()
+    }
+
+    #[test]
+    fn identities() {
+        for e in ["x + 0", "0 + x", "x - 0", "x * 1", "1 * x", "x / 1", "x | 0", "x ^ 0", "x << 0"] {
+            assert_eq!(simplified("int x", e), "Var x\n", "{e}");
+        }
+        for e in ["x & x", "x | x"] {
+            assert_eq!(simplified("int x", e), "Var x\n", "{e}");
+        }
+        for e in ["x * 0", "0 * x", "x & 0", "x - x", "x ^ x"] {
+            assert_eq!(simplified("int x", e), "Int 0: i32\n", "{e}");
+        }
+        // `0 - x` is a negation, not an identity
+        assert_eq!(simplified("int x", "0 - x"), "Binary Sub\nInt 0: i32\nVar x\n");
+    }
+
+    #[test]
+    fn dropped_calls_are_kept() {
+        assert_eq!(simplified("void", "g() * 0"), "Comma\nCall g\nInt 0: i32\n");
+    }
+
+    #[test]
+    fn floats_are_left_alone() {
+        assert_eq!(simplified("double d", "d * 0"), "Binary Mul\nVar d\nFloat 0.0: f64\n");
+        assert_eq!(simplified("double d", "d + 0"), "Binary Add\nVar d\nFloat 0.0: f64\n");
+    }
+
+    #[test]
+    fn counts_rewrites() {
+        let src = "int f(int x) { return (x + 0) * 1; }";
+        let mut p = parse_translation_unit(src, &Target::X86_64, ParseOptions::LENIENT).unwrap().0;
+        assert_eq!(simplify_program(&mut p), 2);
+        assert_eq!(simplify_program(&mut p), 0);
+        assert_eq!(dump_program(&p), "fn f(x: i32) -> i32\n  Return\n    Var x\n");
+    }
+
+    #[test]
+    fn self_comparisons() {
+        for (e, value) in [("x == x", true), ("x <= x", true), ("x >= x", true)] {
+            assert_eq!(simplified("int x", e), format!("Bool {value}\n"), "{e}");
+        }
+        for (e, value) in [("x != x", false), ("x < x", false), ("x > x", false)] {
+            assert_eq!(simplified("int x", e), format!("Bool {value}\n"), "{e}");
+        }
+        assert_eq!(simplified("int *p", "p == p"), "Bool true\n");
+        // calls may differ from one to the next, and NaN isn't equal to itself
+        assert_eq!(simplified("void", "g() == g()"), "Cmp Eq\nCall g\nCall g\n");
+        assert_eq!(simplified("double d", "d == d"), "Cmp Eq\nVar d\nVar d\n");
+    }
+}
@@ -0,0 +1,260 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::lex::{LexError, Spanned, Tok};
+
+/// Runs the preprocessing directives in a lexed token stream and drops their
+/// lines. `#define NAME tokens...` records an object-like macro, and later
+/// uses of `NAME` are replaced by its tokens. `#if 0`/`#if 1`, `#else` and
+/// `#endif` include or drop the tokens between them; any other directive is an error.
+pub fn preprocess(toks: Vec<Spanned<Tok>>) -> Result<Vec<Spanned<Tok>>, LexError> {
+    let mut pp = Preprocessor { macros: Vec::new(), conds: Vec::new() };
+    let mut out = Vec::with_capacity(toks.len());
+    let mut prev_line = 0;
+    let mut i = 0;
+    while i < toks.len() {
+        let t = &toks[i];
+        // `#` only starts a directive as the first token on its line; a directive
+        // runs to the end of its logical line, past any `\`-newline continuations
+        if t.value == Tok::Hash && t.span.logical_line != prev_line {
+            let line = t.span.logical_line;
+            let len = toks[i..]
+                .iter()
+                .position(|t| t.span.logical_line != line || t.value == Tok::Eof)
+                .unwrap_or(toks.len() - i);
+            pp.directive(&toks[i..i + len])?;
+            prev_line = line;
+            i += len;
+            continue;
+        }
+        prev_line = t.span.logical_line;
+        if t.value == Tok::Eof {
+            if let Some(open) = pp.conds.last() {
+                return err_at(&open.at, "unterminated `#if`");
+            }
+        }
+        if pp.active() || t.value == Tok::Eof {
+            expand(t, &pp.macros, &mut Vec::new(), &mut out);
+        }
+        i += 1;
+    }
+    Ok(out)
+}
+
+fn err_at<T>(t: &Spanned<Tok>, msg: impl Into<String>) -> Result<T, LexError> {
+    Err(LexError { msg: msg.into(), line: t.span.line, col: t.span.col })
+}
+
+struct Preprocessor {
+    macros: Vec<(String, Vec<Tok>)>,
+    // open `#if`s, innermost last
+    conds: Vec<Cond>,
+}
+
+struct Cond {
+    // the `#if` token, for "unterminated" errors
+    at: Spanned<Tok>,
+    // tokens of the current branch are kept
+    taking: bool,
+    // some earlier branch was kept, or the whole `#if` sits in dropped code
+    done: bool,
+    seen_else: bool,
+}
+
+impl Preprocessor {
+    fn active(&self) -> bool {
+        self.conds.last().is_none_or(|c| c.taking)
+    }
+
+    // `line` is the whole directive, starting at its `#`
+    fn directive(&mut self, line: &[Spanned<Tok>]) -> Result<(), LexError> {
+        // a lone `#` is the null directive
+        let Some(kw) = line.get(1) else { return Ok(()) };
+        match &kw.value {
+            Tok::If => {
+                if !self.active() {
+                    // dropped code isn't evaluated, but still has to nest
+                    let at = kw.clone();
+                    self.conds.push(Cond { at, taking: false, done: true, seen_else: false });
+                    return Ok(());
+                }
+                let taking = match line.get(2).map(|t| &t.value) {
+                    Some(Tok::IntLit(0, _)) if line.len() == 3 => false,
+                    Some(Tok::IntLit(1, _)) if line.len() == 3 => true,
+                    _ => return err_at(kw, "only `#if 0` and `#if 1` are supported"),
+                };
+                self.conds.push(Cond { at: kw.clone(), taking, done: taking, seen_else: false });
+                Ok(())
+            }
+            Tok::Else => {
+                let Some(c) = self.conds.last_mut() else {
+                    return err_at(kw, "`#else` without `#if`");
+                };
+                if c.seen_else {
+                    return err_at(kw, "`#else` after `#else`");
+                }
+                c.seen_else = true;
+                c.taking = !c.done;
+                c.done = true;
+                Ok(())
+            }
+            Tok::Ident(d) if d == "endif" => match self.conds.pop() {
+                Some(_) => Ok(()),
+                None => err_at(kw, "`#endif` without `#if`"),
+            },
+            // `#ifdef` and friends open a block too, so dropped code skips them as a nested `#if`
+            Tok::Ident(d) if !self.active() && (d == "ifdef" || d == "ifndef") => {
+                let at = kw.clone();
+                self.conds.push(Cond { at, taking: false, done: true, seen_else: false });
+                Ok(())
+            }
+            _ if !self.active() => Ok(()),
+            Tok::Ident(d) if d == "define" => self.define(line),
+            Tok::Ident(d) => err_at(kw, format!("unsupported preprocessor directive `#{}`", d)),
+            _ => err_at(kw, "invalid preprocessor directive"),
+        }
+    }
+
+    fn define(&mut self, line: &[Spanned<Tok>]) -> Result<(), LexError> {
+        let Some(Spanned { value: Tok::Ident(name), span }) = line.get(2) else {
+            return err_at(&line[1], "expected a macro name after `#define`");
+        };
+        // `#define F(x)` with no space before the paren is a function-like macro
+        if let Some(p) = line.get(3).filter(|p| p.value == Tok::LParen && p.span.start == span.end) {
+            return err_at(p, "function-like macros are not supported");
+        }
+        let body: Vec<Tok> = line[3..].iter().map(|t| t.value.clone()).collect();
+        match self.macros.iter().find(|(m, _)| m == name) {
+            Some((_, prev)) if *prev != body => {
+                err_at(&line[2], format!("macro `{}` redefined with a different body", name))
+            }
+            Some(_) => Ok(()),
+            None => {
+                self.macros.push((name.clone(), body));
+                Ok(())
+            }
+        }
+    }
+}
+
+// `active` holds the macros being expanded; as in C, a macro's name is left
+// alone inside its own expansion, which also keeps cyclic definitions finite
+fn expand(
+    t: &Spanned<Tok>,
+    macros: &[(String, Vec<Tok>)],
+    active: &mut Vec<String>,
+    out: &mut Vec<Spanned<Tok>>,
+) {
+    if let Tok::Ident(name) = &t.value {
+        if let Some((_, body)) = macros.iter().find(|(m, _)| m == name && !active.contains(m)) {
+            active.push(name.clone());
+            // expanded tokens report the position of the use
+            for tok in body {
+                expand(&Spanned { value: tok.clone(), span: t.span }, macros, active, out);
+            }
+            active.pop();
+            return;
+        }
+    }
+    out.push(t.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::{lex_all, IntSuffix};
+
+    fn pp(src: &str) -> Result<Vec<Tok>, LexError> {
+        let toks = preprocess(lex_all(src).unwrap())?;
+        let mut out: Vec<Tok> = toks.into_iter().map(|t| t.value).collect();
+        assert_eq!(out.pop(), Some(Tok::Eof));
+        Ok(out)
+    }
+
+    fn ident(name: &str) -> Tok {
+        Tok::Ident(name.to_string())
+    }
+
+    fn int(v: i128) -> Tok {
+        Tok::IntLit(v, IntSuffix::default())
+    }
+
+    #[test]
+    fn object_macros() {
+        assert_eq!(
+            pp("#define N 4\n#define TWICE N + N\nint a[N]; x = TWICE;").unwrap(),
+            [
+                Tok::Int,
+                ident("a"),
+                Tok::LBracket,
+                int(4),
+                Tok::RBracket,
+                Tok::Semi,
+                ident("x"),
+                Tok::Assign,
+                int(4),
+                Tok::Plus,
+                int(4),
+                Tok::Semi,
+            ]
+        );
+        assert_eq!(pp("#define EMPTY\nEMPTY x").unwrap(), [ident("x")]);
+        // a macro isn't expanded inside itself, so cycles stay finite
+        assert_eq!(pp("#define A B\n#define B A\nA").unwrap(), [ident("A")]);
+        // an identical redefinition is fine, a different one isn't
+        assert!(pp("#define N 1\n#define N 1\n").is_ok());
+        assert!(pp("#define N 1\n#define N 2\n").unwrap_err().msg.contains("redefined"));
+        // `F (x)` with a space is an object-like macro whose body starts with `(`
+        assert!(pp("#define F(x) x\n").unwrap_err().msg.contains("function-like"));
+        assert_eq!(pp("#define F (x)\nF").unwrap(), [Tok::LParen, ident("x"), Tok::RParen]);
+    }
+
+    #[test]
+    fn expansions_report_the_use_site() {
+        let toks = preprocess(lex_all("#define N 42\n\n  N").unwrap()).unwrap();
+        assert_eq!((toks[0].span.line, toks[0].span.col), (3, 3));
+    }
+
+    #[test]
+    fn directive_errors() {
+        let e = pp("#define\n").unwrap_err();
+        assert_eq!((e.msg.as_str(), e.line, e.col), ("expected a macro name after `#define`", 1, 2));
+        assert!(pp("#include <stdio.h>").unwrap_err().msg.contains("unsupported"));
+        assert_eq!(pp("#\nx").unwrap(), [ident("x")]);
+    }
+
+    #[test]
+    fn directives_only_start_lines() {
+        // a `#` after other tokens on the line isn't a directive
+        assert_eq!(pp("x #").unwrap(), [ident("x"), Tok::Hash]);
+    }
+
+    #[test]
+    fn conditional_exclusion() {
+        assert_eq!(pp("1\n#if 0\nthis ) is ( not C\n#endif\n2").unwrap(), [int(1), int(2)]);
+        assert_eq!(pp("#if 0\n1\n#else\n2\n#endif").unwrap(), [int(2)]);
+        assert_eq!(pp("#if 1\n1\n#else\n2\n#endif").unwrap(), [int(1)]);
+        assert_eq!(pp("#if 1\n#if 1\n1\n#endif\n#if 0\n2\n#endif\n#endif").unwrap(), [int(1)]);
+        // nested blocks in dropped code still have to match up
+        assert_eq!(pp("#if 0\n#if 1\n1\n#endif\n2\n#endif\n3").unwrap(), [int(3)]);
+        assert_eq!(pp("#if 0\n#ifdef X\n#endif\n#endif\n3").unwrap(), [int(3)]);
+        // a `#define` in dropped code doesn't happen
+        assert_eq!(pp("#if 0\n#define N 1\n#endif\nN").unwrap(), [ident("N")]);
+    }
+
+    #[test]
+    fn conditional_errors() {
+        let e = pp("int x;\n#if 0\nint y;").unwrap_err();
+        assert_eq!((e.msg.as_str(), e.line), ("unterminated `#if`", 2));
+        assert!(pp("#endif").unwrap_err().msg.contains("without `#if`"));
+        assert!(pp("#else").unwrap_err().msg.contains("without `#if`"));
+        assert!(pp("#if 1\n#else\n#else\n#endif").unwrap_err().msg.contains("after `#else`"));
+        assert!(pp("#if X\n#endif").unwrap_err().msg.contains("only `#if 0` and `#if 1`"));
+    }
+
+    #[test]
+    fn continued_directives() {
+        assert_eq!(pp("#define N \\\n 7\nN").unwrap(), [int(7)]);
+        assert_eq!(pp("#define SUM 1 + \\\n 2 \\\n + 3\nSUM").unwrap().len(), 5);
+        assert_eq!(pp("#if 0 \\\n\n1\n#endif\n2").unwrap(), [int(2)]);
+    }
+}
@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ir::lower_ast::frontend as s;
+
+/// A compilation target: the IR triple and data layout, plus the widths of
+/// the C types on it. The parser sizes `long` & co. from here so the AST
+/// always agrees with the layout handed to lowering.
+#[derive(Clone, Copy, Debug)]
+pub struct Target {
+    pub triple: &'static str,
+    pub char_bits: u16,
+    pub short_bits: u16,
+    pub int_bits: u16,
+    pub long_bits: u16,
+    pub long_long_bits: u16,
+    pub float_bits: u16,
+    pub double_bits: u16,
+    pub ptr_bits: u16,
+    /// Cap on scalar alignment in bytes; i386 aligns 8-byte scalars to 4.
+    pub max_align: u64,
+}
+
+impl Target {
+    // LP64
+    pub const X86_64: Target = Target {
+        triple: "x86_64-whale-linux",
+        char_bits: 8,
+        short_bits: 16,
+        int_bits: 32,
+        long_bits: 64,
+        long_long_bits: 64,
+        float_bits: 32,
+        double_bits: 64,
+        ptr_bits: 64,
+        max_align: 8,
+    };
+
+    // ILP32
+    pub const I686: Target = Target {
+        triple: "i686-whale-linux",
+        char_bits: 8,
+        short_bits: 16,
+        int_bits: 32,
+        long_bits: 32,
+        long_long_bits: 64,
+        float_bits: 32,
+        double_bits: 64,
+        ptr_bits: 32,
+        max_align: 4,
+    };
+
+    pub const ALL: &'static [Target] = &[Target::X86_64, Target::I686];
+
+    pub fn from_triple(triple: &str) -> Option<Target> {
+        Target::ALL.iter().find(|t| t.triple == triple).copied()
+    }
+
+    pub fn data_layout(&self) -> ir::DataLayout {
+        ir::DataLayout { ptr_bits: self.ptr_bits.into(), ..ir::DataLayout::default_64bit_le() }
+    }
+
+    /// Size and alignment in bytes, or `None` for incomplete types
+    /// (`void`, unsized arrays, undefined structs).
+    pub fn size_align(&self, ty: &s::TypeRef, structs: &[s::StructDef]) -> Option<(u64, u64)> {
+        match ty {
+            s::TypeRef::Void => None,
+            s::TypeRef::Int { bits, .. } | s::TypeRef::Float { bits } => {
+                let n = (*bits as u64).div_ceil(8);
+                Some((n, n.min(self.max_align)))
+            }
+            s::TypeRef::Pointer(_) => {
+                let n = self.ptr_bits as u64 / 8;
+                Some((n, n.min(self.max_align)))
+            }
+            s::TypeRef::Array { len: 0, .. } => None,
+            s::TypeRef::Array { elem, len } => {
+                let (size, align) = self.size_align(elem, structs)?;
+                Some((size * len, align))
+            }
+            s::TypeRef::Struct(name) => {
+                let def = structs.iter().find(|d| &d.name == name)?;
+                Some((def.size, def.align))
+            }
+        }
+    }
+
+    /// Integer promotion: anything narrower than `int` computes as `int`.
+    pub fn promote(&self, ty: s::TypeRef) -> s::TypeRef {
+        match ty {
+            s::TypeRef::Int { bits, .. } if bits < self.int_bits => {
+                s::TypeRef::Int { bits: self.int_bits, signed: true }
+            }
+            ty => ty,
+        }
+    }
+
+    /// C's usual arithmetic conversions: the type both operands of a binary
+    /// arithmetic operator are converted to.
+    pub fn arith_type(&self, l: s::TypeRef, r: s::TypeRef) -> s::TypeRef {
+        match (self.promote(l), self.promote(r)) {
+            (s::TypeRef::Float { bits: a }, s::TypeRef::Float { bits: b }) => {
+                s::TypeRef::Float { bits: a.max(b) }
+            }
+            (f @ s::TypeRef::Float { .. }, _) | (_, f @ s::TypeRef::Float { .. }) => f,
+            (s::TypeRef::Int { bits: a, signed: sa }, s::TypeRef::Int { bits: b, signed: sb }) => {
+                if a == b {
+                    s::TypeRef::Int { bits: a, signed: sa && sb }
+                } else if a > b {
+                    s::TypeRef::Int { bits: a, signed: sa }
+                } else {
+                    s::TypeRef::Int { bits: b, signed: sb }
+                }
+            }
+            (l, _) => l,
+        }
+    }
+
+    /// Lays out `fields` in declaration order, padding each to its
+    /// alignment and the whole struct to its largest member.
+    pub fn layout_struct(
+        &self,
+        name: String,
+        fields: Vec<(String, s::TypeRef)>,
+        structs: &[s::StructDef],
+    ) -> Option<s::StructDef> {
+        let (mut size, mut align) = (0u64, 1u64);
+        let mut out = Vec::with_capacity(fields.len());
+        for (fname, ty) in fields {
+            let (fsize, falign) = self.size_align(&ty, structs)?;
+            let offset = size.next_multiple_of(falign);
+            out.push(s::StructField { name: fname, ty, offset });
+            size = offset + fsize;
+            align = align.max(falign);
+        }
+        Some(s::StructDef { name, fields: out, size: size.next_multiple_of(align), align })
+    }
+}
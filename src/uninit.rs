@@ -0,0 +1,322 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ir::lower_ast::frontend as s;
+use std::collections::BTreeSet;
+
+/// Finds reads of scalar locals that were declared without an initializer
+/// and are not assigned on every path to the read. Returns one message per
+/// variable and function: "is used uninitialized" when no path assigns it,
+/// "may be used uninitialized" when only some do.
+pub fn check_program(p: &s::Program) -> Vec<String> {
+    let mut out = Vec::new();
+    for func in &p.functions {
+        let mut a = Analysis {
+            func: &func.name,
+            tracked: BTreeSet::new(),
+            breaks: Vec::new(),
+            continues: Vec::new(),
+            labels: Vec::new(),
+            warned: BTreeSet::new(),
+            quiet: 0,
+            out: &mut out,
+        };
+        a.stmts(&func.body, Some(State::default()));
+    }
+    out
+}
+
+/// Definedness at one program point: `must` is assigned on every path,
+/// `may` on at least one.
+#[derive(Clone, Default)]
+struct State {
+    must: BTreeSet<String>,
+    may: BTreeSet<String>,
+}
+
+// `None` is an unreachable point, which joins as the identity
+fn join(a: Option<State>, b: Option<State>) -> Option<State> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(State {
+            must: a.must.intersection(&b.must).cloned().collect(),
+            may: a.may.union(&b.may).cloned().collect(),
+        }),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+struct Analysis<'a> {
+    func: &'a str,
+    // locals declared without an initializer; only these can be read uninitialized
+    tracked: BTreeSet<String>,
+    // state at each `break` of the enclosing loops/switches, innermost last
+    breaks: Vec<Option<State>>,
+    continues: Vec<Option<State>>,
+    // state at forward `goto`s, joined in at the label
+    labels: Vec<(String, Option<State>)>,
+    warned: BTreeSet<String>,
+    // > 0 while a loop body is walked only to learn what it assigns
+    quiet: usize,
+    out: &'a mut Vec<String>,
+}
+
+impl Analysis<'_> {
+    fn stmts(&mut self, body: &[s::Stmt], mut st: Option<State>) -> Option<State> {
+        for stmt in body {
+            st = self.stmt(stmt, st);
+        }
+        st
+    }
+
+    fn stmt(&mut self, stmt: &s::Stmt, st: Option<State>) -> Option<State> {
+        match stmt {
+            s::Stmt::VarDecl { name, ty, init } => {
+                let mut st = st;
+                if let Some(init) = init {
+                    self.expr(init, &st);
+                    assign(&mut st, name);
+                } else if is_scalar(ty) {
+                    // a redeclaration in a loop body starts out uninitialized again
+                    self.tracked.insert(name.clone());
+                    if let Some(st) = &mut st {
+                        st.must.remove(name);
+                        st.may.remove(name);
+                    }
+                }
+                st
+            }
+            s::Stmt::ConstDecl { name, init, .. } | s::Stmt::Assign { name, value: init } => {
+                self.expr(init, &st);
+                let mut st = st;
+                assign(&mut st, name);
+                st
+            }
+            s::Stmt::Store { target, value } => {
+                self.expr(value, &st);
+                // writing a member of a local is not a read of it
+                let mut base = target;
+                while let s::Expr::Member { base: b, .. } = base {
+                    base = b;
+                }
+                let mut st = st;
+                match base {
+                    s::Expr::Var(name) => assign(&mut st, name),
+                    _ => self.expr(target, &st),
+                }
+                st
+            }
+            s::Stmt::ExprStmt(e) => {
+                self.expr(e, &st);
+                st
+            }
+            s::Stmt::If { cond, then_body, else_body } => {
+                self.expr(cond, &st);
+                let then_st = self.stmts(then_body, st.clone());
+                let else_st = self.stmts(else_body, st);
+                join(then_st, else_st)
+            }
+            s::Stmt::While { cond, body } => {
+                // later iterations also see what earlier ones assigned
+                self.quiet += 1;
+                let (back, _) = self.loop_body(body, st.clone());
+                self.quiet -= 1;
+                let head = join(st, back);
+
+                self.expr(cond, &head);
+                let (back, broke) = self.loop_body(body, head.clone());
+                // `while (true)` only leaves through a `break`
+                match cond {
+                    s::Expr::Lit(s::Lit::Bool(true)) => broke,
+                    _ => join(join(head, back), broke),
+                }
+            }
+            s::Stmt::Switch { value, cases } => {
+                self.expr(value, &st);
+                self.breaks.push(None);
+                let mut fall = None;
+                for case in cases {
+                    fall = self.stmts(&case.body, join(st.clone(), fall));
+                }
+                let broke = self.breaks.pop().flatten();
+                let exit = join(fall, broke);
+                if cases.iter().any(|c| c.label.is_none()) {
+                    exit
+                } else {
+                    join(exit, st)
+                }
+            }
+            s::Stmt::Break => {
+                if let Some(b) = self.breaks.last_mut() {
+                    *b = join(b.take(), st);
+                }
+                None
+            }
+            s::Stmt::Continue => {
+                if let Some(c) = self.continues.last_mut() {
+                    *c = join(c.take(), st);
+                }
+                None
+            }
+            s::Stmt::Goto(label) => {
+                match self.labels.iter_mut().find(|(l, _)| l == label) {
+                    Some((_, at)) => *at = join(at.take(), st),
+                    None => self.labels.push((label.clone(), st)),
+                }
+                None
+            }
+            s::Stmt::Label(label) => {
+                let from_goto = self.goto_state(label);
+                // backward gotos haven't been seen yet; rather than guess, assume
+                // they assign everything so they never cause a false positive
+                match (st, from_goto) {
+                    (None, None) => {
+                        Some(State { must: self.tracked.clone(), may: self.tracked.clone() })
+                    }
+                    (st, from_goto) => join(st, from_goto),
+                }
+            }
+            s::Stmt::Return(value) => {
+                if let Some(e) = value {
+                    self.expr(e, &st);
+                }
+                None
+            }
+        }
+    }
+
+    fn goto_state(&self, label: &str) -> Option<State> {
+        self.labels.iter().find(|(l, _)| l == label).and_then(|(_, at)| at.clone())
+    }
+
+    // (state flowing back to the loop head, state at the `break`s)
+    fn loop_body(&mut self, body: &[s::Stmt], st: Option<State>) -> (Option<State>, Option<State>) {
+        self.breaks.push(None);
+        self.continues.push(None);
+        let end = self.stmts(body, st);
+        let continued = self.continues.pop().flatten();
+        let broke = self.breaks.pop().flatten();
+        (join(end, continued), broke)
+    }
+
+    fn expr(&mut self, e: &s::Expr, st: &Option<State>) {
+        match e {
+            s::Expr::Lit(_) => {}
+            s::Expr::Var(name) | s::Expr::IncDec { name, .. } => self.read(name, st),
+            // once its address escapes, stores through the pointer can't be tracked
+            s::Expr::Unary { op: s::UnOpRef::AddrOf, expr } => {
+                let mut base = &**expr;
+                while let s::Expr::Member { base: b, .. } = base {
+                    base = b;
+                }
+                match base {
+                    s::Expr::Var(name) => {
+                        self.tracked.remove(name);
+                    }
+                    _ => self.expr(expr, st),
+                }
+            }
+            s::Expr::Unary { expr, .. }
+            | s::Expr::Member { base: expr, .. }
+            | s::Expr::Cast { expr, .. } => self.expr(expr, st),
+            s::Expr::Binary { left, right, .. }
+            | s::Expr::Cmp { left, right, .. }
+            | s::Expr::Logical { left, right, .. }
+            | s::Expr::Comma { left, right } => {
+                self.expr(left, st);
+                self.expr(right, st);
+            }
+            s::Expr::Call { args, .. } => {
+                for a in args {
+                    self.expr(a, st);
+                }
+            }
+            s::Expr::Ternary { cond, then_expr, else_expr } => {
+                self.expr(cond, st);
+                self.expr(then_expr, st);
+                self.expr(else_expr, st);
+            }
+        }
+    }
+
+    fn read(&mut self, name: &str, st: &Option<State>) {
+        let Some(st) = st else { return };
+        if self.quiet > 0 || !self.tracked.contains(name) || st.must.contains(name) {
+            return;
+        }
+        if self.warned.contains(name) {
+            return;
+        }
+        let how = if st.may.contains(name) { "may be" } else { "is" };
+        // a shadowing local is lowered as `x.1`; report it under its source name
+        let shown = name.split('.').next().unwrap_or(name);
+        self.out.push(format!("`{}` {} used uninitialized in `{}`", shown, how, self.func));
+        self.warned.insert(name.to_string());
+    }
+}
+
+fn assign(st: &mut Option<State>, name: &str) {
+    if let Some(st) = st {
+        st.must.insert(name.to_string());
+        st.may.insert(name.to_string());
+    }
+}
+
+// arrays and structs are written piecewise, so only whole-value types are tracked
+fn is_scalar(ty: &s::TypeRef) -> bool {
+    matches!(ty, s::TypeRef::Int { .. } | s::TypeRef::Float { .. } | s::TypeRef::Pointer(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse_translation_unit, ParseOptions};
+    use crate::target::Target;
+
+    fn check(src: &str) -> Vec<String> {
+        check_program(&parse_translation_unit(src, &Target::X86_64, ParseOptions::LENIENT).unwrap().0)
+    }
+
+    #[test]
+    fn definite_use() {
+        assert_eq!(check("int f() { int x; return x; }"), ["`x` is used uninitialized in `f`"]);
+    }
+
+    #[test]
+    fn maybe_use() {
+        assert_eq!(
+            check("int f(int c) { int x; if (c) x = 1; return x; }"),
+            ["`x` may be used uninitialized in `f`"]
+        );
+        assert_eq!(
+            check("int f(int c) { int x; while (c) { x = 1; c--; } return x; }"),
+            ["`x` may be used uninitialized in `f`"]
+        );
+    }
+
+    #[test]
+    fn every_path_assigns() {
+        assert!(check("int f(int c) { int x; if (c) x = 1; else x = 2; return x; }").is_empty());
+        assert!(check("int f() { int x = 1; return x; }").is_empty());
+        assert!(check("int f(int c) { int x; x = c; return x + x; }").is_empty());
+        assert!(check("int f(int c) { int x; if (c) return 0; x = 1; return x; }").is_empty());
+    }
+
+    #[test]
+    fn switch_paths() {
+        let src = "int f(int c) { int x; switch (c) { case 1: x = 1; break; default: x = 2; } \
+                   return x; }";
+        assert!(check(src).is_empty());
+        let src = "int f(int c) { int x; switch (c) { case 1: x = 1; break; } return x; }";
+        assert_eq!(check(src), ["`x` may be used uninitialized in `f`"]);
+    }
+
+    #[test]
+    fn address_taken_locals_are_not_tracked() {
+        assert!(check("void g(int *p); int f() { int x; g(&x); return x; }").is_empty());
+    }
+
+    #[test]
+    fn one_message_per_variable() {
+        assert_eq!(check("int f() { int x; int y = x; return x + y; }").len(), 1);
+    }
+}
@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use whale_c::target::Target;
+use whale_c::{compile_to_ir, CompileError};
+
+#[test]
+fn compiles_a_valid_program() {
+    let src = "int add(int a, int b) { return a + b; }\n\
+               int main(void) { int s = 0; int i; for (i = 0; i < 4; i++) s += add(i, 1); return s; }\n";
+    assert!(compile_to_ir(src, &Target::X86_64).is_ok());
+    assert!(compile_to_ir(src, &Target::I686).is_ok());
+}
+
+#[test]
+fn compiles_the_demo() {
+    let src = include_str!("../examples/demo.c");
+    if let Err(e) = compile_to_ir(src, &Target::X86_64) {
+        panic!("{e}");
+    }
+}
+
+#[test]
+fn reports_every_parse_error() {
+    let src = "int f(void) { return 1 + ; }\nint g(void) { return y; }\nint h(void) { return 2; }\n";
+    let Err(CompileError::Parse(errors)) = compile_to_ir(src, &Target::X86_64) else {
+        panic!("expected parse errors");
+    };
+    let msgs: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        msgs,
+        ["expected expression, got ';' (1:26)", "use of undeclared identifier `y` (2:22)"]
+    );
+    let shown = CompileError::Parse(errors).to_string();
+    assert_eq!(shown.lines().count(), 2);
+    assert!(shown.starts_with("parse error: expected expression"));
+}
+
+#[test]
+fn lex_errors_are_parse_errors() {
+    let Err(CompileError::Parse(errors)) = compile_to_ir("int x = 0x;", &Target::X86_64) else {
+        panic!("expected a lex error");
+    };
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].to_string().contains("missing digits"), "{}", errors[0]);
+}
+
+#[test]
+fn warnings_do_not_fail() {
+    assert!(compile_to_ir("int f(void) { char c = 300; return c; }", &Target::X86_64).is_ok());
+}
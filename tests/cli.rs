@@ -0,0 +1,258 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+// a scratch file for one test, named after it so parallel tests don't collide
+fn source(name: &str, text: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("whale-c-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_whale-c"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stdout).into_owned()
+}
+
+fn stderr(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stderr).into_owned()
+}
+
+#[test]
+fn emit_tokens() {
+    let path = source("tokens.c", "int x;\n  return 0x1F;\n");
+    let out = run(&["--emit=tokens", path.to_str().unwrap()], "");
+    assert_eq!(out.status.code(), Some(0));
+    let want = "1:1\tInt\n1:5\tIdent(\"x\")\n1:6\tSemi\n2:3\tReturn\n\
+                2:10\tIntLit(31, IntSuffix { unsigned: false, longs: 0 })\n2:14\tSemi\n3:1\tEof\n";
+    assert_eq!(stdout(&out), want);
+}
+
+#[test]
+fn bad_arguments() {
+    let path = source("args.c", "int main() { return 0; }\n");
+    let path = path.to_str().unwrap();
+    assert_eq!(run(&["--emit=nope", path], "").status.code(), Some(2));
+    assert_eq!(run(&["--bogus", path], "").status.code(), Some(2));
+    assert_eq!(run(&[], "").status.code(), Some(2));
+}
+
+#[test]
+fn exit_status() {
+    let ok = source("ok.c", "int main(void) { return 0; }\n");
+    let bad = source("bad.c", "int main(void) { return 1 + ; }\n");
+    let ok = ok.to_str().unwrap();
+    assert_eq!(run(&[ok], "").status.code(), Some(0));
+    assert_eq!(run(&[bad.to_str().unwrap()], "").status.code(), Some(10));
+    assert_eq!(run(&["--bogus", ok], "").status.code(), Some(2));
+    assert_eq!(run(&["no-such-file.c"], "").status.code(), Some(2));
+}
+
+#[test]
+fn json_diagnostics() {
+    let bad = source("json.c", "int main(void) { return 1 + ; }\n");
+    let path = bad.to_str().unwrap();
+    let out = run(&["--diagnostics=json", path], "");
+    assert_eq!(out.status.code(), Some(10));
+    let want = format!(
+        "{{\"severity\":\"error\",\"message\":\"expected expression, got ';'\",\"file\":\"{}\",\
+         \"line\":1,\"col\":29}}\n",
+        path
+    );
+    assert_eq!(stderr(&out), want);
+}
+
+#[test]
+fn partial_ast_after_errors() {
+    let src = "int f(void) { return 1 + ; }\nint g(void) { return 2; }\n";
+    let out = run(&["--emit=ast", "-"], src);
+    assert_eq!(out.status.code(), Some(10));
+    assert_eq!(stdout(&out), "fn g() -> i32\n  Return\n    Int 2: i32\n");
+    assert!(stderr(&out).contains("expected expression"), "{}", stderr(&out));
+}
+
+#[test]
+fn emit_ast() {
+    let path = source("ast.c", "int k() { return 1; }\n");
+    let out = run(&["--emit=ast", path.to_str().unwrap()], "");
+    assert_eq!(out.status.code(), Some(0));
+    assert_eq!(stdout(&out), "fn k() -> i32\n  Return\n    Int 1: i32\n");
+}
+
+#[test]
+fn writes_the_output_file() {
+    let path = source("out.c", "int k() { return 1; }\n");
+    let out_file = path.with_extension("ast");
+    let out = run(&["--emit=ast", "-o", out_file.to_str().unwrap(), path.to_str().unwrap()], "");
+    assert_eq!(out.status.code(), Some(0));
+    assert_eq!(stdout(&out), "");
+    let text = std::fs::read_to_string(&out_file).unwrap();
+    assert_eq!(text, "fn k() -> i32\n  Return\n    Int 1: i32\n");
+
+    // `-o -` is stdout
+    let out = run(&["--emit=ast", "-o", "-", path.to_str().unwrap()], "");
+    assert_eq!(stdout(&out), text);
+    assert_eq!(run(&[path.to_str().unwrap(), "-o"], "").status.code(), Some(2));
+}
+
+#[test]
+fn reads_stdin() {
+    let out = run(&["--emit=ast", "-"], "int k() { return 1; }\n");
+    assert_eq!(out.status.code(), Some(0));
+    assert_eq!(stdout(&out), "fn k() -> i32\n  Return\n    Int 1: i32\n");
+
+    // errors name the input
+    let out = run(&["-"], "int k() { return 1 + ; }\n");
+    assert!(stderr(&out).starts_with("<stdin>: parse error: "), "{}", stderr(&out));
+}
+
+#[test]
+fn several_files() {
+    let a = source("a.c", "int g(int); int main() { return g(2); }\n");
+    let b = source("b.c", "int g(int x) { return x * 2; }\n");
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+    let out = run(&["--emit=ast", a, b], "");
+    assert_eq!(out.status.code(), Some(0));
+    assert!(stdout(&out).contains("fn main()") && stdout(&out).contains("fn g(x: i32)"));
+    let out = run(&[a, b, b], "");
+    assert_eq!(out.status.code(), Some(1));
+    assert!(stderr(&out).contains("duplicate definition of `g`"), "{}", stderr(&out));
+}
+
+#[test]
+fn stats() {
+    let src = source("stats.c", "int main(void) { return 2 + 3; }\n");
+    let out = run(&["-O1", "--stats", src.to_str().unwrap()], "");
+    assert_eq!(out.status.code(), Some(0));
+    assert!(stderr(&out).contains("constant_fold: folded 1"), "{}", stderr(&out));
+}
+
+#[test]
+fn optimization_levels() {
+    let src = source("levels.c", "int main() { return 2 + 3; }\n");
+    let src = src.to_str().unwrap();
+    assert!(stdout(&run(&["--emit=ast", src], "")).contains("Binary Add"));
+    assert!(stdout(&run(&["--emit=ast", "-O0", src], "")).contains("Binary Add"));
+    assert!(stdout(&run(&["--emit=ast", "-O1", src], "")).contains("Int 5: i32"));
+    assert!(stdout(&run(&["--emit=ast", "-O2", src], "")).contains("Int 5: i32"));
+    assert!(stdout(&run(&["--help"], "")).contains("-O1  -O0 + constant folding"));
+}
+
+#[test]
+fn warnings_do_not_fail_the_build() {
+    let src = source("uninit.c", "int main() { int x; return x; }\n");
+    let out = run(&[src.to_str().unwrap()], "");
+    assert_eq!(out.status.code(), Some(0));
+    assert!(stderr(&out).contains("warning: `x` is used uninitialized in `main`"), "{}", stderr(&out));
+}
+
+#[test]
+fn werror() {
+    let src = source("werror.c", "int main() { return 0; return 1; }\n");
+    let src = src.to_str().unwrap();
+    let out = run(&[src], "");
+    assert_eq!(out.status.code(), Some(0));
+    assert!(stderr(&out).contains("warning: unreachable statement after `return` (1:24)"));
+    let out = run(&["--werror", src], "");
+    assert_eq!(out.status.code(), Some(1));
+    assert!(stderr(&out).contains("error: warnings treated as errors (--werror)"));
+}
+
+#[test]
+fn targets() {
+    let src = source("target.c", "long k() { return sizeof(long); }\n");
+    let src = src.to_str().unwrap();
+    let out = run(&["--emit=ast", "-O1", "--target=i686-whale-linux", src], "");
+    assert_eq!(stdout(&out), "fn k() -> i32\n  Return\n    Int 4: u32\n");
+    let out = run(&["--emit=ast", "-O1", "--target=x86_64-whale-linux", src], "");
+    assert_eq!(stdout(&out), "fn k() -> i64\n  Return\n    Int 8: u64\n");
+    let out = run(&["--target=pdp11", src], "");
+    assert_eq!(out.status.code(), Some(2));
+    assert!(stderr(&out).contains("supported: x86_64-whale-linux, i686-whale-linux"));
+}
+
+#[test]
+fn diagnostics_show_the_line() {
+    let bad = source("caret.c", "int main() { return 1 + ; }\n");
+    let out = run(&[bad.to_str().unwrap()], "");
+    let err = stderr(&out);
+    assert!(err.contains("parse error: expected expression, got ';' (1:25)"), "{err}");
+    let caret = format!(" 1 | int main() {{ return 1 + ; }}\n   | {:24}^\n", "");
+    assert!(err.contains(&caret), "{err}");
+}
+
+#[test]
+fn emit_cfg() {
+    let out = run(&["--emit=cfg", "-"], "int k() { return 1; }");
+    assert_eq!(
+        stdout(&out),
+        "digraph cfg {\n  node [shape=box, fontname=\"monospace\"];\n  subgraph \"cluster_k\" {\n    \
+         label=\"k\";\n    \"k.0\" [label=\"entry\\lreturn 1\\l\"];\n    \
+         \"k.1\" [label=\"exit\\l\"];\n    \"k.0\" -> \"k.1\";\n  }\n}\n"
+    );
+}
+
+#[test]
+fn unknown_emit_kind_lists_the_valid_ones() {
+    let out = run(&["--emit=nope", "-"], "");
+    assert_eq!(out.status.code(), Some(2));
+    let err = stderr(&out);
+    assert!(err.contains("unknown --emit kind `nope`; expected one of: tokens, ast, cfg, ir"), "{err}");
+}
+
+#[test]
+fn unsigned_comparison_operands() {
+    let src = "int main() { return 0xFFFFFFFFu < 1u; }";
+    let unfolded = stdout(&run(&["--emit=ast", "-"], src));
+    assert!(unfolded.contains("Cmp Lt\n      Int 4294967295: u32\n      Int 1: u32\n"), "{unfolded}");
+    assert!(stdout(&run(&["--emit=ast", "-O1", "-"], src)).contains("Return\n    Bool false\n"));
+}
+
+#[test]
+fn std_switch() {
+    let src = "int main() { return 0; }";
+    assert_eq!(run(&["--emit=ast", "-"], src).status.code(), Some(0));
+    assert_eq!(run(&["--emit=ast", "--std=lenient", "-"], src).status.code(), Some(0));
+    let out = run(&["--emit=ast", "--std=strict", "-"], src);
+    assert_eq!(out.status.code(), Some(10));
+    assert!(stderr(&out).contains("without a prototype"));
+    assert_eq!(run(&["--std=c11", "-"], "").status.code(), Some(2));
+}
+
+#[test]
+fn std_c89() {
+    let src = "int main(void) { int a = 1; a++; int b = a; return b; }";
+    assert_eq!(run(&["--emit=ast", "-"], src).status.code(), Some(0));
+    let out = run(&["--emit=ast", "--std=c89", "-"], src);
+    assert_eq!(out.status.code(), Some(10));
+    assert!(stderr(&out).contains("declaration after a statement"));
+    assert_eq!(run(&["--emit=ast", "--std=c90", "-"], src).status.code(), Some(10));
+}
+
+#[test]
+fn print_hex() {
+    let src = "int f(int x) { return (x & 0x7fff0000) + -1; }";
+    let decimal = stdout(&run(&["--emit=ast", "-"], src));
+    let out = run(&["--emit=ast", "--print-hex", "-"], src);
+    assert_eq!(out.status.code(), Some(0));
+    let hex = stdout(&out);
+    assert!(decimal.contains("Int 2147418112: i32") && decimal.contains("Int -1: i32"), "{decimal}");
+    assert!(hex.contains("Int 0x7fff0000: i32") && hex.contains("Int -0x1: i32"), "{hex}");
+    assert_eq!(hex.replace("0x7fff0000", "2147418112").replace("-0x1", "-1"), decimal);
+    assert_eq!(run(&["--print-hex", "-"], "").status.code(), Some(2));
+}